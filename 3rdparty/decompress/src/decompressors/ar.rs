@@ -1,4 +1,5 @@
 use crate::decompressors::utils::normalize_mode;
+use crate::sanitize::{check_entry_path, check_outpath, remove_link};
 use crate::{DecompressError, Decompression, Decompressor, ExtractOpts, Listing};
 use ar::Archive;
 use lazy_static::lazy_static;
@@ -20,6 +21,67 @@ fn build_archive(archive: &Path) -> Result<Archive<Box<dyn Read>>, DecompressErr
     let out: Archive<Box<dyn Read>> = Archive::new(Box::new(fd));
     Ok(out)
 }
+
+fn entry_path(header: &ar::Header) -> PathBuf {
+    #[cfg(windows)]
+    {
+        PathBuf::from(String::from_utf8_lossy(header.identifier()).to_string())
+    }
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::prelude::OsStrExt;
+        PathBuf::from(OsStr::from_bytes(header.identifier()))
+    }
+}
+
+fn is_plain_name(path: &Path) -> bool {
+    let mut components = path.components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+///
+/// Extract the first member whose name starts with `prefix` into the `to` folder,
+/// e.g. `data.tar` finds `data.tar.gz`, `data.tar.zst` or a plain `data.tar` in a .deb.
+/// The member keeps its name, so it can be unpacked with a stack that detects it
+/// by content or by name.
+///
+/// Returns `None` if no member matched.
+///
+/// # Errors
+///
+/// This function will return an error if reading the archive or writing the member fails.
+pub fn extract_member(
+    archive: &Path,
+    prefix: &str,
+    to: &Path,
+) -> Result<Option<PathBuf>, DecompressError> {
+    let mut out = build_archive(archive)?;
+
+    while let Some(entry) = out.next_entry() {
+        let entry = entry?;
+        let filepath = entry_path(entry.header());
+
+        if !is_plain_name(&filepath) || !filepath.to_string_lossy().starts_with(prefix) {
+            continue;
+        }
+
+        if !to.exists() {
+            fs::create_dir_all(to)?;
+        }
+
+        let outpath = to.join(filepath);
+        let mut outfile = fs::File::create(&outpath)?;
+        io::copy(&mut BufReader::new(entry), &mut outfile)?;
+        return Ok(Some(outpath));
+    }
+
+    Ok(None)
+}
+
 #[derive(Default)]
 pub struct Ar {
     re: Option<Regex>,
@@ -53,20 +115,7 @@ impl Decompressor for Ar {
         let mut entries = vec![];
         while let Some(entry) = out.next_entry() {
             let entry = entry?;
-            let header = entry.header();
-
-            let filepath = {
-                #[cfg(windows)]
-                {
-                    PathBuf::from(String::from_utf8_lossy(header.identifier()).to_string())
-                }
-                #[cfg(unix)]
-                {
-                    use std::ffi::OsStr;
-                    use std::os::unix::prelude::OsStrExt;
-                    PathBuf::from(OsStr::from_bytes(header.identifier()))
-                }
-            };
+            let filepath = entry_path(entry.header());
             entries.push(filepath.to_string_lossy().to_string());
         }
         Ok(Listing { id: "ar", entries })
@@ -88,27 +137,8 @@ impl Decompressor for Ar {
         // alternative impl: just unpack, and then mv everything back X levels
         while let Some(entry) = out.next_entry() {
             let entry = entry?;
-            let header = entry.header();
-
-            let filepath = {
-                #[cfg(windows)]
-                {
-                    PathBuf::from(String::from_utf8_lossy(header.identifier()).to_string())
-                }
-                #[cfg(unix)]
-                {
-                    use std::ffi::OsStr;
-                    use std::os::unix::prelude::OsStrExt;
-                    PathBuf::from(OsStr::from_bytes(header.identifier()))
-                }
-            };
-
-            if filepath.components().any(|component| match component {
-                Component::ParentDir | Component::RootDir | Component::Prefix(..) => true,
-                Component::Normal(..) | Component::CurDir => false,
-            }) {
-                continue;
-            }
+            let filepath = entry_path(entry.header());
+            check_entry_path(&filepath)?;
 
             // guess what, ar archives don't support components, only 1 level is there, so stripping not relevant!
             // so does create_dir_all'isms
@@ -116,10 +146,12 @@ impl Decompressor for Ar {
             // because we potentially stripped a component, we may have an empty path, in which case
             // the joined target will be identical to the target folder
             // we take this approach to avoid hardcoding a check against empty ""
-            let outpath = to.join(filepath);
+            let outpath = to.join(&filepath);
             if to == outpath {
                 continue;
             }
+            check_outpath(to, &outpath, &filepath)?;
+            remove_link(&outpath)?;
 
             #[cfg(unix)]
             let mode = entry.header().mode();
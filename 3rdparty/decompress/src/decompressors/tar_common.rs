@@ -6,7 +6,8 @@ use std::{
 };
 
 use crate::decompressors::utils::normalize_mode;
-use crate::{DecompressError, ExtractOpts};
+use crate::sanitize::{check_entry_path, check_outpath, create_symlink, remove_link};
+use crate::{DecompressError, ExtractOpts, UnsafeEntryReason};
 use tar::Archive;
 
 pub fn tar_list(out: &mut Archive<Box<dyn Read>>) -> Result<Vec<String>, DecompressError> {
@@ -34,11 +35,15 @@ pub fn tar_extract(
     // alternative impl: just unpack, and then mv everything back X levels
     for entry in out.entries()? {
         let entry = entry?;
-        let filepath = entry.path()?;
+        let entry_path = entry.path()?.into_owned();
+        check_entry_path(&entry_path)?;
 
         // strip prefixed components. this can be 0 parts, in which case strip does not happen.
         // it's done for when archives contain an enclosing folder
-        let filepath = filepath.components().skip(opts.strip).collect::<PathBuf>();
+        let filepath = entry_path
+            .components()
+            .skip(opts.strip)
+            .collect::<PathBuf>();
 
         // because we potentially stripped a component, we may have an empty path, in which case
         // the joined target will be identical to the target folder
@@ -54,28 +59,68 @@ pub fn tar_extract(
 
         let outpath: Cow<'_, Path> = (opts.map)(outpath.as_path());
 
-        if entry.header().entry_type() != tar::EntryType::Directory {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p)?;
+        let entry_type = entry.header().entry_type();
+        if entry_type == tar::EntryType::Directory {
+            continue;
+        }
+
+        // devices, fifos and the like have no business in an unpacked archive
+        let is_regular = matches!(
+            entry_type,
+            tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::GNUSparse
+        );
+        if !is_regular && !entry_type.is_symlink() && !entry_type.is_hard_link() {
+            continue;
+        }
+
+        check_outpath(to, &outpath, &entry_path)?;
+        if let Some(p) = outpath.parent() {
+            if !p.exists() {
+                fs::create_dir_all(p)?;
+            }
+        }
+
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| DecompressError::Error("link without a target".to_string()))?
+                .into_owned();
+
+            if entry_type.is_symlink() {
+                create_symlink(to, &outpath, &target, &entry_path)?;
+            } else {
+                // hard links name another entry, a copy of it keeps the result free of links
+                check_entry_path(&target)?;
+                let source = to.join(target.components().skip(opts.strip).collect::<PathBuf>());
+                let source = (opts.map)(source.as_path()).canonicalize()?;
+                if !source.starts_with(to.canonicalize()?) {
+                    return Err(DecompressError::UnsafeEntry {
+                        entry: entry_path.to_string_lossy().to_string(),
+                        reason: UnsafeEntryReason::ResolvesOutside,
+                    });
                 }
+                remove_link(&outpath)?;
+                fs::copy(&source, &outpath)?;
             }
+            files.push(outpath.to_string_lossy().to_string());
+            continue;
+        }
 
-            let mut outfile = fs::File::create(&outpath)?;
+        remove_link(&outpath)?;
+        let mut outfile = fs::File::create(&outpath)?;
 
-            #[cfg(unix)]
-            let h = entry.header().mode();
+        #[cfg(unix)]
+        let h = entry.header().mode();
 
-            io::copy(&mut BufReader::new(entry), &mut outfile)?;
-            files.push(outpath.to_string_lossy().to_string());
+        io::copy(&mut BufReader::new(entry), &mut outfile)?;
+        files.push(outpath.to_string_lossy().to_string());
 
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Ok(mode) = h {
-                    let mode = normalize_mode(mode);
-                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-                }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Ok(mode) = h {
+                let mode = normalize_mode(mode);
+                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
             }
         }
     }
@@ -39,7 +39,7 @@ impl Tarzst {
 
 impl Decompressor for Tarzst {
     fn test_mimetype(&self, archive: &str) -> bool {
-        archive == "application/x-bzip2"
+        archive == "application/zstd"
     }
 
     fn test(&self, archive: &Path) -> bool {
@@ -9,8 +9,9 @@ use regex::Regex;
 use zip::ZipArchive;
 
 use crate::{
-    decompressors::utils::normalize_mode, DecompressError, Decompression, Decompressor,
-    ExtractOpts, Listing,
+    decompressors::utils::normalize_mode,
+    sanitize::{check_entry_path, check_outpath, create_symlink, remove_link},
+    DecompressError, Decompression, Decompressor, ExtractOpts, Listing,
 };
 
 lazy_static! {
@@ -83,6 +84,8 @@ impl Decompressor for Zip {
             let mut file = rdr
                 .by_index(i)
                 .map_err(|err| DecompressError::Error(err.to_string()))?;
+            let entry_path = PathBuf::from(file.name());
+            check_entry_path(&entry_path)?;
             let filepath = file
                 .enclosed_name()
                 .ok_or_else(|| DecompressError::Error("Invalid file path".to_string()))?;
@@ -104,6 +107,18 @@ impl Decompressor for Zip {
             }
 
             let outpath = (opts.map)(outpath.as_path());
+            check_outpath(to, &outpath, &entry_path)?;
+
+            if file.is_symlink() {
+                let mut target = String::new();
+                io::Read::read_to_string(&mut file, &mut target)?;
+                if let Some(p) = outpath.parent() {
+                    fs::create_dir_all(p)?;
+                }
+                create_symlink(to, &outpath, Path::new(&target), &entry_path)?;
+                files.push(outpath.to_string_lossy().to_string());
+                continue;
+            }
 
             if file.name().ends_with('/') {
                 fs::create_dir_all(&outpath)?;
@@ -113,6 +128,7 @@ impl Decompressor for Zip {
                         fs::create_dir_all(p)?;
                     }
                 }
+                remove_link(&outpath)?;
                 let mut outfile = fs::File::create(&outpath)?;
                 io::copy(&mut file, &mut outfile)?;
                 files.push(outpath.to_string_lossy().to_string());
@@ -1,6 +1,7 @@
 #![allow(clippy::missing_const_for_fn)]
 #![allow(clippy::module_name_repetitions)]
 pub mod decompressors;
+pub mod sanitize;
 
 use derive_builder::Builder;
 use std::borrow::Cow;
@@ -20,6 +21,28 @@ pub enum DecompressError {
 
     #[error("no compressor found")]
     MissingCompressor,
+
+    #[error("refusing to unpack `{entry}`: {reason}")]
+    UnsafeEntry {
+        entry: String,
+        reason: UnsafeEntryReason,
+    },
+}
+
+/// Why an archive entry was refused, see `sanitize`
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsafeEntryReason {
+    #[error("absolute path")]
+    AbsolutePath,
+
+    #[error("path contains `..`")]
+    ParentDir,
+
+    #[error("symlink points outside of the destination")]
+    LinkOutside,
+
+    #[error("path resolves outside of the destination")]
+    ResolvesOutside,
 }
 
 pub type FilterFn = dyn Fn(&Path) -> bool;
@@ -155,17 +178,21 @@ impl Decompress {
         archive: P,
         detect_content: bool,
     ) -> Result<&Box<dyn Decompressor>, DecompressError> {
-        if detect_content {
-            let res = infer::get_from_path(archive.as_ref())?;
-            let mt = res.map(|t| t.mime_type());
+        let by_content = if detect_content {
+            let mt = sniff_mimetype(archive.as_ref())?;
             mt.and_then(|mt| self.decompressors.iter().find(|dec| dec.test_mimetype(mt)))
         } else {
-            println!("f: {:?} ", archive.as_ref());
-            self.decompressors
-                .iter()
-                .find(|dec| dec.test(archive.as_ref()))
-        }
-        .ok_or(DecompressError::MissingCompressor)
+            None
+        };
+
+        // content that could not be identified still gets a chance by its file name
+        by_content
+            .or_else(|| {
+                self.decompressors
+                    .iter()
+                    .find(|dec| dec.test(archive.as_ref()))
+            })
+            .ok_or(DecompressError::MissingCompressor)
     }
 
     /// Build given a custom stack of decompressors
@@ -226,6 +253,25 @@ impl Decompress {
     }
 }
 
+/// Magic of an lzma "alone" stream with default properties, `infer` does not know about it
+const LZMA_MAGIC: [u8; 3] = [0x5d, 0x00, 0x00];
+
+/// Guess a mimetype from the first bytes of a file
+fn sniff_mimetype(archive: &Path) -> Result<Option<&'static str>, DecompressError> {
+    if let Some(kind) = infer::get_from_path(archive)? {
+        return Ok(Some(kind.mime_type()));
+    }
+
+    let mut magic = [0u8; LZMA_MAGIC.len()];
+    let mut fd = std::fs::File::open(archive)?;
+    match io::Read::read_exact(&mut fd, &mut magic) {
+        Ok(()) if magic == LZMA_MAGIC => Ok(Some("application/x-lzma")),
+        Ok(()) => Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Decompress an archive with default decompressor set up
 ///
 /// # Errors
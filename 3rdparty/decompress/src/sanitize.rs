@@ -0,0 +1,150 @@
+//!
+//! Guards that keep archive entries inside the destination folder, whatever
+//! their names, `..` components or symlinks say.
+//!
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::{DecompressError, UnsafeEntryReason};
+
+fn unsafe_entry(entry: &Path, reason: UnsafeEntryReason) -> DecompressError {
+    DecompressError::UnsafeEntry {
+        entry: entry.to_string_lossy().to_string(),
+        reason,
+    }
+}
+
+///
+/// Check the path of an archive entry, it has to be relative and may not contain `..`
+///
+/// # Errors
+///
+/// This function will return `DecompressError::UnsafeEntry` for offending paths.
+pub fn check_entry_path(entry: &Path) -> Result<(), DecompressError> {
+    for component in entry.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            Component::ParentDir => return Err(unsafe_entry(entry, UnsafeEntryReason::ParentDir)),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(unsafe_entry(entry, UnsafeEntryReason::AbsolutePath));
+            }
+        }
+    }
+    Ok(())
+}
+
+///
+/// Check a symlink entry, both paths are relative to the destination. The target is
+/// resolved from the folder of `link` and may not leave the destination.
+///
+/// The caller has to make sure no parent of `link` is a symlink itself, otherwise the
+/// folder the target is resolved from is not the one its path says.
+///
+/// # Errors
+///
+/// This function will return `DecompressError::UnsafeEntry` for offending links.
+pub fn check_link_target(link: &Path, target: &Path) -> Result<(), DecompressError> {
+    check_entry_path(link)?;
+    let depth = link
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .count()
+        .saturating_sub(1);
+
+    link_stays_inside(depth, target).map_err(|reason| unsafe_entry(link, reason))
+}
+
+fn link_stays_inside(mut depth: usize, target: &Path) -> Result<(), UnsafeEntryReason> {
+    // `..` is only followed at the start, after a name it could step back out of another link
+    let mut descended = false;
+    for component in target.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(_) => {
+                depth += 1;
+                descended = true;
+            }
+            Component::ParentDir if !descended && depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(UnsafeEntryReason::LinkOutside);
+            }
+        }
+    }
+    Ok(())
+}
+
+///
+/// Check that writing `outpath` stays inside `root`, with every symlink on the way resolved.
+/// Only the part that exists can be a link, whatever is missing gets created as plain folders.
+pub(crate) fn check_outpath(
+    root: &Path,
+    outpath: &Path,
+    entry: &Path,
+) -> Result<(), DecompressError> {
+    let root = root.canonicalize()?;
+
+    let mut existing = outpath.parent();
+    while let Some(dir) = existing {
+        if fs::symlink_metadata(dir).is_ok() {
+            break;
+        }
+        existing = dir.parent();
+    }
+
+    let resolved = existing.and_then(|dir| Some((dir, dir.canonicalize().ok()?)));
+    let Some((dir, resolved)) = resolved else {
+        return Err(unsafe_entry(entry, UnsafeEntryReason::ResolvesOutside));
+    };
+
+    let missing_plain = outpath
+        .strip_prefix(dir)
+        .is_ok_and(|rest| rest.components().all(|c| matches!(c, Component::Normal(_))));
+    if !resolved.starts_with(&root) || !missing_plain {
+        return Err(unsafe_entry(entry, UnsafeEntryReason::ResolvesOutside));
+    }
+
+    Ok(())
+}
+
+///
+/// Replace rather than write through a link that an earlier entry left at `outpath`
+pub(crate) fn remove_link(outpath: &Path) -> Result<(), DecompressError> {
+    if fs::symlink_metadata(outpath).is_ok_and(|m| m.file_type().is_symlink()) {
+        fs::remove_file(outpath)?;
+    }
+    Ok(())
+}
+
+///
+/// Create a symlink entry at `outpath`, once its target is known to stay inside `root`.
+/// Platforms without symlinks get a file with the target path instead.
+pub(crate) fn create_symlink(
+    root: &Path,
+    outpath: &Path,
+    target: &Path,
+    entry: &Path,
+) -> Result<(), DecompressError> {
+    // resolve the folder physically, the entry path may pass through other links
+    let root = root.canonicalize()?;
+    let parent = outpath
+        .parent()
+        .ok_or_else(|| unsafe_entry(entry, UnsafeEntryReason::ResolvesOutside))?
+        .canonicalize()?;
+    let depth = parent
+        .strip_prefix(&root)
+        .map_err(|_| unsafe_entry(entry, UnsafeEntryReason::ResolvesOutside))?
+        .components()
+        .count();
+
+    link_stays_inside(depth, target).map_err(|reason| unsafe_entry(entry, reason))?;
+
+    remove_link(outpath)?;
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, outpath)?;
+
+    #[cfg(not(unix))]
+    fs::write(outpath, target.to_string_lossy().as_bytes())?;
+
+    Ok(())
+}
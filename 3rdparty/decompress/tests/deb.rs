@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use decompress::decompressors::ar::extract_member;
+use decompress::{ExtractOpts, ExtractOptsBuilder};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/deb")
+        .join(name)
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("decompress_deb_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn detect_opts() -> ExtractOpts {
+    ExtractOptsBuilder::default()
+        .detect_content(true)
+        .map(|p| Cow::from(p))
+        .build()
+        .unwrap()
+}
+
+/// Unpacks the `data.tar*` and `control.tar*` members like a .deb installer would
+fn unpack_deb(name: &str) -> PathBuf {
+    let dir = scratch(name);
+    let members = dir.join("members");
+
+    for (prefix, dest) in [("control.tar", "control"), ("data.tar", "data")] {
+        let member = extract_member(&fixture(name), prefix, &members)
+            .unwrap()
+            .unwrap_or_else(|| panic!("{name} has no {prefix} member"));
+        decompress::decompress(member, dir.join(dest), &detect_opts())
+            .unwrap_or_else(|e| panic!("{name}: {prefix}: {e}"));
+    }
+
+    dir
+}
+
+fn assert_deb(name: &str) {
+    let dir = unpack_deb(name);
+
+    let control = fs::read_to_string(dir.join("control/control")).unwrap();
+    assert!(control.contains("Package: com.example.fixture"), "{name}");
+    assert_eq!(
+        fs::read_to_string(dir.join("data/usr/lib/fixture.txt")).unwrap(),
+        "fixture\n",
+        "{name}"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn deb_gz() {
+    assert_deb("gz.deb");
+}
+
+#[test]
+fn deb_xz() {
+    assert_deb("xz.deb");
+}
+
+#[test]
+fn deb_bz2() {
+    assert_deb("bz2.deb");
+}
+
+#[test]
+fn deb_lzma() {
+    assert_deb("lzma.deb");
+}
+
+#[test]
+fn deb_zst() {
+    assert_deb("zst.deb");
+}
+
+#[test]
+fn deb_uncompressed() {
+    assert_deb("tar.deb");
+}
+
+#[test]
+fn deb_member_detected_by_content() {
+    // data.tar.gz is really zstd compressed
+    assert_deb("mislabeled.deb");
+}
+
+#[test]
+fn deb_missing_member() {
+    let dir = scratch("missing");
+    assert!(
+        extract_member(&fixture("gz.deb"), "data.tar.zst", &dir)
+            .unwrap()
+            .is_none()
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use decompress::sanitize::{check_entry_path, check_link_target};
+use decompress::{DecompressError, ExtractOptsBuilder, UnsafeEntryReason};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/malicious")
+        .join(name)
+}
+
+/// Unpacks into `<scratch>/dest`, so anything that escapes lands next to it
+fn unpack(name: &str) -> (PathBuf, Result<(), DecompressError>) {
+    let scratch = std::env::temp_dir().join(format!(
+        "decompress_malicious_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).unwrap();
+
+    let opts = ExtractOptsBuilder::default().build().unwrap();
+    let res = decompress::decompress(fixture(name), scratch.join("dest"), &opts).map(|_| ());
+    (scratch, res)
+}
+
+fn assert_refused(name: &str, expected: UnsafeEntryReason) {
+    let (scratch, res) = unpack(name);
+
+    match res {
+        Err(DecompressError::UnsafeEntry { reason, .. }) => assert_eq!(reason, expected, "{name}"),
+        other => panic!("{name}: expected an unsafe entry error, got {other:?}"),
+    }
+
+    let escaped = fs::read_dir(&scratch)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .filter(|n| n != "dest")
+        .collect::<Vec<_>>();
+    assert!(escaped.is_empty(), "{name}: {escaped:?} escaped");
+    assert!(!Path::new("/tmp/decompress_evil_absolute.txt").exists());
+
+    fs::remove_dir_all(scratch).unwrap();
+}
+
+#[test]
+fn tar_parent_dir() {
+    assert_refused("dotdot.tar", UnsafeEntryReason::ParentDir);
+}
+
+#[test]
+fn tar_parent_dir_every_codec() {
+    for name in [
+        "dotdot.tar.gz",
+        "dotdot.tar.bz2",
+        "dotdot.tar.xz",
+        "dotdot.tar.lzma",
+        "dotdot.tar.zst",
+    ] {
+        assert_refused(name, UnsafeEntryReason::ParentDir);
+    }
+}
+
+#[test]
+fn tar_absolute() {
+    assert_refused("absolute.tar", UnsafeEntryReason::AbsolutePath);
+}
+
+#[test]
+fn tar_symlink() {
+    assert_refused("symlink.tar", UnsafeEntryReason::LinkOutside);
+}
+
+#[test]
+fn tar_symlink_absolute() {
+    assert_refused("symlink-absolute.tar", UnsafeEntryReason::LinkOutside);
+}
+
+#[test]
+fn tar_symlink_chain() {
+    // `d/s -> ..` is fine on its own, `d/s/l -> ../evil.txt` then resolves from the root
+    assert_refused("symlink-chain.tar", UnsafeEntryReason::LinkOutside);
+}
+
+#[test]
+fn tar_hardlink() {
+    assert_refused("hardlink.tar", UnsafeEntryReason::ParentDir);
+}
+
+#[test]
+fn zip_parent_dir() {
+    assert_refused("dotdot.zip", UnsafeEntryReason::ParentDir);
+}
+
+#[test]
+fn zip_absolute() {
+    assert_refused("absolute.zip", UnsafeEntryReason::AbsolutePath);
+}
+
+#[test]
+fn zip_symlink() {
+    assert_refused("symlink.zip", UnsafeEntryReason::LinkOutside);
+}
+
+#[test]
+fn zip_symlink_chain() {
+    assert_refused("symlink-chain.zip", UnsafeEntryReason::LinkOutside);
+}
+
+#[test]
+fn ar_parent_dir() {
+    assert_refused("dotdot.ar", UnsafeEntryReason::ParentDir);
+}
+
+#[test]
+fn ar_absolute() {
+    assert_refused("absolute.ar", UnsafeEntryReason::AbsolutePath);
+}
+
+#[test]
+fn entry_paths() {
+    assert!(check_entry_path(Path::new("Payload/App.app/Info.plist")).is_ok());
+    assert!(check_entry_path(Path::new("./usr/lib/tweak.dylib")).is_ok());
+    assert!(check_entry_path(Path::new("Payload/../../evil")).is_err());
+    assert!(check_entry_path(Path::new("/etc/passwd")).is_err());
+}
+
+#[test]
+fn link_targets() {
+    let link = Path::new("Payload/App.app/Frameworks/A.framework/A");
+    assert!(check_link_target(link, Path::new("Versions/Current/A")).is_ok());
+    assert!(check_link_target(link, Path::new("../../../../evil")).is_ok());
+    assert!(check_link_target(link, Path::new("../../../../../evil")).is_err());
+    assert!(check_link_target(link, Path::new("Versions/../../../../../evil")).is_err());
+    assert!(check_link_target(link, Path::new("/usr/lib/libobjc.dylib")).is_err());
+}
+
+#[test]
+fn tar_links_inside() {
+    let (scratch, res) = unpack("links.tar");
+    res.unwrap();
+
+    let dest = scratch.join("dest");
+    for path in [
+        "usr/lib/alias.dylib",
+        "usr/lib/sub/up.dylib",
+        "usr/copy.dylib",
+    ] {
+        assert_eq!(
+            fs::read_to_string(dest.join(path)).unwrap(),
+            "real\n",
+            "{path}"
+        );
+    }
+
+    fs::remove_dir_all(scratch).unwrap();
+}
@@ -0,0 +1,28 @@
+use plume_core::Error;
+use rust_i18n::t;
+
+/// Error text for the user, followed by what to do about it for developer API errors we know.
+pub(crate) fn describe(err: &(dyn std::error::Error + 'static)) -> String {
+    let hint = std::iter::successors(Some(err), |e| e.source())
+        .find_map(|e| e.downcast_ref::<Error>())
+        .and_then(hint);
+
+    match hint {
+        Some(hint) => format!("{err}\n\n{hint}"),
+        None => err.to_string(),
+    }
+}
+
+fn hint(err: &Error) -> Option<String> {
+    let key = match err {
+        Error::DeviceLimit { .. } => "error_hint_device_limit",
+        Error::AppIdLimit { .. } => "error_hint_app_id_limit",
+        Error::MaximumCertificates { .. } => "error_hint_maximum_certificates",
+        Error::SessionExpired { .. } => "error_hint_session_expired",
+        Error::TeamNotFound { .. } => "error_hint_team_not_found",
+        Error::AgreementNotAccepted { .. } => "error_hint_agreement",
+        Error::CapabilityNotAllowed { .. } => "error_hint_capability",
+        _ => return None,
+    };
+    Some(t!(key).to_string())
+}
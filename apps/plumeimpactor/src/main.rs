@@ -7,8 +7,10 @@ use crate::refresh::spawn_refresh_daemon;
 #[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
 use single_instance::SingleInstance;
 
+mod api_errors;
 mod appearance;
 mod certificate_reset;
+mod confirmation;
 mod defaults;
 mod macos_app;
 mod refresh;
@@ -34,7 +34,7 @@ impl GeneralScreen {
                 Task::perform(
                     async {
                         rfd::AsyncFileDialog::new()
-                            .add_filter(t!("ipa"), &["ipa", "tipa"])
+                            .add_filter(t!("ipa"), &["ipa", "tipa", "zip", "xcarchive"])
                             .set_title(t!("select_ipa"))
                             .pick_file()
                             .await
@@ -53,11 +53,9 @@ impl GeneralScreen {
             }
             Message::FilesDropped(paths) => {
                 for path in paths {
-                    if let Some(ext) = path.extension() {
-                        if ext == "ipa" || ext == "tipa" {
-                            if let Ok(package) = Package::new(path) {
-                                return Task::done(Message::NavigateToInstaller(package));
-                            }
+                    if Package::is_supported_path(&path) {
+                        if let Ok(package) = Package::new(path) {
+                            return Task::done(Message::NavigateToInstaller(package));
                         }
                     }
                 }
@@ -20,10 +20,10 @@ use crate::tray::ImpactorTray;
 use crate::{appearance, defaults};
 use windows::login_window;
 
-static REFRESH_DAEMON_DEVICES: std::sync::OnceLock<crate::refresh::ConnectedDevices> =
+static REFRESH_DAEMON_DEVICES: std::sync::OnceLock<plume_utils::ConnectedDevices> =
     std::sync::OnceLock::new();
 
-pub fn set_refresh_daemon_devices(devices: crate::refresh::ConnectedDevices) {
+pub fn set_refresh_daemon_devices(devices: plume_utils::ConnectedDevices) {
     let _ = REFRESH_DAEMON_DEVICES.set(devices);
 }
 
@@ -74,9 +74,9 @@ pub enum Message {
     SettingsScreen(settings::Message),
     InstallerScreen(package::Message),
     ProgressScreen(progress::Message),
-    CertificateResetRequested(crate::certificate_reset::ConfirmationRequest),
-    ConfirmCertificateReset,
-    CancelCertificateReset,
+    ConfirmationRequested(crate::confirmation::ConfirmationRequest),
+    ConfirmationAccepted,
+    ConfirmationDeclined,
 
     // Installation
     StartInstallation,
@@ -92,7 +92,7 @@ pub struct Impactor {
     account_store: Option<AccountStore>,
     login_windows: std::collections::HashMap<window::Id, login_window::LoginWindow>,
     pending_installation: bool,
-    certificate_reset_queue: VecDeque<crate::certificate_reset::ConfirmationRequest>,
+    confirmation_queue: VecDeque<crate::confirmation::ConfirmationRequest>,
     selected_locale: Option<String>,
 }
 
@@ -143,7 +143,7 @@ impl Impactor {
                 account_store: Some(store),
                 login_windows: std::collections::HashMap::new(),
                 pending_installation: false,
-                certificate_reset_queue: VecDeque::new(),
+                confirmation_queue: VecDeque::new(),
                 selected_locale,
             },
             open_task,
@@ -151,18 +151,38 @@ impl Impactor {
     }
 
     fn init_account_store_sync() -> AccountStore {
-        let path = defaults::get_data_path().join("accounts.json");
-        AccountStore::load_sync(&Some(path)).unwrap_or_default()
+        AccountStore::load_sync(&Some(Self::account_store_path())).unwrap_or_default()
     }
 
-    fn respond_to_next_certificate_reset(&mut self, accepted: bool) {
-        if let Some(request) = self.certificate_reset_queue.pop_front() {
+    fn account_store_path() -> std::path::PathBuf {
+        defaults::get_data_path().join("accounts.json")
+    }
+
+    // Writes go through the lock so changes from plumesign or the refresh
+    // daemon are merged, and the copy we show is replaced by the saved one
+    fn update_account_store(
+        account_store: &mut Option<AccountStore>,
+        f: impl FnOnce(&mut AccountStore) -> Result<(), plume_core::Error>,
+    ) -> Result<(), plume_core::Error> {
+        *account_store = Some(AccountStore::update_sync(&Self::account_store_path(), f)?);
+        Ok(())
+    }
+
+    fn sorted_account_email(&self, index: usize) -> Option<String> {
+        let store = self.account_store.as_ref()?;
+        let mut emails: Vec<_> = store.accounts().keys().cloned().collect();
+        emails.sort();
+        emails.get(index).cloned()
+    }
+
+    fn respond_to_next_confirmation(&mut self, accepted: bool) {
+        if let Some(request) = self.confirmation_queue.pop_front() {
             request.respond(accepted);
         }
     }
 
-    fn cancel_pending_certificate_resets(&mut self) {
-        while let Some(request) = self.certificate_reset_queue.pop_front() {
+    fn cancel_pending_confirmations(&mut self) {
+        while let Some(request) = self.confirmation_queue.pop_front() {
             request.respond(false);
         }
     }
@@ -361,20 +381,20 @@ impl Impactor {
                     Task::none()
                 }
             }
-            Message::CertificateResetRequested(request) => {
-                self.certificate_reset_queue.push_back(request);
+            Message::ConfirmationRequested(request) => {
+                self.confirmation_queue.push_back(request);
                 if self.main_window.is_none() {
                     Task::done(Message::ShowWindow)
                 } else {
                     Task::none()
                 }
             }
-            Message::ConfirmCertificateReset => {
-                self.respond_to_next_certificate_reset(true);
+            Message::ConfirmationAccepted => {
+                self.respond_to_next_confirmation(true);
                 Task::none()
             }
-            Message::CancelCertificateReset => {
-                self.respond_to_next_certificate_reset(false);
+            Message::ConfirmationDeclined => {
+                self.respond_to_next_confirmation(false);
                 Task::none()
             }
             Message::ShowWindow => {
@@ -389,7 +409,7 @@ impl Impactor {
             }
             Message::HideWindow => {
                 if let Some(id) = self.main_window {
-                    self.cancel_pending_certificate_resets();
+                    self.cancel_pending_confirmations();
                     self.main_window = None;
                     crate::macos_app::set_main_window_visible(false);
                     window::close(id)
@@ -449,6 +469,10 @@ impl Impactor {
                         self.current_screen = ImpactorScreen::Installer(
                             package::PackageScreen::new(Some(package), options),
                         );
+                        return Task::batch(vec![
+                            task,
+                            Task::done(Message::InstallerScreen(package::Message::LoadBundle)),
+                        ]);
                     } else if let general::Message::NavigateToUtilities = msg {
                         let rppairing_enabled = match &self.current_screen {
                             ImpactorScreen::Utilities(screen) => screen.rppairing_enabled,
@@ -484,21 +508,26 @@ impl Impactor {
                             task.map(move |msg| Message::LoginWindowMessage(id, msg))
                         }
                         settings::Message::SelectAccount(index) => {
-                            if let Some(store) = &mut self.account_store {
-                                let mut emails: Vec<_> = store.accounts().keys().cloned().collect();
-                                emails.sort();
-                                if let Some(email) = emails.get(index) {
-                                    let _ = store.account_select_sync(email);
+                            if let Some(email) = self.sorted_account_email(index) {
+                                if let Err(e) =
+                                    Self::update_account_store(&mut self.account_store, |store| {
+                                        store.account_select(&email)
+                                    })
+                                {
+                                    log::error!("Failed to select account: {:?}", e);
                                 }
                             }
                             Task::none()
                         }
                         settings::Message::RemoveAccount(index) => {
-                            if let Some(store) = &mut self.account_store {
-                                let mut emails: Vec<_> = store.accounts().keys().cloned().collect();
-                                emails.sort();
-                                if let Some(email) = emails.get(index) {
-                                    let _ = store.accounts_remove_sync(email);
+                            if let Some(email) = self.sorted_account_email(index) {
+                                if let Err(e) =
+                                    Self::update_account_store(&mut self.account_store, |store| {
+                                        store.accounts_remove(&email);
+                                        Ok(())
+                                    })
+                                {
+                                    log::error!("Failed to remove account: {:?}", e);
                                 }
                             }
                             Task::none()
@@ -567,17 +596,74 @@ impl Impactor {
                             screen.update(msg).map(Message::SettingsScreen)
                         }
                         settings::Message::SelectTeam(ref email, ref team_id) => {
-                            if let Some(store) = &mut self.account_store {
-                                if let Err(e) =
-                                    store.update_account_team_sync(email, team_id.clone())
-                                {
-                                    log::error!("Failed to update team: {:?}", e);
-                                } else {
-                                    self.account_store = Some(Self::init_account_store_sync());
-                                }
+                            if let Err(e) =
+                                Self::update_account_store(&mut self.account_store, |store| {
+                                    store.update_account_team(email, team_id.clone())
+                                })
+                            {
+                                log::error!("Failed to update team: {:?}", e);
                             }
                             screen.update(msg).map(Message::SettingsScreen)
                         }
+                        settings::Message::CleanupAppIds => {
+                            let Some(store) = self.account_store.clone() else {
+                                return Task::none();
+                            };
+                            let Some(account) = store.selected_account().cloned() else {
+                                return Task::none();
+                            };
+
+                            let cleanup = Task::perform(
+                                async move {
+                                    let (tx, rx) = std::sync::mpsc::channel();
+
+                                    std::thread::spawn(move || {
+                                        let rt = tokio::runtime::Runtime::new().unwrap();
+                                        let result = rt.block_on(async move {
+                                            crate::subscriptions::cleanup_app_ids(&account, &store)
+                                                .await
+                                        });
+                                        let _ = tx.send(result);
+                                    });
+
+                                    rx.recv()
+                                        .unwrap_or_else(|_| Err("Cleanup stopped".to_string()))
+                                },
+                                |result| {
+                                    Message::SettingsScreen(settings::Message::CleanupFinished(
+                                        result,
+                                    ))
+                                },
+                            );
+
+                            Task::batch([screen.update(msg).map(Message::SettingsScreen), cleanup])
+                        }
+                        settings::Message::ExportProfile => {
+                            let password = screen.profile_password().to_string();
+                            let export =
+                                Task::perform(subscriptions::export_profile(password), |result| {
+                                    Message::SettingsScreen(settings::Message::ProfileFinished(
+                                        result,
+                                    ))
+                                });
+
+                            Task::batch([screen.update(msg).map(Message::SettingsScreen), export])
+                        }
+                        settings::Message::ImportProfile => {
+                            let password = screen.profile_password().to_string();
+                            let import =
+                                Task::perform(subscriptions::import_profile(password), |result| {
+                                    Message::SettingsScreen(settings::Message::ProfileFinished(
+                                        result,
+                                    ))
+                                });
+
+                            Task::batch([screen.update(msg).map(Message::SettingsScreen), import])
+                        }
+                        settings::Message::ProfileFinished(_) => Task::batch([
+                            screen.update(msg).map(Message::SettingsScreen),
+                            Task::done(Message::UpdateTrayMenu),
+                        ]),
                         settings::Message::SelectLocale(choice) => {
                             self.selected_locale = choice.clone();
                             let effective = choice
@@ -585,10 +671,13 @@ impl Impactor {
                                 .or_else(|| current_locale::current_locale().ok())
                                 .unwrap_or_else(|| "en".to_string());
                             rust_i18n::set_locale(&effective);
-                            if let Some(store) = &mut self.account_store {
-                                if let Err(err) = store.set_locale_sync(choice) {
-                                    log::error!("Failed to persist locale: {err}");
-                                }
+                            if let Err(err) =
+                                Self::update_account_store(&mut self.account_store, |store| {
+                                    store.set_locale(choice);
+                                    Ok(())
+                                })
+                            {
+                                log::error!("Failed to persist locale: {err}");
                             }
                             Task::none()
                         }
@@ -689,7 +778,7 @@ impl Impactor {
                                         };
 
                                         if let Some(device) = device_opt {
-                                            let daemon = crate::refresh::RefreshDaemon::new();
+                                            let daemon = crate::refresh::refresh_daemon();
                                             if let Err(e) = daemon
                                                 .refresh_app(&store, refresh_device, app, &device)
                                                 .await
@@ -721,41 +810,45 @@ impl Impactor {
                 Task::done(Message::UpdateTrayMenu)
             }
             Message::ForgetApp { udid, app_path } => {
-                if let Some(store) = &mut self.account_store {
-                    if let Some(mut refresh_device) = store.get_refresh_device(&udid).cloned() {
-                        if let Some(app) = refresh_device
+                let forgotten = self
+                    .account_store
+                    .as_ref()
+                    .and_then(|store| store.get_refresh_device(&udid))
+                    .and_then(|device| {
+                        device
                             .apps
                             .iter()
                             .find(|a| a.path.to_string_lossy() == app_path)
-                        {
-                            let app_path_buf = app.path.clone();
-                            std::thread::spawn(move || {
-                                if app_path_buf.exists() {
-                                    if let Err(e) = std::fs::remove_dir_all(&app_path_buf) {
-                                        log::error!(
-                                            "Failed to delete app at {:?}: {}",
-                                            app_path_buf,
-                                            e
-                                        );
-                                    } else {
-                                        log::info!("Deleted app at {:?}", app_path_buf);
-                                    }
-                                }
-                            });
+                    })
+                    .map(|app| app.path.clone());
+
+                if let Some(app_path_buf) = forgotten {
+                    std::thread::spawn(move || {
+                        if app_path_buf.exists() {
+                            if let Err(e) = std::fs::remove_dir_all(&app_path_buf) {
+                                log::error!("Failed to delete app at {:?}: {}", app_path_buf, e);
+                            } else {
+                                log::info!("Deleted app at {:?}", app_path_buf);
+                            }
                         }
+                    });
+                }
 
+                if let Err(e) = Self::update_account_store(&mut self.account_store, |store| {
+                    if let Some(mut refresh_device) = store.get_refresh_device(&udid).cloned() {
                         refresh_device
                             .apps
                             .retain(|a| a.path.to_string_lossy() != app_path);
 
                         if refresh_device.apps.is_empty() {
-                            let _ = store.remove_refresh_device_sync(&udid);
+                            store.remove_refresh_device(&udid);
                         } else {
-                            let _ = store.add_or_update_refresh_device_sync(refresh_device);
+                            store.add_or_update_refresh_device(refresh_device);
                         }
-
-                        self.account_store = Some(Self::init_account_store_sync());
                     }
+                    Ok(())
+                }) {
+                    log::error!("Failed to forget app: {:?}", e);
                 }
                 Task::done(Message::UpdateTrayMenu)
             }
@@ -806,7 +899,7 @@ impl Impactor {
             };
 
         let tray_menu_refresh_subscription = subscriptions::tray_menu_refresh_subscription();
-        let certificate_reset_subscription = subscriptions::certificate_reset_subscription();
+        let confirmation_subscription = subscriptions::confirmation_subscription();
         let relaunch_subscription = subscriptions::relaunch_subscription();
 
         let close_subscription = iced::event::listen_with(|event, _status, _id| {
@@ -822,7 +915,7 @@ impl Impactor {
             hover_subscription,
             progress_subscription,
             tray_menu_refresh_subscription,
-            certificate_reset_subscription,
+            confirmation_subscription,
             relaunch_subscription,
             close_subscription,
         ])
@@ -843,8 +936,8 @@ impl Impactor {
                 .padding(appearance::THEME_PADDING)
                 .into();
 
-        if self.certificate_reset_queue.front().is_some() {
-            stack![base, self.view_certificate_reset_prompt()].into()
+        if self.confirmation_queue.front().is_some() {
+            stack![base, self.view_confirmation_prompt()].into()
         } else {
             base
         }
@@ -905,24 +998,24 @@ impl Impactor {
         .into()
     }
 
-    fn view_certificate_reset_prompt(&self) -> Element<'_, Message> {
-        let Some(request) = self.certificate_reset_queue.front() else {
+    fn view_confirmation_prompt(&self) -> Element<'_, Message> {
+        let Some(request) = self.confirmation_queue.front() else {
             return container(text("")).into();
         };
 
         let actions = row![
             button(text("Cancel"))
-                .on_press(Message::CancelCertificateReset)
+                .on_press(Message::ConfirmationDeclined)
                 .style(appearance::s_button),
             button(text("Continue"))
-                .on_press(Message::ConfirmCertificateReset)
+                .on_press(Message::ConfirmationAccepted)
                 .style(appearance::p_button),
         ]
         .spacing(appearance::THEME_PADDING);
 
         let dialog = container(
             column![
-                text("Certificate reset required").size(appearance::THEME_FONT_SIZE + 2.0),
+                text(&request.title).size(appearance::THEME_FONT_SIZE + 2.0),
                 text(&request.message),
                 actions,
             ]
@@ -989,12 +1082,13 @@ impl Impactor {
             };
 
             let device = self.selected_device.clone();
+            let bundle = installer.loaded_bundle();
             let options = installer.options.clone();
             let account = self
                 .account_store
                 .as_ref()
                 .and_then(|s| s.selected_account().cloned());
-            let mut store = self.account_store.clone();
+            let store_path = self.account_store.as_ref().and_then(|s| s.path());
 
             let (tx, rx) = std::sync::mpsc::channel();
             let progress_rx = std::sync::Arc::new(std::sync::Mutex::new(rx));
@@ -1010,9 +1104,10 @@ impl Impactor {
                     match subscriptions::run_installation(
                         &package,
                         device.as_ref(),
+                        bundle,
                         &options,
                         account.as_ref(),
-                        store.as_mut(),
+                        store_path.as_deref(),
                         &tx,
                     )
                     .await
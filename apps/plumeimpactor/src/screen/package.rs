@@ -1,9 +1,14 @@
+use iced::futures::channel::oneshot;
 use iced::widget::{
     button, checkbox, column, container, image, pick_list, row, scrollable, stack, text, text_input,
 };
 use iced::{Alignment, Center, Element, Fill, Task};
-use plume_utils::{Package, PlistInfoTrait, SignerInstallMode, SignerMode, SignerOptions};
+use plume_utils::{
+    Bundle, Package, PlistInfoTrait, Signer, SignerInstallMode, SignerManifest, SignerMode,
+    SignerOptions, Tweak, TweakPlan,
+};
 use rust_i18n::t;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use tiny_skia::{FillRule, Mask, Path, PathBuilder, Transform};
 
@@ -23,15 +28,20 @@ pub enum Message {
     ToggleLiquidGlass(bool),
     ToggleRefresh(bool),
     ToggleElleKit(bool),
+    ToggleRemoveBundle(PathBuf, bool),
     UpdateSignerMode(SignerMode),
     UpdateInstallMode(SignerInstallMode),
     AddTweak,
     AddBundle,
     RemoveTweak(usize),
+    TweakPlanned(PathBuf, Result<TweakPlan, String>),
+    LoadBundle,
+    BundleLoaded(Result<(Bundle, Vec<PathBuf>), String>),
     SetCustomIcon,
     ClearCustomIcon,
     SetCustomEntitlements,
     ClearCustomEntitlements,
+    ExportManifest,
     Back,
     RequestInstallation,
 }
@@ -43,6 +53,10 @@ pub struct PackageScreen {
     package_icon_handle: Option<image::Handle>,
     custom_icon_path: Option<PathBuf>,
     custom_icon_handle: Option<image::Handle>,
+    tweak_plans: HashMap<PathBuf, Result<TweakPlan, String>>,
+    /// Extracted app and its removable bundles, install signs this same copy.
+    bundle: Option<Result<(Bundle, Vec<PathBuf>), String>>,
+    app_id_count: Option<usize>,
 }
 
 impl PackageScreen {
@@ -61,9 +75,29 @@ impl PackageScreen {
             package_icon_handle,
             custom_icon_path,
             custom_icon_handle,
+            tweak_plans: HashMap::new(),
+            bundle: None,
+            app_id_count: None,
         }
     }
 
+    /// The app extracted when the package was loaded, install signs it in place.
+    pub fn loaded_bundle(&self) -> Option<Bundle> {
+        match &self.bundle {
+            Some(Ok((bundle, _))) => Some(bundle.clone()),
+            _ => None,
+        }
+    }
+
+    fn update_app_id_count(&mut self) {
+        self.app_id_count = match &self.bundle {
+            Some(Ok((bundle, _))) => Signer::new(None, self.options.clone())
+                .app_id_count(bundle)
+                .ok(),
+            _ => None,
+        };
+    }
+
     pub fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::UpdateCustomName(name) => {
@@ -130,6 +164,7 @@ impl PackageScreen {
             }
             Message::ToggleSingleProfile(value) => {
                 self.options.embedding.single_profile = value;
+                self.update_app_id_count();
                 Task::none()
             }
             Message::ToggleLiquidGlass(value) => {
@@ -146,6 +181,16 @@ impl PackageScreen {
             }
             Message::UpdateSignerMode(mode) => {
                 self.options.mode = mode;
+                self.update_app_id_count();
+                Task::none()
+            }
+            Message::ToggleRemoveBundle(path, value) => {
+                let bundles = &mut self.options.removals.bundles;
+                bundles.retain(|b| b != &path);
+                if value {
+                    bundles.push(path);
+                }
+                self.update_app_id_count();
                 Task::none()
             }
             Message::UpdateInstallMode(mode) => {
@@ -160,9 +205,10 @@ impl PackageScreen {
 
                 if let Some(path) = path {
                     match &mut self.options.tweaks {
-                        Some(vec) => vec.push(path),
-                        None => self.options.tweaks = Some(vec![path]),
+                        Some(vec) => vec.push(path.clone()),
+                        None => self.options.tweaks = Some(vec![path.clone()]),
                     }
+                    return plan_tweak(path);
                 }
 
                 Task::none()
@@ -176,9 +222,10 @@ impl PackageScreen {
                     if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
                         if ["framework", "bundle", "appex"].contains(&ext) {
                             match &mut self.options.tweaks {
-                                Some(vec) => vec.push(path),
-                                None => self.options.tweaks = Some(vec![path]),
+                                Some(vec) => vec.push(path.clone()),
+                                None => self.options.tweaks = Some(vec![path.clone()]),
                             }
+                            return plan_tweak(path);
                         }
                     }
                 }
@@ -188,11 +235,30 @@ impl PackageScreen {
             Message::RemoveTweak(index) => {
                 if let Some(tweaks) = &mut self.options.tweaks {
                     if index < tweaks.len() {
-                        tweaks.remove(index);
+                        let removed = tweaks.remove(index);
+                        if !tweaks.contains(&removed) {
+                            self.tweak_plans.remove(&removed);
+                        }
                     }
                 }
                 Task::none()
             }
+            Message::TweakPlanned(path, plan) => {
+                self.tweak_plans.insert(path, plan);
+                Task::none()
+            }
+            Message::LoadBundle => match &self.selected_package {
+                Some(package) => load_bundle(package.clone()),
+                None => Task::none(),
+            },
+            Message::BundleLoaded(result) => {
+                if let Err(e) = &result {
+                    log::error!("Failed to read bundle: {}", e);
+                }
+                self.bundle = Some(result);
+                self.update_app_id_count();
+                Task::none()
+            }
             Message::SetCustomIcon => {
                 let path = rfd::FileDialog::new()
                     .add_filter("Image files", &["png", "jpg", "jpeg"])
@@ -229,6 +295,21 @@ impl PackageScreen {
                 self.options.custom_entitlements = None;
                 Task::none()
             }
+            Message::ExportManifest => {
+                let path = rfd::FileDialog::new()
+                    .add_filter("Signing manifest", &["toml", "json"])
+                    .set_title("Export Signing Settings")
+                    .set_file_name("manifest.toml")
+                    .save_file();
+
+                if let Some(path) = path {
+                    if let Err(e) = SignerManifest::new(self.options.clone()).save(&path) {
+                        log::error!("Failed to export signing settings: {}", e);
+                    }
+                }
+
+                Task::none()
+            }
             _ => Task::none(),
         }
     }
@@ -246,11 +327,42 @@ impl PackageScreen {
             .spacing(appearance::THEME_PADDING),
         );
 
-        column![
-            container(content).width(Fill).height(Fill),
-            self.view_buttons(has_device)
-        ]
-        .spacing(appearance::THEME_PADDING)
+        let mut page = column![].spacing(appearance::THEME_PADDING);
+        if !pkg.encrypted_executables().is_empty() {
+            page = page.push(self.view_encrypted_warning(pkg));
+        }
+
+        page.push(container(content).width(Fill).height(Fill))
+            .push(self.view_buttons(has_device))
+            .into()
+    }
+
+    fn view_encrypted_warning(&self, pkg: &Package) -> Element<'_, Message> {
+        let executables = pkg
+            .encrypted_executables()
+            .iter()
+            .map(|entry| entry.trim_start_matches("Payload/"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        container(
+            column![
+                text(t!("options_encrypted_warn")).size(12),
+                text(executables).size(11),
+            ]
+            .spacing(4),
+        )
+        .padding(8)
+        .width(Fill)
+        .style(|theme: &iced::Theme| container::Style {
+            text_color: Some(theme.palette().danger),
+            border: iced::Border {
+                width: 1.0,
+                color: theme.palette().danger,
+                radius: appearance::THEME_CORNER_RADIUS.into(),
+            },
+            ..Default::default()
+        })
         .into()
     }
 
@@ -357,6 +469,8 @@ impl PackageScreen {
             checkbox(self.options.refresh)
                 .label(t!("options_auto_refresh"))
                 .on_toggle(Message::ToggleRefresh),
+            text(t!("options_remove")).size(12),
+            self.view_removable_bundles(),
             text(t!("options_mode")).size(12),
             pick_list(
                 &[SignerInstallMode::Install, SignerInstallMode::Export][..],
@@ -373,6 +487,13 @@ impl PackageScreen {
             )
             .style(appearance::s_pick_list)
             .placeholder(t!("options_signing_desc")),
+            button(appearance::icon_text(
+                appearance::SHARE,
+                t!("options_export_manifest"),
+                None
+            ))
+            .on_press(Message::ExportManifest)
+            .style(appearance::s_button),
         ]
         .spacing(8)
         .width(Fill)
@@ -384,6 +505,7 @@ impl PackageScreen {
             SignerInstallMode::Install => (has_device, t!("install")),
             SignerInstallMode::Export => (true, t!("export")),
         };
+        let button_enabled = button_enabled && self.bundle.is_some();
 
         container(
             row![
@@ -410,6 +532,44 @@ impl PackageScreen {
         .into()
     }
 
+    fn view_removable_bundles(&self) -> Element<'_, Message> {
+        let removable = match &self.bundle {
+            None => return text(t!("options_remove_loading")).size(11).into(),
+            Some(Err(e)) => {
+                return text(e.clone())
+                    .size(11)
+                    .style(text::danger)
+                    .wrapping(text::Wrapping::WordOrGlyph)
+                    .into();
+            }
+            Some(Ok((_, removable))) => removable,
+        };
+
+        let mut list = column![].spacing(4);
+
+        if removable.is_empty() {
+            list = list.push(text(t!("options_remove_none")).size(11));
+        }
+
+        for path in removable {
+            let path_clone = path.clone();
+            list = list.push(
+                checkbox(self.options.removals.bundles.contains(path))
+                    .label(path.display().to_string())
+                    .on_toggle(move |value| Message::ToggleRemoveBundle(path_clone.clone(), value)),
+            );
+        }
+
+        if let Some(count) = self
+            .app_id_count
+            .filter(|_| self.options.mode == SignerMode::Pem)
+        {
+            list = list.push(text(format!("{} {}", t!("options_app_ids"), count)).size(11));
+        }
+
+        list.into()
+    }
+
     fn view_custom_entitlements(&self) -> Element<'_, Message> {
         let enabled = self.options.embedding.single_profile;
 
@@ -522,7 +682,7 @@ impl PackageScreen {
                 .spacing(8)
                 .align_y(Alignment::Start);
 
-                tweak_list = tweak_list.push(tweak_row);
+                tweak_list = tweak_list.push(tweak_row).push(self.view_tweak_plan(tweak));
             }
 
             scrollable(tweak_list).into()
@@ -530,6 +690,133 @@ impl PackageScreen {
             text("No tweaks added").size(12).into()
         }
     }
+
+    fn view_tweak_plan(&self, tweak: &std::path::Path) -> Element<'_, Message> {
+        let plan = match self.tweak_plans.get(tweak) {
+            None => return text(t!("options_tweak_planning")).size(11).into(),
+            Some(Err(e)) => {
+                return text(e.clone())
+                    .size(11)
+                    .style(text::danger)
+                    .wrapping(text::Wrapping::WordOrGlyph)
+                    .into();
+            }
+            Some(Ok(plan)) => plan,
+        };
+
+        if plan.items.is_empty() {
+            return text(t!("options_tweak_empty")).size(11).into();
+        }
+
+        let mut items = column![].spacing(2).padding([0, 8]);
+
+        let depends = plan
+            .control
+            .as_ref()
+            .map(|control| control.injectable_depends())
+            .unwrap_or_default();
+        if !depends.is_empty() {
+            let depends = depends
+                .iter()
+                .map(|clause| clause.join(" | "))
+                .collect::<Vec<_>>()
+                .join(", ");
+            items = items.push(
+                text(format!("{} {}", t!("options_tweak_depends"), depends))
+                    .size(11)
+                    .wrapping(text::Wrapping::WordOrGlyph),
+            );
+        }
+        for item in &plan.items {
+            let line = match &item.load_command {
+                Some(load_command) => {
+                    format!("{} ({})", item.destination.display(), load_command)
+                }
+                None => item.destination.display().to_string(),
+            };
+            items = items.push(text(line).size(11).wrapping(text::Wrapping::WordOrGlyph));
+        }
+
+        if plan.items.iter().any(|item| item.rewrites_substrate) {
+            items = items.push(text(t!("options_tweak_substrate")).size(11));
+        }
+
+        let app = self
+            .selected_package
+            .as_ref()
+            .and_then(|pkg| Some((pkg.get_bundle_identifier()?, pkg.get_executable()?)));
+        if let Some((bundle_id, executable)) = app {
+            let filtered_out = plan.items.iter().any(|item| {
+                item.filter
+                    .as_ref()
+                    .is_some_and(|f| !f.matches_app(&bundle_id, &executable))
+            });
+            if filtered_out {
+                items = items.push(
+                    text(t!("options_tweak_filter_mismatch"))
+                        .size(11)
+                        .style(text::danger)
+                        .wrapping(text::Wrapping::WordOrGlyph),
+                );
+            }
+        }
+
+        items.into()
+    }
+}
+
+/// Extracts the package off the UI thread to list what can be removed from it.
+fn load_bundle(package: Package) -> Task<Message> {
+    let (tx, rx) = oneshot::channel();
+
+    std::thread::spawn(move || {
+        let result = package
+            .get_package_bundle()
+            .and_then(|bundle| {
+                let removable = bundle
+                    .removable_bundles()?
+                    .iter()
+                    .filter_map(|b| bundle.relative_path(b).map(PathBuf::from))
+                    .collect::<Vec<_>>();
+                Ok((bundle, removable))
+            })
+            .map_err(|e| e.to_string());
+        let _ = tx.send(result);
+    });
+
+    Task::perform(
+        async move {
+            rx.await
+                .unwrap_or_else(|_| Err("Failed to read bundle".to_string()))
+        },
+        Message::BundleLoaded,
+    )
+}
+
+/// Plans a tweak off the UI thread, the iced executor isn't a tokio runtime.
+fn plan_tweak(path: PathBuf) -> Task<Message> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let tweak_path = path.clone();
+
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = rt
+            .block_on(Tweak::plan(&tweak_path))
+            .map_err(|e| e.to_string());
+        let _ = tx.send(result);
+    });
+
+    Task::perform(
+        async move {
+            std::thread::spawn(move || {
+                rx.recv()
+                    .unwrap_or_else(|_| Err("Failed to receive result".to_string()))
+            })
+            .join()
+            .unwrap()
+        },
+        move |result| Message::TweakPlanned(path, result),
+    )
 }
 
 const IOS_ICON_CORNER_RADIUS_FACTOR: f32 = 0.225;
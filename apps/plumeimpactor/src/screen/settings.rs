@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
-use iced::widget::{button, checkbox, column, container, pick_list, row, scrollable, text};
+use iced::widget::{
+    button, checkbox, column, container, pick_list, row, scrollable, text, text_input,
+};
 use iced::{Alignment, Element, Fill, Task};
-use plume_store::AccountStore;
+use plume_store::{AccountStore, AppIdQuota};
 use rust_i18n::t;
 
 use crate::appearance;
@@ -30,6 +32,12 @@ pub enum Message {
     TeamsLoaded(String, Vec<Team>),
     ToggleAutoStart(bool),
     SelectLocale(Option<String>),
+    CleanupAppIds,
+    CleanupFinished(Result<usize, String>),
+    ProfilePasswordChanged(String),
+    ExportProfile,
+    ImportProfile,
+    ProfileFinished(Option<Result<String, String>>), // None when the file dialog was dismissed
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -67,6 +75,11 @@ impl std::fmt::Display for LocaleChoice {
 pub struct SettingsScreen {
     teams: HashMap<String, Vec<Team>>,
     loading_teams: Option<String>,
+    cleaning_up: bool,
+    cleanup_status: Option<String>,
+    profile_password: String,
+    profile_busy: bool,
+    profile_status: Option<String>,
 }
 
 impl SettingsScreen {
@@ -74,9 +87,18 @@ impl SettingsScreen {
         Self {
             teams: HashMap::new(),
             loading_teams: None,
+            cleaning_up: false,
+            cleanup_status: None,
+            profile_password: String::new(),
+            profile_busy: false,
+            profile_status: None,
         }
     }
 
+    pub fn profile_password(&self) -> &str {
+        &self.profile_password
+    }
+
     pub fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::FetchTeams(ref email) => {
@@ -91,6 +113,36 @@ impl SettingsScreen {
             Message::ToggleAutoStart(_) => Task::none(),
             Message::SelectTeam(_, _) => Task::none(),
             Message::SelectLocale(_) => Task::none(),
+            Message::CleanupAppIds => {
+                self.cleaning_up = true;
+                self.cleanup_status = None;
+                Task::none()
+            }
+            Message::CleanupFinished(result) => {
+                self.cleaning_up = false;
+                self.cleanup_status = Some(match result {
+                    Ok(count) => format!("{} {}", t!("settings_app_ids_deleted"), count),
+                    Err(e) => e,
+                });
+                Task::none()
+            }
+            Message::ProfilePasswordChanged(password) => {
+                self.profile_password = password;
+                Task::none()
+            }
+            Message::ExportProfile | Message::ImportProfile => {
+                self.profile_busy = true;
+                self.profile_status = None;
+                Task::none()
+            }
+            Message::ProfileFinished(result) => {
+                self.profile_busy = false;
+                if let Some(result) = result {
+                    self.profile_password.clear();
+                    self.profile_status = Some(result.unwrap_or_else(|e| e));
+                }
+                Task::none()
+            }
             _ => Task::none(),
         }
     }
@@ -191,14 +243,55 @@ impl SettingsScreen {
             content = content.push(text(t!("settings_no_accounts_yet")));
         }
 
+        if let Some(account) = store.selected_account().filter(|a| !a.team_id().is_empty()) {
+            content = content.push(self.view_app_id_quota(store.app_id_quota(account.team_id())));
+        }
+
         let auto_start_enabled = crate::startup::auto_start_enabled();
         content = content.push(self.view_auto_start_toggle(auto_start_enabled));
         content = content.push(self.view_language_picker(selected_locale));
+        content = content.push(self.view_profile());
         content = content.push(self.view_account_buttons(selected_index));
 
         content.into()
     }
 
+    fn view_app_id_quota(&self, quota: AppIdQuota) -> Element<'_, Message> {
+        let mut details = column![text(format!(
+            "{} {}/{}",
+            t!("settings_app_id_quota"),
+            quota.remaining(),
+            quota.limit
+        ))]
+        .spacing(4);
+
+        if let Some(next_slot) = quota.next_slot {
+            let next_slot = next_slot.with_timezone(&chrono::Local);
+            details = details.push(text(format!(
+                "{} {}",
+                t!("settings_app_id_next_slot"),
+                next_slot.format("%Y-%m-%d %H:%M")
+            )));
+        }
+
+        if let Some(status) = &self.cleanup_status {
+            details = details.push(text(status));
+        }
+
+        let cleanup = button(appearance::icon_text(
+            appearance::MINUS,
+            t!("settings_cleanup_app_ids"),
+            None,
+        ))
+        .on_press_maybe((!self.cleaning_up).then_some(Message::CleanupAppIds))
+        .style(appearance::s_button);
+
+        row![details.width(Fill), cleanup]
+            .spacing(appearance::THEME_PADDING)
+            .align_y(Alignment::Center)
+            .into()
+    }
+
     fn view_auto_start_toggle(&self, auto_start_enabled: bool) -> Element<'_, Message> {
         checkbox(auto_start_enabled)
             .label(t!("settings_launch_on_startup"))
@@ -235,6 +328,43 @@ impl SettingsScreen {
             .into()
     }
 
+    fn view_profile(&self) -> Element<'_, Message> {
+        let ready = !self.profile_busy && !self.profile_password.is_empty();
+
+        let password = text_input(&t!("settings_profile_password"), &self.profile_password)
+            .on_input(Message::ProfilePasswordChanged)
+            .secure(true)
+            .width(Fill);
+        let export = button(appearance::icon_text(
+            appearance::SHARE,
+            t!("settings_export_profile"),
+            None,
+        ))
+        .on_press_maybe(ready.then_some(Message::ExportProfile))
+        .style(appearance::s_button);
+        let import = button(appearance::icon_text(
+            appearance::DOWNLOAD,
+            t!("settings_import_profile"),
+            None,
+        ))
+        .on_press_maybe(ready.then_some(Message::ImportProfile))
+        .style(appearance::s_button);
+
+        let mut profile = column![
+            text(t!("settings_profile")),
+            row![password, export, import]
+                .spacing(appearance::THEME_PADDING)
+                .align_y(Alignment::Center)
+        ]
+        .spacing(appearance::THEME_PADDING);
+
+        if let Some(status) = &self.profile_status {
+            profile = profile.push(text(status));
+        }
+
+        profile.into()
+    }
+
     fn view_account_buttons(&self, selected_index: Option<usize>) -> Element<'_, Message> {
         let mut buttons = row![
             button(appearance::icon_text(
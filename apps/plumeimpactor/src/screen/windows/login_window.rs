@@ -124,11 +124,11 @@ impl LoginWindow {
                 self.login_error = None;
                 let path = crate::defaults::get_data_path().join("accounts.json");
 
-                if let Ok(mut store) = tokio::runtime::Runtime::new()
-                    .unwrap()
-                    .block_on(async { AccountStore::load(&Some(path.clone())).await })
-                {
-                    let _ = store.accounts_add_sync(account);
+                if let Err(e) = AccountStore::update_sync(&path, |store| {
+                    store.accounts_add(account);
+                    Ok(())
+                }) {
+                    log::error!("Failed to save account: {:?}", e);
                 }
 
                 if let Some(id) = self.window_id {
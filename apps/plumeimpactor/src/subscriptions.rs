@@ -148,7 +148,7 @@ pub(crate) fn tray_menu_refresh_subscription() -> Subscription<Message> {
     })
 }
 
-pub(crate) fn certificate_reset_subscription() -> Subscription<Message> {
+pub(crate) fn confirmation_subscription() -> Subscription<Message> {
     Subscription::run(|| {
         iced::stream::channel(
             10,
@@ -157,8 +157,8 @@ pub(crate) fn certificate_reset_subscription() -> Subscription<Message> {
                 let (tx, mut rx) = iced::futures::channel::mpsc::unbounded::<Message>();
 
                 std::thread::spawn(move || {
-                    while let Some(request) = crate::certificate_reset::wait_for_request() {
-                        let _ = tx.unbounded_send(Message::CertificateResetRequested(request));
+                    while let Some(request) = crate::confirmation::wait_for_request() {
+                        let _ = tx.unbounded_send(Message::ConfirmationRequested(request));
                     }
                 });
 
@@ -277,13 +277,16 @@ pub(crate) fn installation_progress_listener(
 pub(crate) async fn run_installation(
     package: &plume_utils::Package,
     device: Option<&Device>,
+    loaded_bundle: Option<Bundle>,
     options: &plume_utils::SignerOptions,
     account: Option<&plume_store::GsaAccount>,
-    mut store: Option<&mut plume_store::AccountStore>,
+    store_path: Option<&std::path::Path>,
     tx: &std::sync::mpsc::Sender<(String, i32)>,
 ) -> Result<(), String> {
     use plume_core::{AnisetteConfiguration, CertificateIdentity, developer::DeveloperSession};
-    use plume_utils::{Signer, SignerInstallMode, SignerMode};
+    use plume_utils::{CompatibilityIssue, Signer, SignerInstallMode, SignerMode};
+
+    use crate::api_errors::describe;
 
     let package_file: Bundle;
     let mut options = options.clone();
@@ -293,6 +296,49 @@ pub(crate) async fn run_installation(
 
     send("Preparing package...".to_string(), 10);
 
+    // The package screen already extracted it to list removable bundles
+    let bundle = match loaded_bundle.filter(|bundle| bundle.bundle_dir().exists()) {
+        Some(bundle) => bundle,
+        None => {
+            send("Extracting package...".to_string(), 15);
+            package.get_package_bundle().map_err(|e| describe(&e))?
+        }
+    };
+
+    // Checked before signing, so an app the device can't run doesn't use up App IDs
+    if options.install_mode == SignerInstallMode::Install {
+        if let Some(dev) = device.filter(|dev| !dev.is_mac) {
+            send("Checking compatibility...".to_string(), 15);
+
+            match dev.check_compatibility(&bundle).await {
+                Ok(mut report) => {
+                    // The signer lowers MinimumOSVersion when asked to
+                    if options.features.support_minimum_os_version {
+                        report.issues.retain(|issue| {
+                            !matches!(issue, CompatibilityIssue::MinimumOsVersion { .. })
+                        });
+                    }
+
+                    if report.is_blocking() {
+                        return Err(format!("Incompatible with {}:\n{}", dev.name, report));
+                    }
+
+                    if !report.issues.is_empty() {
+                        log::warn!("{report}");
+                        let message = format!("{report}\n\nInstall anyway?");
+                        if !crate::confirmation::request_confirmation(
+                            "Compatibility warning",
+                            &message,
+                        ) {
+                            return Err("Installation cancelled".to_string());
+                        }
+                    }
+                }
+                Err(e) => log::warn!("Skipping compatibility check: {}", describe(&e)),
+            }
+        }
+    }
+
     match options.mode {
         SignerMode::Pem => {
             let Some(account) = account else {
@@ -308,9 +354,9 @@ pub(crate) async fn run_installation(
                     .set_configuration_path(crate::defaults::get_data_path()),
             )
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| describe(&e))?;
 
-            let teams_response = session.qh_list_teams().await.map_err(|e| e.to_string())?;
+            let teams_response = session.qh_list_teams().await.map_err(|e| describe(&e))?;
 
             if teams_response.teams.is_empty() {
                 return Err("No teams available for this account".to_string());
@@ -344,7 +390,7 @@ pub(crate) async fn run_installation(
                 Some(&mut on_certificate_reset),
             )
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| describe(&e))?;
 
             send("Ensuring device is registered...".to_string(), 30);
 
@@ -352,59 +398,58 @@ pub(crate) async fn run_installation(
                 session
                     .qh_ensure_device(team_id, &dev.name, &dev.udid)
                     .await
-                    .map_err(|e| e.to_string())?;
+                    .map_err(|e| describe(&e))?;
             }
 
-            send("Extracting package...".to_string(), 50);
-
             let mut signer = Signer::new(Some(identity), options.clone());
 
-            let bundle = package.get_package_bundle().map_err(|e| e.to_string())?;
-
             send("Signing package...".to_string(), 70);
 
             signer
                 .modify_bundle(&bundle, &Some(team_id.clone()))
                 .await
-                .map_err(|e| e.to_string())?;
-            signer
+                .map_err(|e| describe(&e))?;
+            let app_id_count = signer.app_id_count(&bundle).map_err(|e| describe(&e))?;
+            send(format!("Registering {} App ID(s)...", app_id_count), 70);
+
+            let registered = signer
                 .register_bundle(&bundle, &session, team_id, false)
-                .await
-                .map_err(|e| e.to_string())?;
+                .await;
+            if let Some(store_path) = store_path {
+                if let Err(e) = plume_store::AccountStore::update_sync(store_path, |store| {
+                    store.record_app_ids(team_id, &signer.created_app_ids);
+                    Ok(())
+                }) {
+                    log::error!("Failed to record created App IDs: {e}");
+                }
+            }
+            registered.map_err(|e| describe(&e))?;
             signer
                 .sign_bundle(&bundle)
                 .await
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| describe(&e))?;
 
             options = signer.options.clone();
             package_file = bundle;
         }
         SignerMode::Adhoc => {
-            send("Extracting package...".to_string(), 50);
-
             let mut signer = Signer::new(None, options.clone());
 
-            let bundle = package.get_package_bundle().map_err(|e| e.to_string())?;
-
             send("Signing package...".to_string(), 70);
 
             signer
                 .modify_bundle(&bundle, &None)
                 .await
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| describe(&e))?;
             signer
                 .sign_bundle(&bundle)
                 .await
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| describe(&e))?;
 
             options = signer.options.clone();
             package_file = bundle;
         }
         _ => {
-            send("Extracting package...".to_string(), 50);
-
-            let bundle = package.get_package_bundle().map_err(|e| e.to_string())?;
-
             package_file = bundle;
         }
     }
@@ -425,7 +470,7 @@ pub(crate) async fn run_installation(
                         })
                     })
                     .await
-                    .map_err(|e| format!("Install error: {}", e))?;
+                    .map_err(|e| format!("Install error: {}", describe(&e)))?;
 
                     if options.app.supports_pairing_file() {
                         if let (Some(custom_identifier), Some(pairing_file_bundle_path)) = (
@@ -445,7 +490,7 @@ pub(crate) async fn run_installation(
 
                     plume_utils::install_app_mac(&package_file.bundle_dir())
                         .await
-                        .map_err(|e| e.to_string())?;
+                        .map_err(|e| describe(&e))?;
                 }
             } else {
                 return Err("No device connected for installation".to_string());
@@ -455,8 +500,8 @@ pub(crate) async fn run_installation(
             send("Exporting...".to_string(), 90);
 
             let archive_path = package
-                .get_archive_based_on_path(&package_file.bundle_dir())
-                .map_err(|e| e.to_string())?;
+                .get_archive_based_on_path(&package_file.bundle_dir(), options.compression)
+                .map_err(|e| describe(&e))?;
 
             let file = rfd::AsyncFileDialog::new()
                 .set_title("Save Package As")
@@ -472,7 +517,7 @@ pub(crate) async fn run_installation(
             if let Some(save_path) = file {
                 tokio::fs::copy(&archive_path, &save_path.path())
                     .await
-                    .map_err(|e| e.to_string())?;
+                    .map_err(|e| describe(&e))?;
             }
         }
     }
@@ -482,7 +527,7 @@ pub(crate) async fn run_installation(
         let path = get_data_path().join("refresh_store");
         tokio::fs::create_dir_all(&path)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| describe(&e))?;
 
         let original_name = package_file
             .bundle_dir()
@@ -500,9 +545,9 @@ pub(crate) async fn run_installation(
 
         plume_utils::copy_dir_recursively(&package_file.bundle_dir(), &dest_path)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| describe(&e))?;
 
-        if let (Some(dev), Some(account), Some(store)) = (&device, &account, store.as_mut()) {
+        if let (Some(dev), Some(account), Some(store_path)) = (&device, &account, store_path) {
             let embedded_prov_path = dest_path.join("embedded.mobileprovision");
 
             let provision_path = if embedded_prov_path.exists() {
@@ -529,30 +574,32 @@ pub(crate) async fn run_installation(
                         scheduled_refresh,
                     };
 
-                    let mut refresh_device = store
-                        .get_refresh_device(&dev.udid)
-                        .cloned()
-                        .unwrap_or_else(|| plume_store::RefreshDevice {
-                            udid: dev.udid.clone(),
-                            name: dev.name.clone(),
-                            account: account.email().clone(),
-                            apps: Vec::new(),
-                            is_mac: dev.is_mac,
-                        });
-
-                    if let Some(existing_app) = refresh_device
-                        .apps
-                        .iter_mut()
-                        .find(|a| a.bundle_id == refresh_app.bundle_id)
-                    {
-                        *existing_app = refresh_app;
-                    } else {
-                        refresh_device.apps.push(refresh_app);
-                    }
+                    plume_store::AccountStore::update_sync(store_path, |store| {
+                        let mut refresh_device = store
+                            .get_refresh_device(&dev.udid)
+                            .cloned()
+                            .unwrap_or_else(|| plume_store::RefreshDevice {
+                                udid: dev.udid.clone(),
+                                name: dev.name.clone(),
+                                account: account.email().clone(),
+                                apps: Vec::new(),
+                                is_mac: dev.is_mac,
+                            });
+
+                        if let Some(existing_app) = refresh_device
+                            .apps
+                            .iter_mut()
+                            .find(|a| a.bundle_id == refresh_app.bundle_id)
+                        {
+                            *existing_app = refresh_app;
+                        } else {
+                            refresh_device.apps.push(refresh_app);
+                        }
 
-                    store
-                        .add_or_update_refresh_device_sync(refresh_device)
-                        .map_err(|e| e.to_string())?;
+                        store.add_or_update_refresh_device(refresh_device);
+                        Ok(())
+                    })
+                    .map_err(|e| describe(&e))?;
                 }
             }
         }
@@ -662,3 +709,122 @@ pub(crate) async fn fetch_teams(
         })
         .collect())
 }
+
+/// Deletes App IDs of the account's team that no app saved for refreshing uses, after asking.
+pub(crate) async fn cleanup_app_ids(
+    account: &plume_store::GsaAccount,
+    store: &plume_store::AccountStore,
+) -> Result<usize, String> {
+    use plume_core::{AnisetteConfiguration, developer::DeveloperSession};
+
+    let team_id = account.team_id();
+    if team_id.is_empty() {
+        return Err("Select a team in Settings first".to_string());
+    }
+
+    let session = DeveloperSession::new(
+        account.adsid().clone(),
+        account.xcode_gs_token().clone(),
+        AnisetteConfiguration::default().set_configuration_path(crate::defaults::get_data_path()),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let unused: Vec<_> = session
+        .qh_list_app_ids(team_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .app_ids
+        .into_iter()
+        .filter(|app_id| !app_id.is_wild_card && !store.is_app_id_in_use(&app_id.identifier))
+        .collect();
+
+    if unused.is_empty() {
+        return Ok(0);
+    }
+
+    let identifiers: Vec<_> = unused
+        .iter()
+        .map(|app_id| app_id.identifier.as_str())
+        .collect();
+    let message = format!(
+        "These App IDs are not used by any app saved for refreshing:\n{}\n\nDelete them? This does not give back App ID slots.",
+        identifiers.join("\n")
+    );
+    if !crate::confirmation::request_confirmation("Clean up App IDs", &message) {
+        return Ok(0);
+    }
+
+    for app_id in &unused {
+        session
+            .qh_delete_app_id(team_id, &app_id.app_id_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        log::info!("Deleted App ID {}", app_id.identifier);
+    }
+
+    Ok(unused.len())
+}
+
+/// Saves a profile where the user picks, `None` when the dialog is dismissed.
+pub(crate) async fn export_profile(password: String) -> Option<Result<String, String>> {
+    use crate::api_errors::describe;
+
+    let file = rfd::AsyncFileDialog::new()
+        .set_title("Export Profile")
+        .set_file_name("impactor.plumeprofile")
+        .save_file()
+        .await?;
+    let path = file.path().to_path_buf();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let output = path.clone();
+    std::thread::spawn(move || {
+        let _ = tx.send(plume_utils::export_profile(
+            &get_data_path(),
+            &password,
+            &output,
+        ));
+    });
+
+    Some(match rx.recv() {
+        Ok(Ok(_)) => Ok(format!(
+            "{} {}",
+            rust_i18n::t!("settings_profile_exported"),
+            path.display()
+        )),
+        Ok(Err(e)) => Err(describe(&e)),
+        Err(_) => Err("Export stopped".to_string()),
+    })
+}
+
+/// Merges a profile the user picks into this machine, `None` when the dialog is dismissed.
+pub(crate) async fn import_profile(password: String) -> Option<Result<String, String>> {
+    use crate::api_errors::describe;
+
+    let file = rfd::AsyncFileDialog::new()
+        .set_title("Import Profile")
+        .add_filter("Impactor profile", &["plumeprofile"])
+        .pick_file()
+        .await?;
+    let path = file.path().to_path_buf();
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(plume_utils::import_profile(
+            &get_data_path(),
+            &password,
+            &path,
+        ));
+    });
+
+    Some(match rx.recv() {
+        Ok(Ok(summary)) => Ok(format!(
+            "{} {}",
+            rust_i18n::t!("settings_profile_imported"),
+            summary.accounts
+        )),
+        Ok(Err(e)) => Err(describe(&e)),
+        Err(_) => Err("Import stopped".to_string()),
+    })
+}
@@ -3,12 +3,23 @@ use std::path::PathBuf;
 
 use anyhow::{Ok, Result};
 use clap::{Args, Subcommand};
-use dialoguer::Select;
-
-use plume_core::{AnisetteConfiguration, auth::Account, developer::DeveloperSession};
-use plume_store::AccountStore;
-
-use crate::get_data_path;
+use dialoguer::{Confirm, MultiSelect, Select};
+use serde::Serialize;
+
+use plume_core::{
+    AnisetteConfiguration,
+    auth::Account,
+    developer::{
+        DeveloperSession,
+        qh::{app_ids::AppID, certs::Cert, devices::Device},
+    },
+};
+use plume_store::{AccountStore, AppIdQuota, FREE_APP_ID_WINDOW_DAYS};
+
+use crate::{
+    get_data_path,
+    output::{OutputFormat, print_json},
+};
 
 #[derive(Debug, Args)]
 #[command(arg_required_else_help = true)]
@@ -34,7 +45,7 @@ pub enum AccountCommands {
     Devices(DevicesArgs),
     /// Register a new device
     RegisterDevice(RegisterDeviceArgs),
-    /// List all app IDs for a team
+    /// List all app IDs for a team and how many more can be created
     AppIds(AppIdsArgs),
 }
 
@@ -87,6 +98,9 @@ pub struct AppIdsArgs {
     /// Team ID to list app IDs for
     #[arg(short = 't', long = "team", value_name = "TEAM_ID")]
     pub team_id: Option<String>,
+    /// Pick App IDs no app saved for refreshing uses and delete them
+    #[arg(long = "cleanup")]
+    pub cleanup: bool,
 }
 
 #[derive(Debug, Args)]
@@ -96,16 +110,129 @@ pub struct SwitchArgs {
     pub email: String,
 }
 
-pub async fn execute(args: AccountArgs) -> Result<()> {
+#[derive(Debug, Serialize)]
+struct AccountRecord<'a> {
+    email: &'a str,
+    first_name: &'a str,
+    selected: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct CertificateRecord<'a> {
+    name: &'a str,
+    certificate_id: &'a str,
+    serial_number: &'a str,
+    status: &'a str,
+    expiration_date: String,
+    cert_type: Option<&'a str>,
+    machine_id: Option<&'a str>,
+    machine_name: Option<&'a str>,
+}
+
+impl<'a> From<&'a Cert> for CertificateRecord<'a> {
+    fn from(cert: &'a Cert) -> Self {
+        Self {
+            name: &cert.name,
+            certificate_id: &cert.certificate_id,
+            serial_number: &cert.serial_number,
+            status: &cert.status,
+            expiration_date: cert.expiration_date.to_xml_format(),
+            cert_type: cert.cert_type.as_ref().map(|t| t.name.as_str()),
+            machine_id: cert.machine_id.as_deref(),
+            machine_name: cert.machine_name.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DeviceRecord<'a> {
+    device_id: &'a str,
+    name: &'a str,
+    udid: &'a str,
+    platform: &'a str,
+    class: &'a str,
+    status: &'a str,
+    expiration_date: Option<String>,
+}
+
+impl<'a> From<&'a Device> for DeviceRecord<'a> {
+    fn from(device: &'a Device) -> Self {
+        Self {
+            device_id: &device.device_id,
+            name: &device.name,
+            udid: &device.device_number,
+            platform: &device.device_platform,
+            class: &device.device_class,
+            status: &device.status,
+            expiration_date: device.expiration_date.as_ref().map(|d| d.to_xml_format()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AppIdRecord<'a> {
+    app_id_id: &'a str,
+    name: &'a str,
+    identifier: &'a str,
+    prefix: &'a str,
+    wildcard: bool,
+}
+
+impl<'a> From<&'a AppID> for AppIdRecord<'a> {
+    fn from(app_id: &'a AppID) -> Self {
+        Self {
+            app_id_id: &app_id.app_id_id,
+            name: &app_id.name,
+            identifier: &app_id.identifier,
+            prefix: &app_id.prefix,
+            wildcard: app_id.is_wild_card,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AppIdQuotaRecord {
+    used: usize,
+    limit: usize,
+    remaining: usize,
+    next_slot: Option<String>,
+}
+
+impl From<AppIdQuota> for AppIdQuotaRecord {
+    fn from(quota: AppIdQuota) -> Self {
+        Self {
+            used: quota.used,
+            limit: quota.limit,
+            remaining: quota.remaining(),
+            next_slot: quota.next_slot.map(|d| d.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AppIdsRecord<'a> {
+    app_ids: Vec<AppIdRecord<'a>>,
+    quota: AppIdQuotaRecord,
+    deleted: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct AccountStatus<'a> {
+    email: &'a str,
+}
+
+pub async fn execute(args: AccountArgs, format: OutputFormat) -> Result<()> {
     match args.command {
-        AccountCommands::Login(login_args) => login(login_args).await,
-        AccountCommands::Logout => logout().await,
-        AccountCommands::List => list_accounts().await,
-        AccountCommands::Switch(switch_args) => switch_account(switch_args).await,
-        AccountCommands::Certificates(cert_args) => certificates(cert_args).await,
-        AccountCommands::Devices(device_args) => devices(device_args).await,
-        AccountCommands::RegisterDevice(register_args) => register_device(register_args).await,
-        AccountCommands::AppIds(app_id_args) => app_ids(app_id_args).await,
+        AccountCommands::Login(login_args) => login(login_args, format).await,
+        AccountCommands::Logout => logout(format).await,
+        AccountCommands::List => list_accounts(format).await,
+        AccountCommands::Switch(switch_args) => switch_account(switch_args, format).await,
+        AccountCommands::Certificates(cert_args) => certificates(cert_args, format).await,
+        AccountCommands::Devices(device_args) => devices(device_args, format).await,
+        AccountCommands::RegisterDevice(register_args) => {
+            register_device(register_args, format).await
+        }
+        AccountCommands::AppIds(app_id_args) => app_ids(app_id_args, format).await,
     }
 }
 
@@ -113,18 +240,42 @@ fn get_settings_path() -> PathBuf {
     get_data_path().join("accounts.json")
 }
 
+/// Remembers App IDs created on `team_id`, free teams can only create a few per week.
+pub fn record_created_app_ids(team_id: &str, identifiers: &[String]) -> Result<()> {
+    if identifiers.is_empty() {
+        return Ok(());
+    }
+
+    AccountStore::update_sync(&get_settings_path(), |settings| {
+        settings.record_app_ids(team_id, identifiers);
+        std::result::Result::Ok(())
+    })?;
+    Ok(())
+}
+
 pub async fn get_authenticated_account() -> Result<DeveloperSession> {
+    get_authenticated_account_for(None).await
+}
+
+/// Restores a session for the given saved account, or the selected one when `email` is `None`.
+pub async fn get_authenticated_account_for(email: Option<&str>) -> Result<DeveloperSession> {
     let settings_path = get_settings_path();
     let settings = AccountStore::load(&Some(settings_path.clone())).await?;
 
-    let gsa_account = settings
-        .selected_account()
-        .ok_or_else(|| {
+    let gsa_account = match email {
+        Some(email) => settings.get_account(email).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Account '{}' not found. Use 'account list' to see available accounts.",
+                email
+            )
+        })?,
+        None => settings.selected_account().ok_or_else(|| {
             anyhow::anyhow!(
                 "No account selected. Please login first using 'plumesign account login'"
             )
-        })?
-        .clone();
+        })?,
+    }
+    .clone();
 
     let anisette_config = AnisetteConfiguration::default().set_configuration_path(get_data_path());
 
@@ -140,7 +291,7 @@ pub async fn get_authenticated_account() -> Result<DeveloperSession> {
     Ok(session)
 }
 
-async fn login(args: LoginArgs) -> Result<()> {
+async fn login(args: LoginArgs, format: OutputFormat) -> Result<()> {
     let tfa_closure = |req: plume_core::auth::TwoFactorRequest| -> std::result::Result<
         plume_core::auth::TwoFactorAction,
         String,
@@ -214,35 +365,48 @@ async fn login(args: LoginArgs) -> Result<()> {
     println!("Logging in...");
     let account = Account::login(login_closure, tfa_closure, anisette_config).await?;
 
-    let settings_path = get_settings_path();
-    let mut settings = AccountStore::load(&Some(settings_path.clone())).await?;
-    settings
-        .accounts_add_from_session(username, account)
-        .await?;
+    let gsa_account = plume_store::account_from_session(username.clone(), account).await?;
+    AccountStore::update(&get_settings_path(), |settings| {
+        settings.accounts_add(gsa_account);
+        std::result::Result::Ok(())
+    })
+    .await?;
 
     log::info!("Successfully logged in and account saved.");
 
+    if format.is_json() {
+        print_json(&AccountStatus { email: &username })?;
+    }
+
     Ok(())
 }
 
-async fn logout() -> Result<()> {
-    let settings_path = get_settings_path();
-    let mut settings = AccountStore::load(&Some(settings_path.clone())).await?;
-
-    let email = settings
-        .selected_account()
-        .ok_or_else(|| anyhow::anyhow!("No account currently logged in"))?
-        .email()
-        .clone();
+async fn logout(format: OutputFormat) -> Result<()> {
+    // Read the selected account under the lock, it may have been switched meanwhile
+    let mut email = None;
+    AccountStore::update(&get_settings_path(), |settings| {
+        email = settings
+            .selected_account()
+            .map(|account| account.email().clone());
+        if let Some(email) = &email {
+            settings.accounts_remove(email);
+        }
+        std::result::Result::Ok(())
+    })
+    .await?;
 
-    settings.accounts_remove(&email).await?;
+    let email = email.ok_or_else(|| anyhow::anyhow!("No account currently logged in"))?;
 
     log::info!("Successfully logged out and removed account.");
 
+    if format.is_json() {
+        print_json(&AccountStatus { email: &email })?;
+    }
+
     Ok(())
 }
 
-async fn certificates(args: CertificatesArgs) -> Result<()> {
+async fn certificates(args: CertificatesArgs, format: OutputFormat) -> Result<()> {
     let session = get_authenticated_account().await?;
 
     let team_id = if args.team_id.is_none() {
@@ -253,12 +417,16 @@ async fn certificates(args: CertificatesArgs) -> Result<()> {
 
     let p = session.qh_list_certs(&team_id).await?.certificates;
 
+    if format.is_json() {
+        return print_json(&p.iter().map(CertificateRecord::from).collect::<Vec<_>>());
+    }
+
     log::info!("{:#?}", p);
 
     Ok(())
 }
 
-async fn devices(args: DevicesArgs) -> Result<()> {
+async fn devices(args: DevicesArgs, format: OutputFormat) -> Result<()> {
     let session = get_authenticated_account().await?;
 
     let team_id = if args.team_id.is_none() {
@@ -269,12 +437,16 @@ async fn devices(args: DevicesArgs) -> Result<()> {
 
     let p = session.qh_list_devices(&team_id).await?.devices;
 
+    if format.is_json() {
+        return print_json(&p.iter().map(DeviceRecord::from).collect::<Vec<_>>());
+    }
+
     log::info!("{:#?}", p);
 
     Ok(())
 }
 
-async fn register_device(args: RegisterDeviceArgs) -> Result<()> {
+async fn register_device(args: RegisterDeviceArgs, format: OutputFormat) -> Result<()> {
     let session = get_authenticated_account().await?;
 
     let team_id = if args.team_id.is_none() {
@@ -288,6 +460,10 @@ async fn register_device(args: RegisterDeviceArgs) -> Result<()> {
         .await?
         .device;
 
+    if format.is_json() {
+        return print_json(&DeviceRecord::from(&p));
+    }
+
     log::info!("{:#?}", p);
 
     Ok(())
@@ -310,7 +486,7 @@ pub async fn teams(session: &DeveloperSession) -> Result<String> {
     Ok(teams[selection].team_id.clone())
 }
 
-pub async fn app_ids(args: AppIdsArgs) -> Result<()> {
+pub async fn app_ids(args: AppIdsArgs, format: OutputFormat) -> Result<()> {
     let session = get_authenticated_account().await?;
 
     let team_id = if args.team_id.is_none() {
@@ -319,26 +495,114 @@ pub async fn app_ids(args: AppIdsArgs) -> Result<()> {
         args.team_id.unwrap()
     };
 
-    let p = session.v1_list_app_ids(&team_id, None).await?.data;
+    let mut p = session.qh_list_app_ids(&team_id).await?.app_ids;
+    let settings = AccountStore::load(&Some(get_settings_path())).await?;
+
+    let deleted = if args.cleanup {
+        cleanup_app_ids(&session, &team_id, &p, &settings).await?
+    } else {
+        Vec::new()
+    };
+    p.retain(|app_id| !deleted.contains(&app_id.app_id_id));
+
+    let quota = settings.app_id_quota(&team_id);
+
+    if format.is_json() {
+        return print_json(&AppIdsRecord {
+            app_ids: p.iter().map(AppIdRecord::from).collect(),
+            quota: quota.into(),
+            deleted,
+        });
+    }
 
     log::info!("{:#?}", p);
+    log::info!(
+        "Created {} of {} App IDs allowed for free teams in the last {} days, {} remaining",
+        quota.used,
+        quota.limit,
+        FREE_APP_ID_WINDOW_DAYS,
+        quota.remaining()
+    );
+    if let Some(next_slot) = quota.next_slot {
+        log::info!("Next slot frees at {}", next_slot.to_rfc3339());
+    }
 
     Ok(())
 }
 
-async fn list_accounts() -> Result<()> {
+/// Offers to delete App IDs that no saved refresh needs, returning the deleted `app_id_id`s.
+async fn cleanup_app_ids(
+    session: &DeveloperSession,
+    team_id: &String,
+    app_ids: &[AppID],
+    settings: &AccountStore,
+) -> Result<Vec<String>> {
+    let unused: Vec<&AppID> = app_ids
+        .iter()
+        .filter(|app_id| !app_id.is_wild_card && !settings.is_app_id_in_use(&app_id.identifier))
+        .collect();
+
+    if unused.is_empty() {
+        log::info!("Every App ID is used by an app saved for refreshing");
+        return Ok(Vec::new());
+    }
+
+    let items: Vec<String> = unused
+        .iter()
+        .map(|app_id| format!("{} ({})", app_id.name, app_id.identifier))
+        .collect();
+
+    log::info!("Deleting App IDs does not give back slots, only time does.");
+    let selection = MultiSelect::new()
+        .with_prompt("Select the App IDs to delete")
+        .items(&items)
+        .interact()?;
+
+    if selection.is_empty()
+        || !Confirm::new()
+            .with_prompt(format!("Delete {} App ID(s)?", selection.len()))
+            .default(false)
+            .interact()?
+    {
+        return Ok(Vec::new());
+    }
+
+    let mut deleted = Vec::new();
+    for index in selection {
+        let app_id = unused[index];
+        session.qh_delete_app_id(team_id, &app_id.app_id_id).await?;
+        log::info!("Deleted {}", app_id.identifier);
+        deleted.push(app_id.app_id_id.clone());
+    }
+
+    Ok(deleted)
+}
+
+async fn list_accounts(format: OutputFormat) -> Result<()> {
     let settings_path = get_settings_path();
     let settings = AccountStore::load(&Some(settings_path)).await?;
 
     let accounts = settings.accounts();
+    let selected_email = settings.selected_account().map(|a| a.email().clone());
+
+    if format.is_json() {
+        let mut records = accounts
+            .iter()
+            .map(|(email, account)| AccountRecord {
+                email,
+                first_name: account.first_name(),
+                selected: Some(email) == selected_email.as_ref(),
+            })
+            .collect::<Vec<_>>();
+        records.sort_by(|a, b| a.email.cmp(b.email));
+        return print_json(&records);
+    }
 
     if accounts.is_empty() {
         log::info!("No accounts found. Use 'account login' to add an account.");
         return Ok(());
     }
 
-    let selected_email = settings.selected_account().map(|a| a.email().clone());
-
     log::info!("Saved accounts:");
     for (email, account) in accounts {
         let selected = if Some(email) == selected_email.as_ref() {
@@ -353,20 +617,29 @@ async fn list_accounts() -> Result<()> {
     Ok(())
 }
 
-async fn switch_account(args: SwitchArgs) -> Result<()> {
-    let settings_path = get_settings_path();
-    let mut settings = AccountStore::load(&Some(settings_path)).await?;
+async fn switch_account(args: SwitchArgs, format: OutputFormat) -> Result<()> {
+    let mut found = false;
+    AccountStore::update(&get_settings_path(), |settings| {
+        found = settings.get_account(&args.email).is_some();
+        if found {
+            settings.account_select(&args.email)?;
+        }
+        std::result::Result::Ok(())
+    })
+    .await?;
 
-    if settings.get_account(&args.email).is_none() {
+    if !found {
         return Err(anyhow::anyhow!(
             "Account '{}' not found. Use 'account list' to see available accounts.",
             args.email
         ));
     }
 
-    settings.account_select(&args.email).await?;
-
     log::info!("Switched to account: {}", args.email);
 
+    if format.is_json() {
+        print_json(&AccountStatus { email: &args.email })?;
+    }
+
     Ok(())
 }
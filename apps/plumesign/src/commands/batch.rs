@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use clap::Args;
+use futures::StreamExt;
+use serde::Serialize;
+
+use plume_core::{CertificateIdentity, developer::DeveloperSession};
+use plume_utils::{Package, Signer, SignerCompression, SignerManifest, SignerMode, SignerOptions};
+
+use crate::{
+    commands::account::{get_authenticated_account_for, record_created_app_ids, teams},
+    get_data_path,
+    output::{OutputFormat, Reported, print_json},
+};
+
+#[derive(Debug, Args)]
+#[command(arg_required_else_help = true)]
+pub struct BatchArgs {
+    /// Directories or glob patterns of packages to sign (.ipa or .tipa)
+    #[arg(value_name = "INPUT", num_args = 1.., required = true)]
+    pub inputs: Vec<String>,
+    /// Directory to write the signed packages to
+    #[arg(long, short, value_name = "OUTPUT_DIR")]
+    pub output_dir: PathBuf,
+    /// Signing manifest (.toml or .json) with the signer options applied to every package
+    #[arg(long, value_name = "MANIFEST")]
+    pub manifest: Option<PathBuf>,
+    /// PEM files for certificate and private key
+    #[arg(long = "pem", value_name = "PEM", num_args = 1..)]
+    pub pem_files: Option<Vec<PathBuf>>,
+    /// Use Apple ID credentials for signing
+    #[arg(long = "apple-id")]
+    pub apple_id: bool,
+    /// Number of packages to process at the same time
+    #[arg(long, short, value_name = "JOBS", default_value_t = 4)]
+    pub jobs: usize,
+    /// Compression level of the signed packages, from 0 (store) to 9
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u8).range(0..=9))]
+    pub compression_level: Option<u8>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchRecord<'a> {
+    input: &'a PathBuf,
+    output: Option<&'a PathBuf>,
+    error: Option<String>,
+}
+
+pub async fn execute(args: BatchArgs, format: OutputFormat) -> Result<()> {
+    let manifest = match &args.manifest {
+        Some(manifest_path) => SignerManifest::load(manifest_path)?,
+        None => SignerManifest::default(),
+    };
+
+    let packages = collect_packages(&args.inputs)?;
+    if packages.is_empty() {
+        return Err(anyhow::anyhow!("No packages matched the given inputs."));
+    }
+    check_output_names(&packages)?;
+
+    tokio::fs::create_dir_all(&args.output_dir).await?;
+
+    let mut options = manifest.options;
+    if let Some(level) = args.compression_level {
+        options.compression = SignerCompression::from_level(level);
+    }
+
+    // The session and identity are created once and shared by every package.
+    let (certificate, account) = if let Some(pem_files) = args.pem_files {
+        let cert_identity = CertificateIdentity::new_with_paths(Some(pem_files)).await?;
+
+        options.mode = SignerMode::Pem;
+        (Some(Arc::new(cert_identity)), None)
+    } else if args.apple_id {
+        let session = get_authenticated_account_for(manifest.account.as_deref()).await?;
+        let team_id = match manifest.team_id {
+            Some(team_id) => team_id,
+            None => teams(&session).await?,
+        };
+        let cert_identity = CertificateIdentity::new_with_session(
+            &session,
+            get_data_path(),
+            None,
+            &team_id,
+            false,
+            None,
+        )
+        .await?;
+
+        options.mode = SignerMode::Pem;
+        (Some(Arc::new(cert_identity)), Some((session, team_id)))
+    } else {
+        if options.mode != SignerMode::None {
+            options.mode = SignerMode::Adhoc;
+        }
+        (None, None)
+    };
+
+    log::info!(
+        "Signing {} package(s) with up to {} job(s)...",
+        packages.len(),
+        args.jobs.max(1)
+    );
+
+    let account = account.map(Arc::new);
+    let runtime = tokio::runtime::Handle::current();
+    let results: Vec<(PathBuf, Result<PathBuf>)> = futures::stream::iter(packages)
+        .map(|path| {
+            let certificate = certificate.clone();
+            let options = options.clone();
+            let output_dir = args.output_dir.clone();
+            let account = account.clone();
+            let runtime = runtime.clone();
+            async move {
+                // Signing and archiving block, so every job gets a thread of its own
+                let job = tokio::task::spawn_blocking({
+                    let path = path.clone();
+                    move || {
+                        runtime.block_on(sign_package(
+                            &path,
+                            &output_dir,
+                            certificate,
+                            options,
+                            account.as_deref(),
+                        ))
+                    }
+                });
+                let result = job.await.unwrap_or_else(|e| Err(e.into()));
+                (path, result)
+            }
+        })
+        .buffer_unordered(args.jobs.max(1))
+        .collect()
+        .await;
+
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+
+    if format.is_json() {
+        let records = results
+            .iter()
+            .map(|(input, result)| BatchRecord {
+                input,
+                output: result.as_ref().ok(),
+                error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            })
+            .collect::<Vec<_>>();
+        print_json(&records)?;
+
+        // The records already describe every failure, only the exit code is left to report.
+        if let Some(e) = results.into_iter().find_map(|(_, r)| r.err()) {
+            return Err(e.context(Reported));
+        }
+        return Ok(());
+    }
+
+    log::info!("Batch summary:");
+    for (path, result) in &results {
+        match result {
+            Ok(output_path) => {
+                log::info!(" ✔ {} -> {}", path.display(), output_path.display())
+            }
+            Err(e) => log::error!(" ✘ {}: {}", path.display(), e),
+        }
+    }
+    log::info!("{} succeeded, {} failed", results.len() - failed, failed);
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} package(s) failed to sign",
+            failed,
+            results.len()
+        ));
+    }
+
+    Ok(())
+}
+
+async fn sign_package(
+    path: &Path,
+    output_dir: &Path,
+    certificate: Option<Arc<CertificateIdentity>>,
+    options: SignerOptions,
+    account: Option<&(DeveloperSession, String)>,
+) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid package path"))?;
+    let output_path = output_dir.join(file_name);
+
+    if output_path == path {
+        return Err(anyhow::anyhow!(
+            "Output would overwrite the input package, use a different output directory"
+        ));
+    }
+
+    log::info!("Signing {}...", path.display());
+
+    let pkg = Package::new(path.to_path_buf())?;
+    let result = async {
+        let bundle = pkg.get_package_bundle()?;
+        let compression = options.compression;
+        // Every package gets its own signer, `modify_bundle` rewrites the options per bundle.
+        let mut signer = Signer::new_shared(certificate, options);
+
+        signer
+            .modify_bundle(&bundle, &account.map(|(_, team_id)| team_id.clone()))
+            .await?;
+
+        if let Some((session, team_id)) = account {
+            log::info!(
+                "{}: registering {} App ID(s)",
+                path.display(),
+                signer.app_id_count(&bundle)?
+            );
+            let registered = signer
+                .register_bundle(&bundle, session, team_id, false)
+                .await;
+            record_created_app_ids(team_id, &signer.created_app_ids)?;
+            registered?;
+        }
+
+        signer.sign_bundle(&bundle).await?;
+
+        let archived_path = pkg.get_archive_based_on_path(bundle.bundle_dir(), compression)?;
+        tokio::fs::copy(&archived_path, &output_path).await?;
+
+        Ok::<_, anyhow::Error>(output_path)
+    }
+    .await;
+
+    if std::env::var("PLUME_DELETE_AFTER_FINISHED").is_err() {
+        pkg.remove_package_stage();
+    }
+
+    result
+}
+
+/// Packages are written to the output directory by file name, two inputs with the
+/// same name would overwrite each other.
+fn check_output_names(packages: &[PathBuf]) -> Result<()> {
+    let mut names = HashMap::new();
+
+    for path in packages {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        // Lowercased, macOS and Windows file systems don't tell the difference
+        let key = name.to_string_lossy().to_lowercase();
+        if let Some(previous) = names.insert(key, path) {
+            return Err(anyhow::anyhow!(
+                "{} and {} would both be written as {}, rename one of them",
+                previous.display(),
+                path.display(),
+                name.to_string_lossy()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Expands directories and glob patterns into a sorted, deduplicated list of packages.
+fn collect_packages(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let is_package = |p: &Path| {
+        p.is_file()
+            && p.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("ipa") || e.eq_ignore_ascii_case("tipa"))
+    };
+
+    let mut packages = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        let matched = if path.is_dir() {
+            std::fs::read_dir(path)?
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|p| is_package(p))
+                .collect::<Vec<_>>()
+        } else {
+            glob::glob(input)?
+                .filter_map(Result::ok)
+                .filter(|p| is_package(p))
+                .collect::<Vec<_>>()
+        };
+
+        if matched.is_empty() {
+            log::warn!("No packages found for input: {}", input);
+        }
+
+        packages.extend(matched);
+    }
+
+    packages.sort();
+    packages.dedup();
+
+    Ok(packages)
+}
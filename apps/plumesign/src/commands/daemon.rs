@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Args;
+use futures::StreamExt;
+use idevice::usbmuxd::{UsbmuxdConnection, UsbmuxdListenEvent};
+use plume_store::AccountStore;
+use plume_utils::{ConnectedDevices, Device, RefreshDaemon};
+
+use crate::get_data_path;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Args)]
+pub struct DaemonArgs {
+    /// Revoke existing certificates when the account has no room for a new one.
+    /// This breaks existing SideStore and AltStore installs
+    #[arg(long = "allow-certificate-reset")]
+    pub allow_certificate_reset: bool,
+}
+
+pub async fn execute(args: DaemonArgs) -> Result<()> {
+    let store = AccountStore::load(&Some(get_data_path().join("accounts.json"))).await?;
+    let apps: usize = store
+        .refreshes()
+        .values()
+        .map(|device| device.apps.len())
+        .sum();
+    log::info!(
+        "Refreshing {} app(s) on {} device(s) from {}",
+        apps,
+        store.refreshes().len(),
+        get_data_path().display()
+    );
+
+    let mut daemon = RefreshDaemon::new(get_data_path());
+    if args.allow_certificate_reset {
+        daemon = daemon.with_certificate_reset(|| {
+            log::warn!(
+                "Resetting certificates, existing SideStore and AltStore installs will stop working"
+            );
+            true
+        });
+    }
+
+    tokio::join!(listen_for_devices(daemon.connected_devices()), daemon.run());
+
+    Ok(())
+}
+
+async fn listen_for_devices(devices: ConnectedDevices) {
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    {
+        if let Some(mac_udid) = plume_gestalt::get_udid() {
+            add_device(
+                &devices,
+                Device {
+                    name: "This Mac".into(),
+                    udid: mac_udid,
+                    device_id: u32::MAX,
+                    usbmuxd_device: None,
+                    is_mac: true,
+                },
+            );
+        }
+    }
+
+    // usbmuxd may not be up yet or may restart, keep reconnecting to it
+    loop {
+        if let Err(e) = track_devices(&devices).await {
+            log::warn!("Can't reach usbmuxd: {}", e);
+        }
+
+        if let Ok(mut devices) = devices.lock() {
+            devices.retain(|_, device| device.is_mac);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn track_devices(devices: &ConnectedDevices) -> Result<()> {
+    let mut muxer = UsbmuxdConnection::default().await?;
+
+    for dev in muxer.get_devices().await? {
+        add_device(devices, Device::new(dev).await);
+    }
+
+    let mut stream = muxer.listen().await?;
+    while let Some(event) = stream.next().await {
+        match event {
+            Ok(UsbmuxdListenEvent::Connected(dev)) => add_device(devices, Device::new(dev).await),
+            Ok(UsbmuxdListenEvent::Disconnected(id)) => remove_device(devices, id),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+fn add_device(devices: &ConnectedDevices, device: Device) {
+    log::info!("Device connected: {} ({})", device.name, device.udid);
+    if let Ok(mut devices) = devices.lock() {
+        devices.insert(device.udid.clone(), device);
+    }
+}
+
+fn remove_device(devices: &ConnectedDevices, device_id: u32) {
+    if let Ok(mut devices) = devices.lock() {
+        // The same device can be attached over USB and the network, only drop this connection
+        devices.retain(|udid, device| {
+            let keep = device.device_id != device_id;
+            if !keep {
+                log::info!("Device disconnected: {} ({})", device.name, udid);
+            }
+            keep
+        });
+    }
+}
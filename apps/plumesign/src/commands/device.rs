@@ -8,7 +8,10 @@ use idevice::{
     installation_proxy::InstallationProxyClient,
     usbmuxd::{UsbmuxdAddr, UsbmuxdConnection},
 };
-use plume_utils::{Device, Package, get_device_for_id};
+use plume_utils::{Bundle, Device, Package, get_device_for_id};
+use serde::Serialize;
+
+use crate::output::{OutputFormat, print_json};
 
 #[derive(Debug, Args)]
 #[command(arg_required_else_help = true)]
@@ -21,9 +24,12 @@ pub struct DeviceArgs {
         conflicts_with = "mac"
     )]
     pub udid: Option<String>,
-    /// Install app at specified path to device (.ipa, .app)
+    /// Install app at specified path to device (.ipa, .tipa, .xcarchive, .app or a zipped .app)
     #[arg(short = 'i', long = "install", value_name = "PATH")]
     pub install: Option<PathBuf>,
+    /// Install without checking whether the app is compatible with the device
+    #[arg(long = "force", requires = "install")]
+    pub force: bool,
     /// Install pairing record from specified path to device
     #[arg(
         short = 'p',
@@ -45,7 +51,16 @@ pub struct DeviceArgs {
     pub mac: bool,
 }
 
-pub async fn execute(args: DeviceArgs) -> Result<()> {
+#[derive(Debug, Default, Serialize)]
+struct DeviceRecord {
+    name: String,
+    udid: String,
+    installed: Option<PathBuf>,
+    compatibility_issues: Vec<String>,
+    pairing_app: Option<String>,
+}
+
+pub async fn execute(args: DeviceArgs, format: OutputFormat) -> Result<()> {
     let device = {
         #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
         {
@@ -67,10 +82,16 @@ pub async fn execute(args: DeviceArgs) -> Result<()> {
         }
     };
 
+    let mut record = DeviceRecord {
+        name: device.name.clone(),
+        udid: device.udid.clone(),
+        ..Default::default()
+    };
+
     if let Some(app_path) = args.install {
         let mut app_path = app_path;
 
-        if !app_path.is_dir() {
+        if !app_path.is_dir() || Package::is_supported_path(&app_path) {
             app_path = Package::new(app_path)?
                 .get_package_bundle()?
                 .bundle_dir()
@@ -81,7 +102,28 @@ pub async fn execute(args: DeviceArgs) -> Result<()> {
         if args.mac {
             log::info!("Installing app at {:?} to connected Mac", app_path);
             plume_utils::install_app_mac(&app_path).await?;
-            return Ok(());
+            record.installed = Some(app_path);
+            return print_record(&record, format);
+        }
+
+        // Best effort, a check that can't run doesn't hold up the install
+        if !args.force {
+            match device.check_compatibility(&Bundle::new(&app_path)?).await {
+                Ok(report) => {
+                    for issue in &report.issues {
+                        log::warn!("{}", issue);
+                    }
+                    record.compatibility_issues =
+                        report.issues.iter().map(|i| i.to_string()).collect();
+                    if report.is_blocking() {
+                        return Err(anyhow::anyhow!(
+                            "App is not compatible with device {}, pass --force to install anyway",
+                            device.name
+                        ));
+                    }
+                }
+                Err(e) => log::warn!("Skipping compatibility check: {}", e),
+            }
         }
 
         log::info!("Installing app at {:?} to device {}", app_path, device.name);
@@ -90,6 +132,7 @@ pub async fn execute(args: DeviceArgs) -> Result<()> {
                 log::info!("{}", progress);
             })
             .await?;
+        record.installed = Some(app_path);
     }
 
     if args.pairing {
@@ -107,9 +150,17 @@ pub async fn execute(args: DeviceArgs) -> Result<()> {
             device
                 .install_pairing_record(&app_identifier, pairing_path.to_str().unwrap())
                 .await?;
+            record.pairing_app = Some(app_identifier);
         }
     }
 
+    print_record(&record, format)
+}
+
+fn print_record(record: &DeviceRecord, format: OutputFormat) -> Result<()> {
+    if format.is_json() {
+        return print_json(record);
+    }
     Ok(())
 }
 
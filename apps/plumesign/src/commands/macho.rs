@@ -1,15 +1,24 @@
 use anyhow::Result;
 use clap::Args;
-use plume_core::{MachO, MachOExt};
+use plume_core::{MachO, MachOExt, MachOSliceInfo};
+use serde::Serialize;
 use std::path::PathBuf;
 
+use crate::output::{OutputFormat, print_json};
+
 #[derive(Debug, Args)]
 #[command(arg_required_else_help = true)]
 pub struct MachArgs {
     #[arg(value_name = "BINARY")]
     pub binary: PathBuf,
+    /// Only inspect or edit one slice of a fat binary, by index or architecture (e.g., arm64)
+    #[arg(long, value_name = "SLICE")]
+    pub slice: Option<String>,
     #[arg(long)]
     pub entitlements: bool,
+    /// Report architecture, versions, rpaths, encryption and signature of every slice
+    #[arg(long)]
+    pub inspect: bool,
     /// List all dylib dependencies
     #[arg(long)]
     pub list_dylibs: bool,
@@ -19,14 +28,42 @@ pub struct MachArgs {
     /// Replace an existing dylib dependency
     #[arg(long, value_names = &["OLD", "NEW"], num_args = 2)]
     pub replace_dylib: Option<Vec<String>>,
+    /// Remove a dylib dependency
+    #[arg(long, value_name = "DYLIB_PATH")]
+    pub remove_dylib: Option<String>,
+    /// List all rpaths
+    #[arg(long)]
+    pub list_rpaths: bool,
+    /// Add an rpath (e.g., @executable_path/Frameworks)
+    #[arg(long, value_name = "RPATH")]
+    pub add_rpath: Option<String>,
+    /// Remove an rpath
+    #[arg(long, value_name = "RPATH")]
+    pub remove_rpath: Option<String>,
     /// Set the SDK version (e.g., 26.0.0)
     #[arg(long, value_name = "SDK_VERSION")]
     pub sdk_version: Option<String>,
 }
 
-pub async fn execute(args: MachArgs) -> Result<()> {
+#[derive(Debug, Serialize)]
+struct DylibsRecord<'a> {
+    binary: &'a PathBuf,
+    dylibs: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpathsRecord<'a> {
+    binary: &'a PathBuf,
+    rpaths: Vec<String>,
+}
+
+pub async fn execute(args: MachArgs, format: OutputFormat) -> Result<()> {
     let mut macho = MachO::new(&args.binary)?;
 
+    if let Some(slice) = &args.slice {
+        macho.select_slice(slice)?;
+    }
+
     if let Some(dylib_path) = &args.add_dylib {
         macho.add_dylib(dylib_path)?;
         return Ok(());
@@ -39,30 +76,76 @@ pub async fn execute(args: MachArgs) -> Result<()> {
         }
     }
 
+    if let Some(dylib_path) = &args.remove_dylib {
+        macho.remove_dylib(dylib_path)?;
+        return Ok(());
+    }
+
+    if let Some(rpath) = &args.add_rpath {
+        macho.add_rpath(rpath)?;
+        return Ok(());
+    }
+
+    if let Some(rpath) = &args.remove_rpath {
+        macho.remove_rpath(rpath)?;
+        return Ok(());
+    }
+
+    if args.inspect {
+        let mut slices = macho.inspect()?;
+        if let Some(selected) = macho.selected_slice() {
+            slices.retain(|slice| slice.index == selected);
+        }
+        if format.is_json() {
+            return print_json(&slices);
+        }
+        for slice in slices {
+            print_slice(&slice)?;
+        }
+        return Ok(());
+    }
+
     if args.list_dylibs {
-        // TODO: add index argument
-        let d = macho
-            .macho_file()
-            .nth_macho(0)
-            .unwrap()
-            .dylib_load_paths()
-            .unwrap();
+        let d = macho.slice()?.dylib_load_paths()?;
+        if format.is_json() {
+            return print_json(&DylibsRecord {
+                binary: &args.binary,
+                dylibs: d,
+            });
+        }
         for path in d {
             println!("{path}");
         }
         return Ok(());
     }
 
+    if args.list_rpaths {
+        let rpaths = macho.slice()?.rpaths();
+        if format.is_json() {
+            return print_json(&RpathsRecord {
+                binary: &args.binary,
+                rpaths,
+            });
+        }
+        for rpath in rpaths {
+            println!("{rpath}");
+        }
+        return Ok(());
+    }
+
     if let Some(sdk_version) = &args.sdk_version {
         macho.replace_sdk_version(sdk_version)?;
         return Ok(());
     }
 
-    let entitlements = macho.entitlements();
     if args.entitlements {
+        let entitlements = macho.slice()?.embedded_entitlements()?;
+        if format.is_json() {
+            return print_json(&entitlements);
+        }
         if let Some(ent) = entitlements {
             let mut buf = Vec::new();
-            plist::Value::Dictionary(ent.clone()).to_writer_xml(&mut buf)?;
+            plist::Value::Dictionary(ent).to_writer_xml(&mut buf)?;
             let xml_str = String::from_utf8(buf)?;
             println!("{}", xml_str);
         }
@@ -70,3 +153,53 @@ pub async fn execute(args: MachArgs) -> Result<()> {
 
     Ok(())
 }
+
+fn print_slice(slice: &MachOSliceInfo) -> Result<()> {
+    println!("[{}] {}", slice.index, slice.arch);
+
+    if let Some(version) = &slice.build_version {
+        println!("  Platform: {}", version.platform);
+        println!("  Minimum OS: {}", version.min_os);
+        println!("  SDK: {}", version.sdk);
+    }
+
+    match &slice.encryption {
+        Some(info) if info.cryptid != 0 => println!("  Encrypted: yes (cryptid {})", info.cryptid),
+        Some(_) => println!("  Encrypted: no (cryptid 0)"),
+        None => println!("  Encrypted: no"),
+    }
+
+    match &slice.code_signature {
+        Some(signature) => {
+            println!("  Signed: yes");
+            println!("  Identifier: {}", signature.identifier);
+            println!(
+                "  Team ID: {}",
+                signature.team_id.as_deref().unwrap_or("none")
+            );
+            for cdhash in &signature.cdhashes {
+                println!("  CDHash: {cdhash}");
+            }
+        }
+        None => println!("  Signed: no"),
+    }
+
+    println!("  Rpaths:");
+    for rpath in &slice.rpaths {
+        println!("    {rpath}");
+    }
+
+    println!("  Dylibs:");
+    for dylib in &slice.dylibs {
+        println!("    {dylib}");
+    }
+
+    if let Some(ent) = &slice.entitlements {
+        let mut buf = Vec::new();
+        plist::Value::Dictionary(ent.clone()).to_writer_xml(&mut buf)?;
+        println!("  Entitlements:");
+        println!("{}", String::from_utf8(buf)?);
+    }
+
+    Ok(())
+}
@@ -1,9 +1,15 @@
 use clap::{Parser, Subcommand};
 
+use crate::output::OutputFormat;
+
 pub mod account;
+pub mod batch;
+pub mod daemon;
 pub mod device;
 pub mod macho;
+pub mod profile;
 pub mod sign;
+pub mod tweaks;
 
 #[derive(Debug, Parser)]
 #[command(
@@ -15,6 +21,9 @@ pub mod sign;
     arg_required_else_help = true
 )]
 pub struct Cli {
+    /// Output format for command results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -23,10 +32,18 @@ pub struct Cli {
 pub enum Commands {
     /// Sign an iOS app bundle with certificate and provisioning profile
     Sign(sign::SignArgs),
+    /// Sign multiple packages with a single account session
+    Batch(batch::BatchArgs),
+    /// List or remove tweaks injected into an app
+    Tweaks(tweaks::TweaksArgs),
     /// Inspect Mach-O binaries
     MachO(macho::MachArgs),
     /// Manage Apple Developer account authentication
     Account(account::AccountArgs),
     /// Device management commands
     Device(device::DeviceArgs),
+    /// Move accounts, keys and saved apps between machines
+    Profile(profile::ProfileArgs),
+    /// Keep saved apps refreshed on connected devices, for servers and systemd
+    Daemon(daemon::DaemonArgs),
 }
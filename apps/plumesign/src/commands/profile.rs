@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use dialoguer::Password;
+use serde::Serialize;
+
+use plume_utils::ProfileSummary;
+
+use crate::{
+    get_data_path,
+    output::{OutputFormat, print_json},
+};
+
+#[derive(Debug, Args)]
+#[command(arg_required_else_help = true)]
+pub struct ProfileArgs {
+    #[command(subcommand)]
+    pub command: ProfileCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfileCommands {
+    /// Save accounts, signing keys and apps saved for refreshing to a password protected file
+    Export(ProfileExportArgs),
+    /// Merge a profile from another machine into this one
+    Import(ProfileImportArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ProfileExportArgs {
+    /// Where to write the profile
+    #[arg(value_name = "OUTPUT")]
+    pub output: PathBuf,
+    /// Password (will prompt if not provided)
+    #[arg(short = 'p', long = "password", value_name = "PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ProfileImportArgs {
+    /// Profile written by `profile export`
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+    /// Password (will prompt if not provided)
+    #[arg(short = 'p', long = "password", value_name = "PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProfileRecord<'a> {
+    path: &'a PathBuf,
+    #[serde(flatten)]
+    summary: ProfileSummary,
+}
+
+pub async fn execute(args: ProfileArgs, format: OutputFormat) -> Result<()> {
+    match args.command {
+        ProfileCommands::Export(args) => export(args, format).await,
+        ProfileCommands::Import(args) => import(args, format).await,
+    }
+}
+
+async fn export(args: ProfileExportArgs, format: OutputFormat) -> Result<()> {
+    let password = match args.password {
+        Some(password) => password,
+        None => Password::new()
+            .with_prompt("Profile password")
+            .with_confirmation("Repeat password", "Passwords don't match")
+            .interact()?,
+    };
+    if password.is_empty() {
+        return Err(anyhow::anyhow!("The profile password can't be empty"));
+    }
+
+    let summary = plume_utils::export_profile(&get_data_path(), &password, &args.output)?;
+
+    if format.is_json() {
+        return print_json(&ProfileRecord {
+            path: &args.output,
+            summary,
+        });
+    }
+
+    log::info!(
+        "Exported {} account(s), {} key(s) and {} app(s) on {} device(s) to {}",
+        summary.accounts,
+        summary.keys,
+        summary.apps,
+        summary.devices,
+        args.output.display()
+    );
+    log::warn!("Anyone with the file and its password can sign in as these accounts.");
+
+    Ok(())
+}
+
+async fn import(args: ProfileImportArgs, format: OutputFormat) -> Result<()> {
+    let password = match args.password {
+        Some(password) => password,
+        None => Password::new().with_prompt("Profile password").interact()?,
+    };
+
+    let summary = plume_utils::import_profile(&get_data_path(), &password, &args.input)?;
+
+    if format.is_json() {
+        return print_json(&ProfileRecord {
+            path: &args.input,
+            summary,
+        });
+    }
+
+    log::info!(
+        "Imported {} account(s), {} key(s) and {} app(s) on {} device(s) from {}",
+        summary.accounts,
+        summary.keys,
+        summary.apps,
+        summary.devices,
+        args.input.display()
+    );
+
+    Ok(())
+}
@@ -2,24 +2,37 @@ use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::Args;
+use serde::Serialize;
 
 use plume_core::{CertificateIdentity, MobileProvision};
-use plume_utils::{Bundle, Package, Signer, SignerMode, SignerOptions};
+use plume_utils::{
+    Bundle, Package, Signer, SignerCompression, SignerInstallMode, SignerManifest, SignerMode,
+    TweakPlans,
+};
 
 use crate::{
     commands::{
-        account::{get_authenticated_account, teams},
+        account::{get_authenticated_account_for, record_created_app_ids, teams},
         device::select_device,
     },
     get_data_path,
+    output::{OutputFormat, print_json},
 };
 
 #[derive(Debug, Args)]
 #[command(arg_required_else_help = true)]
 pub struct SignArgs {
-    /// Path to the app bundle or package to sign (.app or .ipa)
-    #[arg(long, short, value_name = "PACKAGE")]
-    pub package: PathBuf,
+    /// Path to the app bundle or package to sign (.app, .ipa, .tipa, .xcarchive or a zipped .app)
+    #[arg(
+        long,
+        short,
+        value_name = "PACKAGE",
+        required_unless_present = "manifest"
+    )]
+    pub package: Option<PathBuf>,
+    /// Signing manifest (.toml or .json) describing the full signer options
+    #[arg(long, value_name = "MANIFEST")]
+    pub manifest: Option<PathBuf>,
     /// PEM files for certificate and private key
     #[arg(long = "pem", value_name = "PEM", num_args = 1..)]
     pub pem_files: Option<Vec<PathBuf>>,
@@ -41,6 +54,18 @@ pub struct SignArgs {
     /// Perform ad-hoc signing (no certificate required)
     #[arg(long, short, num_args = 1..)]
     pub tweaks: Option<Vec<PathBuf>>,
+    /// Directory of .deb files used to resolve tweak dependencies
+    #[arg(long = "tweak-repo", value_name = "DIR")]
+    pub tweak_repository: Option<PathBuf>,
+    /// Remove every app extension (PlugIns and Extensions), each one costs an App ID
+    #[arg(long)]
+    pub remove_extensions: bool,
+    /// Remove the Watch app and its placeholder
+    #[arg(long)]
+    pub remove_watch_app: bool,
+    /// Remove a nested bundle, relative to the app (i.e. PlugIns/Widgets.appex)
+    #[arg(long = "remove-bundle", value_name = "PATH")]
+    pub remove_bundles: Vec<PathBuf>,
     /// Register device and install after signing
     #[arg(long)]
     pub register_and_install: bool,
@@ -50,37 +75,87 @@ pub struct SignArgs {
     /// Output path for signed .ipa (only for .ipa input)
     #[arg(long, short, value_name = "OUTPUT")]
     pub output: Option<PathBuf>,
+    /// Compression level of the signed .ipa, from 0 (store) to 9
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u8).range(0..=9))]
+    pub compression_level: Option<u8>,
     /// Install to connected Mac (arm64 only)
     #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
     #[arg(short = 'm', long = "mac", value_name = "MAC", conflicts_with = "udid")]
     pub mac: bool,
+    /// Show what the tweaks would install, without signing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SignRecord<'a> {
+    package: &'a PathBuf,
+    output: Option<&'a PathBuf>,
+    installed_to: Option<String>,
 }
 
-pub async fn execute(args: SignArgs) -> Result<()> {
-    if !args.package.is_dir() && !args.apple_id && args.output.is_none() {
+pub async fn execute(args: SignArgs, format: OutputFormat) -> Result<()> {
+    let manifest = match &args.manifest {
+        Some(manifest_path) => SignerManifest::load(manifest_path)?,
+        None => SignerManifest::default(),
+    };
+
+    let package_path = args.package.or(manifest.package).ok_or_else(|| {
+        anyhow::anyhow!("No package specified, use -p/--package or set `package` in the manifest.")
+    })?;
+    let output = args.output.or(manifest.output);
+    let register_and_install = args.register_and_install
+        || (manifest.udid.is_some() && manifest.options.install_mode == SignerInstallMode::Install);
+    let udid = args.udid.or(manifest.udid);
+    // Manifests use the GUI semantics, where `pem` means signing with the saved Apple ID.
+    let apple_id = args.apple_id
+        || (args.manifest.is_some()
+            && args.pem_files.is_none()
+            && manifest.options.mode == SignerMode::Pem);
+
+    let mut options = manifest.options;
+    options.custom_identifier = args.bundle_identifier.or(options.custom_identifier);
+    options.custom_name = args.name.or(options.custom_name);
+    options.custom_version = args.version.or(options.custom_version);
+    if let Some(tweaks) = args.tweaks {
+        options.tweaks.get_or_insert_with(Vec::new).extend(tweaks);
+    }
+    options.tweak_repository = args.tweak_repository.or(options.tweak_repository);
+    options.removals.extensions |= args.remove_extensions;
+    options.removals.watch_app |= args.remove_watch_app;
+    options.removals.bundles.extend(args.remove_bundles);
+    if let Some(level) = args.compression_level {
+        options.compression = SignerCompression::from_level(level);
+    }
+    let compression = options.compression;
+
+    if args.dry_run {
+        let plans = Signer::new(None, options).plan_tweaks().await?;
+        if format.is_json() {
+            return print_json(&plans);
+        }
+        print_tweak_plans(&plans);
+        return Ok(());
+    }
+
+    let is_bundle = package_path.is_dir() && !Package::is_supported_path(&package_path);
+
+    if !is_bundle && !apple_id && output.is_none() {
         return Err(anyhow::anyhow!(
             "-o/--output is required when signing an .ipa without --apple-id (ad-hoc mode)."
         ));
     }
 
-    let mut options = SignerOptions {
-        custom_identifier: args.bundle_identifier,
-        custom_name: args.name,
-        custom_version: args.version,
-        tweaks: args.tweaks,
-        ..Default::default()
-    };
-
-    let (bundle, package) = if args.package.is_dir() {
-        log::warn!("⚠️  Signing bundle in place: {}", args.package.display());
-        if args.output.is_some() {
+    let (bundle, package) = if is_bundle {
+        log::warn!("⚠️  Signing bundle in place: {}", package_path.display());
+        if output.is_some() {
             log::warn!(
                 "Note: -o/--output flag is ignored for .app bundles (in-place signing only)"
             );
         }
-        (Bundle::new(&args.package)?, None)
+        (Bundle::new(&package_path)?, None)
     } else {
-        let pkg = Package::new(args.package.clone())?;
+        let pkg = Package::new(package_path.clone())?;
         let bundle = pkg.get_package_bundle()?;
         (bundle, Some(pkg))
     };
@@ -90,9 +165,12 @@ pub async fn execute(args: SignArgs) -> Result<()> {
 
         options.mode = SignerMode::Pem;
         (Signer::new(Some(cert_identity), options), None)
-    } else if args.apple_id {
-        let session = get_authenticated_account().await?;
-        let team_id = teams(&session).await?;
+    } else if apple_id {
+        let session = get_authenticated_account_for(manifest.account.as_deref()).await?;
+        let team_id = match manifest.team_id {
+            Some(team_id) => team_id,
+            None => teams(&session).await?,
+        };
         let cert_identity = CertificateIdentity::new_with_session(
             &session,
             get_data_path(),
@@ -109,7 +187,9 @@ pub async fn execute(args: SignArgs) -> Result<()> {
             Some((session, team_id)),
         )
     } else {
-        options.mode = SignerMode::Adhoc;
+        if options.mode != SignerMode::None {
+            options.mode = SignerMode::Adhoc;
+        }
         (Signer::new(None, options), None)
     };
 
@@ -118,7 +198,7 @@ pub async fn execute(args: SignArgs) -> Result<()> {
         signer.provisioning_files.push(prov.clone());
     }
 
-    let device = if args.register_and_install {
+    let device = if register_and_install {
         #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
         {
             if args.mac {
@@ -132,17 +212,19 @@ pub async fn execute(args: SignArgs) -> Result<()> {
                     is_mac: true,
                 })
             } else {
-                Some(select_device(args.udid).await?)
+                Some(select_device(udid).await?)
             }
         }
         #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
         {
-            Some(select_device(args.udid).await?)
+            Some(select_device(udid).await?)
         }
     } else {
         None
     };
 
+    let installed_to = device.as_ref().map(|dev| dev.udid.clone());
+
     if let Some((session, team_id)) = team_id_opt {
         signer
             .modify_bundle(&bundle, &Some(team_id.clone()))
@@ -155,9 +237,12 @@ pub async fn execute(args: SignArgs) -> Result<()> {
                 .await?;
         }
 
-        signer
+        log::info!("Registering {} App ID(s)", signer.app_id_count(&bundle)?);
+        let registered = signer
             .register_bundle(&bundle, &session, &team_id, false)
-            .await?;
+            .await;
+        record_created_app_ids(&team_id, &signer.created_app_ids)?;
+        registered?;
         signer.sign_bundle(&bundle).await?;
 
         if let Some(dev) = device {
@@ -198,9 +283,9 @@ pub async fn execute(args: SignArgs) -> Result<()> {
     }
 
     if let Some(pkg) = package {
-        if let Some(output_path) = args.output {
-            let archived_path = pkg.get_archive_based_on_path(&args.package.clone())?;
-            tokio::fs::copy(&archived_path, &output_path).await?;
+        if let Some(output_path) = &output {
+            let archived_path = pkg.get_archive_based_on_path(bundle.bundle_dir(), compression)?;
+            tokio::fs::copy(&archived_path, output_path).await?;
             log::info!("Saved signed package to: {}", output_path.display());
             if std::env::var("PLUME_DELETE_AFTER_FINISHED").is_err() {
                 pkg.remove_package_stage();
@@ -213,5 +298,67 @@ pub async fn execute(args: SignArgs) -> Result<()> {
         }
     }
 
+    if format.is_json() {
+        print_json(&SignRecord {
+            package: &package_path,
+            output: output.as_ref().filter(|_| !is_bundle),
+            installed_to,
+        })?;
+    }
+
     Ok(())
 }
+
+fn print_tweak_plans(plans: &TweakPlans) {
+    if plans.plans.is_empty() {
+        println!("No tweaks to install.");
+        return;
+    }
+
+    for plan in &plans.plans {
+        println!("{}", plan.tweak.display());
+        if let Some(control) = &plan.control {
+            let depends = control
+                .depends
+                .iter()
+                .map(|clause| clause.join(" | "))
+                .collect::<Vec<_>>();
+            println!(
+                "  {} {}",
+                control.package,
+                control.version.as_deref().unwrap_or_default()
+            );
+            if !depends.is_empty() {
+                println!("  depends: {}", depends.join(", "));
+            }
+        }
+        for item in &plan.items {
+            println!(
+                "  {:?}: {} -> {}",
+                item.kind,
+                item.source.display(),
+                item.destination.display()
+            );
+            if let Some(load_command) = &item.load_command {
+                println!("    load command: {}", load_command);
+            }
+            if item.rewrites_substrate {
+                println!("    CydiaSubstrate is rewritten to ElleKit");
+            }
+            for rewrite in &item.rewrites {
+                println!("    rewrites: {} -> {}", rewrite.from, rewrite.to);
+            }
+            if let Some(filter) = &item.filter {
+                let targets = filter.bundles.iter().chain(&filter.executables);
+                println!(
+                    "    filter: {}",
+                    targets.cloned().collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+    }
+
+    if !plans.unresolved.is_empty() {
+        println!("Unresolved dependencies: {}", plans.unresolved.join(", "));
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use serde::Serialize;
+
+use plume_utils::{Bundle, InjectedDylib, Package, SignerCompression, Tweak};
+
+use crate::output::{OutputFormat, print_json};
+
+#[derive(Debug, Args)]
+#[command(arg_required_else_help = true)]
+pub struct TweaksArgs {
+    #[command(subcommand)]
+    pub command: TweaksCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TweaksCommands {
+    /// List dylibs and frameworks injected into the main executable
+    List(TweaksListArgs),
+    /// Remove injected dylibs and frameworks, the app must be signed again afterwards
+    Remove(TweaksRemoveArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct TweaksListArgs {
+    /// Path to the app bundle or package (.app, .ipa, .tipa, .xcarchive or a zipped .app)
+    #[arg(value_name = "PACKAGE")]
+    pub package: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct TweaksRemoveArgs {
+    /// Path to the app bundle or package (.app, .ipa, .tipa, .xcarchive or a zipped .app)
+    #[arg(value_name = "PACKAGE")]
+    pub package: PathBuf,
+    /// Load command paths to remove (e.g., @rpath/Tweak.dylib)
+    #[arg(value_name = "LOAD_PATH", required_unless_present = "all")]
+    pub load_paths: Vec<String>,
+    /// Remove every injected dylib or framework that links CydiaSubstrate
+    #[arg(long, conflicts_with = "load_paths")]
+    pub all: bool,
+    /// Output path for the modified .ipa (only for .ipa input)
+    #[arg(long, short, value_name = "OUTPUT")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct TweaksRecord<'a> {
+    package: &'a PathBuf,
+    injected: &'a [InjectedDylib],
+}
+
+pub async fn execute(args: TweaksArgs, format: OutputFormat) -> Result<()> {
+    match args.command {
+        TweaksCommands::List(args) => list(args, format).await,
+        TweaksCommands::Remove(args) => remove(args, format).await,
+    }
+}
+
+async fn list(args: TweaksListArgs, format: OutputFormat) -> Result<()> {
+    let (bundle, package) = open_package(&args.package)?;
+    let injected = Tweak::list_injected(&bundle);
+
+    if let Some(pkg) = package {
+        pkg.remove_package_stage();
+    }
+    let injected = injected?;
+
+    if format.is_json() {
+        return print_json(&TweaksRecord {
+            package: &args.package,
+            injected: &injected,
+        });
+    }
+
+    if injected.is_empty() {
+        log::info!("No injected dylibs found.");
+        return Ok(());
+    }
+
+    for item in &injected {
+        if item.uses_substrate {
+            println!("{} (tweak)", item.load_path);
+        } else {
+            println!("{}", item.load_path);
+        }
+        // The main executable sits at the root of the app
+        for executable in item
+            .executables
+            .iter()
+            .filter(|e| e.parent() != Some(Path::new("")))
+        {
+            println!("  in {}", executable.display());
+        }
+    }
+
+    Ok(())
+}
+
+async fn remove(args: TweaksRemoveArgs, format: OutputFormat) -> Result<()> {
+    if !is_bundle(&args.package) && args.output.is_none() {
+        return Err(anyhow::anyhow!(
+            "-o/--output is required when removing tweaks from an .ipa."
+        ));
+    }
+
+    let (bundle, package) = open_package(&args.package)?;
+
+    let result = async {
+        let load_paths = if args.all {
+            Tweak::list_injected(&bundle)?
+                .into_iter()
+                .filter(|i| i.uses_substrate)
+                .map(|i| i.load_path)
+                .collect()
+        } else {
+            args.load_paths.clone()
+        };
+
+        let removed = Tweak::uninstall(&bundle, &load_paths).await?;
+
+        if let (Some(pkg), Some(output_path)) = (&package, &args.output) {
+            let archived_path =
+                pkg.get_archive_based_on_path(bundle.bundle_dir(), SignerCompression::default())?;
+            tokio::fs::copy(&archived_path, output_path).await?;
+            log::info!("Saved package to: {}", output_path.display());
+        }
+
+        Ok::<_, anyhow::Error>(removed)
+    }
+    .await;
+
+    if let Some(pkg) = package {
+        pkg.remove_package_stage();
+    }
+    let removed = result?;
+
+    if format.is_json() {
+        return print_json(&TweaksRecord {
+            package: &args.package,
+            injected: &removed,
+        });
+    }
+
+    if removed.is_empty() {
+        log::info!("Nothing to remove.");
+    }
+    for item in &removed {
+        log::info!("Removed {}", item.load_path);
+    }
+
+    Ok(())
+}
+
+fn is_bundle(path: &Path) -> bool {
+    path.is_dir() && !Package::is_supported_path(path)
+}
+
+fn open_package(path: &Path) -> Result<(Bundle, Option<Package>)> {
+    if is_bundle(path) {
+        return Ok((Bundle::new(path)?, None));
+    }
+
+    let pkg = Package::new(path.to_path_buf())?;
+    let bundle = pkg.get_package_bundle()?;
+    Ok((bundle, Some(pkg)))
+}
@@ -1,4 +1,5 @@
 mod commands;
+mod output;
 
 use std::{
     env, fs,
@@ -9,19 +10,33 @@ use clap::Parser;
 use commands::{Cli, Commands};
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
-    let _ = rustls::crypto::ring::default_provider().install_default();
+async fn main() {
     let cli = Cli::parse();
-
-    match cli.command {
-        Commands::Sign(args) => commands::sign::execute(args).await?,
-        Commands::MachO(args) => commands::macho::execute(args).await?,
-        Commands::Account(args) => commands::account::execute(args).await?,
-        Commands::Device(args) => commands::device::execute(args).await?,
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug"));
+    if matches!(cli.command, Commands::Daemon(_)) {
+        // journald reads a service's stdout
+        logger.target(env_logger::Target::Stdout);
     }
+    logger.init();
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let format = cli.format;
+
+    let result = match cli.command {
+        Commands::Sign(args) => commands::sign::execute(args, format).await,
+        Commands::Batch(args) => commands::batch::execute(args, format).await,
+        Commands::Tweaks(args) => commands::tweaks::execute(args, format).await,
+        Commands::MachO(args) => commands::macho::execute(args, format).await,
+        Commands::Account(args) => commands::account::execute(args, format).await,
+        Commands::Device(args) => commands::device::execute(args, format).await,
+        Commands::Profile(args) => commands::profile::execute(args, format).await,
+        Commands::Daemon(args) => commands::daemon::execute(args).await,
+    };
 
-    Ok(())
+    if let Err(e) = result {
+        output::report_error(&e, format);
+        std::process::exit(output::exit_code(&e));
+    }
 }
 
 pub fn get_data_path() -> PathBuf {
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How command results are written to stdout, logs always go to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// Machine readable JSON
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Context for an error whose details a command has already written as JSON,
+/// [`report_error`] then only picks the exit code for it.
+#[derive(Debug)]
+pub struct Reported;
+
+impl fmt::Display for Reported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("details were written to stdout")
+    }
+}
+
+// Exit codes are part of the scripting interface, do not renumber them.
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_AUTH: i32 = 3;
+pub const EXIT_DEVELOPER_API: i32 = 4;
+pub const EXIT_NETWORK: i32 = 5;
+pub const EXIT_CERTIFICATE: i32 = 6;
+pub const EXIT_CODESIGN: i32 = 7;
+pub const EXIT_IO: i32 = 8;
+pub const EXIT_PARSE: i32 = 9;
+pub const EXIT_ENCRYPTED: i32 = 10;
+
+/// Maps an error chain to a stable exit code, mostly by its `plume_core::Error` variant.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    use plume_core::Error;
+
+    if err.chain().any(|e| {
+        matches!(
+            e.downcast_ref::<plume_utils::Error>(),
+            Some(plume_utils::Error::EncryptedBinary { .. })
+        )
+    }) {
+        return EXIT_ENCRYPTED;
+    }
+
+    let Some(core) = err.chain().find_map(|e| e.downcast_ref::<Error>()) else {
+        if err.chain().any(|e| e.is::<std::io::Error>()) {
+            return EXIT_IO;
+        }
+        return EXIT_FAILURE;
+    };
+
+    match core {
+        Error::AuthSrpWithMessage(..)
+        | Error::ExtraStep(_)
+        | Error::Bad2faCode
+        | Error::Anisette(_)
+        | Error::SessionExpired { .. } => EXIT_AUTH,
+        Error::DeveloperApi { .. }
+        | Error::DeviceLimit { .. }
+        | Error::AppIdLimit { .. }
+        | Error::MaximumCertificates { .. }
+        | Error::TeamNotFound { .. }
+        | Error::AgreementNotAccepted { .. }
+        | Error::CapabilityNotAllowed { .. }
+        | Error::DeveloperSessionRequestFailed => EXIT_DEVELOPER_API,
+        Error::Reqwest(_) => EXIT_NETWORK,
+        Error::Certificate(_)
+        | Error::CertificatePemMissing
+        | Error::Pem(_)
+        | Error::X509(_)
+        | Error::Rsa(_)
+        | Error::PKCS1(_)
+        | Error::PKCS8(_)
+        | Error::RcGen(_) => EXIT_CERTIFICATE,
+        Error::BundleExecutableMissing
+        | Error::ProvisioningEntitlementsUnknown
+        | Error::Codesign(_)
+        | Error::CodeSignBuilder(_) => EXIT_CODESIGN,
+        Error::Io(_) => EXIT_IO,
+        Error::Parse | Error::Plist(_) | Error::SerdeJson(_) => EXIT_PARSE,
+        _ => EXIT_FAILURE,
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorReport<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: i32,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result_code: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+}
+
+/// Reports a failed command, as a JSON object on stdout when requested.
+pub fn report_error(err: &anyhow::Error, format: OutputFormat) {
+    if !format.is_json() {
+        log::error!("{:#}", err);
+        return;
+    }
+    if err.downcast_ref::<Reported>().is_some() {
+        return;
+    }
+
+    let api = err
+        .chain()
+        .find_map(|e| e.downcast_ref::<plume_core::Error>());
+    let url = match api {
+        Some(plume_core::Error::DeveloperApi { url, .. }) => Some(url.as_str()),
+        _ => None,
+    };
+
+    let report = ErrorReport {
+        error: ErrorBody {
+            code: exit_code(err),
+            message: format!("{:#}", err),
+            result_code: api.and_then(plume_core::Error::developer_api_code),
+            url,
+        },
+    };
+
+    if let Err(e) = print_json(&report) {
+        log::error!("{:#}", e);
+    }
+}
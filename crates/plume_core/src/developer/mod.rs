@@ -4,6 +4,8 @@ pub mod v1;
 
 pub use session::{DeveloperSession, RequestType};
 
+use crate::Error;
+
 #[macro_export]
 macro_rules! developer_endpoint {
     ($endpoint:expr) => {
@@ -15,3 +17,250 @@ macro_rules! developer_endpoint {
 pub fn strip_invalid_chars(s: &str) -> String {
     s.chars().filter(|c| c.is_ascii_alphabetic()).collect()
 }
+
+// Result codes seen from Xcode's endpoints
+const SESSION_EXPIRED: i64 = 1100;
+const MAXIMUM_CERTIFICATES: i64 = 7460;
+const APP_ID_LIMIT: i64 = 9401;
+// Codes of the v1 endpoints
+const NOT_AUTHORIZED: &str = "NOT_AUTHORIZED";
+const AGREEMENTS_MISSING: &str = "FORBIDDEN.REQUIRED_AGREEMENTS_MISSING_OR_EXPIRED";
+
+/// Failures that get a dedicated error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Failure {
+    SessionExpired,
+    MaximumCertificates,
+    AgreementNotAccepted,
+    AppIdLimit,
+    DeviceLimit,
+    TeamNotFound,
+    CapabilityNotAllowed,
+}
+
+/// Maps a failed developer services response to a dedicated error when we recognize it.
+///
+/// Known result codes decide first. Anything else falls back to the message, see
+/// [`failure_from_message`].
+pub(crate) fn api_error(
+    url: String,
+    result_code: i64,
+    http_code: Option<u16>,
+    code: Option<&str>,
+    message: String,
+) -> Error {
+    let code = code.unwrap_or_default();
+    let failure = failure_from_code(result_code, http_code, code)
+        .or_else(|| failure_from_message(&message, code));
+
+    match failure {
+        Some(Failure::SessionExpired) => Error::SessionExpired {
+            result_code,
+            message,
+        },
+        Some(Failure::MaximumCertificates) => Error::MaximumCertificates {
+            result_code,
+            message,
+        },
+        Some(Failure::AgreementNotAccepted) => Error::AgreementNotAccepted {
+            result_code,
+            message,
+        },
+        Some(Failure::AppIdLimit) => Error::AppIdLimit {
+            result_code,
+            message,
+        },
+        Some(Failure::DeviceLimit) => Error::DeviceLimit {
+            result_code,
+            message,
+        },
+        Some(Failure::TeamNotFound) => Error::TeamNotFound {
+            result_code,
+            message,
+        },
+        Some(Failure::CapabilityNotAllowed) => Error::CapabilityNotAllowed {
+            result_code,
+            message,
+        },
+        None => Error::DeveloperApi {
+            url,
+            result_code,
+            http_code,
+            message,
+        },
+    }
+}
+
+fn failure_from_code(result_code: i64, http_code: Option<u16>, code: &str) -> Option<Failure> {
+    match (result_code, http_code, code) {
+        (SESSION_EXPIRED, _, _) | (_, Some(401), _) | (_, _, NOT_AUTHORIZED) => {
+            Some(Failure::SessionExpired)
+        }
+        (MAXIMUM_CERTIFICATES, _, _) => Some(Failure::MaximumCertificates),
+        (APP_ID_LIMIT, _, _) => Some(Failure::AppIdLimit),
+        (_, _, AGREEMENTS_MISSING) => Some(Failure::AgreementNotAccepted),
+        _ => None,
+    }
+}
+
+/// Fallback for codes we have no mapping for, going by Apple's English wording.
+///
+/// Apple reuses some codes (e.g. 35) for unrelated failures and the rest aren't
+/// documented, so this is a best guess and may miss localized or reworded messages.
+fn failure_from_message(message: &str, code: &str) -> Option<Failure> {
+    let lower = message.to_lowercase();
+
+    if lower.contains("session has expired") {
+        Some(Failure::SessionExpired)
+    } else if lower.contains("current ios development certificate") {
+        Some(Failure::MaximumCertificates)
+    } else if lower.contains("agreement") || code.contains("AGREEMENT") {
+        Some(Failure::AgreementNotAccepted)
+    } else if lower.contains("app id") && (lower.contains("maximum") || lower.contains("limit")) {
+        Some(Failure::AppIdLimit)
+    } else if lower.contains("device") && (lower.contains("maximum") || lower.contains("limit")) {
+        Some(Failure::DeviceLimit)
+    } else if lower.contains("team") && (lower.contains("not found") || lower.contains("no team")) {
+        Some(Failure::TeamNotFound)
+    } else if lower.contains("capabilit") && (lower.contains("not") || code.contains("FORBIDDEN")) {
+        Some(Failure::CapabilityNotAllowed)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qh_error(result_code: i64, message: &str) -> Error {
+        api_error(
+            "https://example.com".into(),
+            result_code,
+            None,
+            None,
+            message.into(),
+        )
+    }
+
+    fn v1_error(code: &str, status: u16, message: &str) -> Error {
+        api_error(
+            "https://example.com".into(),
+            -1,
+            Some(status),
+            Some(code),
+            message.into(),
+        )
+    }
+
+    #[test]
+    fn session_expired() {
+        assert!(matches!(
+            qh_error(1100, "Your session has expired. Please log in."),
+            Error::SessionExpired {
+                result_code: 1100,
+                ..
+            }
+        ));
+        assert!(matches!(
+            v1_error(
+                NOT_AUTHORIZED,
+                401,
+                "Authentication credentials are missing or invalid."
+            ),
+            Error::SessionExpired { .. }
+        ));
+    }
+
+    #[test]
+    fn maximum_certificates() {
+        assert!(matches!(
+            qh_error(
+                7460,
+                "You already have a current iOS Development certificate."
+            ),
+            Error::MaximumCertificates {
+                result_code: 7460,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn agreement_not_accepted() {
+        assert!(matches!(
+            v1_error(
+                AGREEMENTS_MISSING,
+                403,
+                "A required agreement is missing or has expired."
+            ),
+            Error::AgreementNotAccepted { .. }
+        ));
+    }
+
+    #[test]
+    fn app_id_limit() {
+        assert!(matches!(
+            qh_error(9401, "Maximum App ID limit reached."),
+            Error::AppIdLimit {
+                result_code: 9401,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn device_limit_from_message() {
+        assert!(matches!(
+            qh_error(
+                35,
+                "You have reached the maximum number of registered devices."
+            ),
+            Error::DeviceLimit {
+                result_code: 35,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn team_not_found_from_message() {
+        assert!(matches!(
+            qh_error(1, "Team not found for this account."),
+            Error::TeamNotFound { .. }
+        ));
+    }
+
+    #[test]
+    fn capability_not_allowed_from_message() {
+        assert!(matches!(
+            v1_error(
+                "FORBIDDEN_ERROR",
+                403,
+                "This capability is not available for your team."
+            ),
+            Error::CapabilityNotAllowed { .. }
+        ));
+    }
+
+    #[test]
+    fn code_wins_over_message() {
+        // An App ID failure worded like a device one still maps by its code
+        assert!(matches!(
+            qh_error(9401, "Device and App ID limit reached."),
+            Error::AppIdLimit { .. }
+        ));
+    }
+
+    #[test]
+    fn unknown_failure_keeps_details() {
+        let error = qh_error(35, "Invalid identifier.");
+        assert!(matches!(
+            error,
+            Error::DeveloperApi {
+                result_code: 35,
+                ..
+            }
+        ));
+    }
+}
@@ -93,17 +93,18 @@ impl DeveloperSession {
         Ok(app_id)
     }
 
+    /// The flag is set when the App ID did not exist yet and was registered by this call.
     pub async fn qh_ensure_app_id(
         &self,
         team_id: &String,
         name: &String,
         identifier: &String,
-    ) -> Result<AppID, Error> {
+    ) -> Result<(AppID, bool), Error> {
         if let Some(app_id) = self.qh_get_app_id(team_id, identifier).await? {
-            Ok(app_id)
+            Ok((app_id, false))
         } else {
             let response = self.qh_add_app_id(team_id, name, identifier).await?;
-            Ok(response.app_id)
+            Ok((response.app_id, true))
         }
     }
 }
@@ -131,11 +132,11 @@ pub struct AppIDResponse {
 #[serde(rename_all = "camelCase")]
 pub struct AppID {
     pub app_id_id: String,
-    name: String,
+    pub name: String,
     app_id_platform: String,
-    prefix: String,
+    pub prefix: String,
     pub identifier: String,
-    is_wild_card: bool,
+    pub is_wild_card: bool,
     is_duplicate: bool,
     features: Features,
     enabled_features: Option<Vec<String>>,
@@ -36,11 +36,12 @@ impl QHResponseMeta {
             .or(self.result_string)
             .unwrap_or_else(|| "Unknown API error".to_string());
 
-        crate::Error::DeveloperApi {
+        crate::developer::api_error(
             url,
-            result_code: self.result_code.as_signed().unwrap_or(0),
-            http_code: self.http_code.and_then(|c| c.as_signed().map(|v| v as u16)),
+            self.result_code.as_signed().unwrap_or(0),
+            self.http_code.and_then(|c| c.as_signed().map(|v| v as u16)),
+            None,
             message,
-        }
+        )
     }
 }
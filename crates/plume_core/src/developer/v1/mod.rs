@@ -33,11 +33,12 @@ impl V1ErrorDetail {
             .or(self.title.clone())
             .unwrap_or_else(|| "Unknown API error".to_string());
 
-        crate::Error::DeveloperApi {
+        crate::developer::api_error(
             url,
-            result_code: self.result_code,
-            http_code: self.status.parse().ok(),
+            self.result_code,
+            self.status.parse().ok(),
+            Some(&self.code),
             message,
-        }
+        )
     }
 }
@@ -6,7 +6,10 @@ pub use apple_codesign::{AppleCodesignError, SettingsScope, SigningSettings, Uni
 
 pub use omnisette::AnisetteConfiguration;
 
-pub use utils::{CertificateIdentity, MachO, MachOExt, MobileProvision};
+pub use utils::{
+    BuildVersion, CertificateIdentity, CodeSignatureInfo, EncryptionInfo, MachO, MachOExt,
+    MachOSliceInfo, MobileProvision, secrets,
+};
 
 use thiserror::Error as ThisError;
 #[derive(Debug, ThisError)]
@@ -26,6 +29,24 @@ pub enum Error {
         http_code: Option<u16>,
         message: String,
     },
+    #[error("Device limit reached ({result_code}): {message}")]
+    DeviceLimit { result_code: i64, message: String },
+    #[error("App ID limit reached ({result_code}): {message}")]
+    AppIdLimit { result_code: i64, message: String },
+    #[error("Maximum number of certificates reached ({result_code}): {message}")]
+    MaximumCertificates { result_code: i64, message: String },
+    #[error("Developer session is invalid or expired ({result_code}): {message}")]
+    SessionExpired { result_code: i64, message: String },
+    #[error("Team not found ({result_code}): {message}")]
+    TeamNotFound { result_code: i64, message: String },
+    #[error("Developer agreement not accepted ({result_code}): {message}")]
+    AgreementNotAccepted { result_code: i64, message: String },
+    #[error("Capability not allowed ({result_code}): {message}")]
+    CapabilityNotAllowed { result_code: i64, message: String },
+    #[error("Secret store error: {0}")]
+    SecretStore(String),
+    #[error("Account store error: {0}")]
+    Store(String),
     #[error("Request to developer session failed")]
     DeveloperSessionRequestFailed,
     #[error("Authentication SRP error {0}: {1}")]
@@ -36,6 +57,8 @@ pub enum Error {
     Bad2faCode,
     #[error("Failed to parse")]
     Parse, // TODO: better parsing errors
+    #[error("Mach-O slice not found: {0}")]
+    MachOSliceNotFound(String),
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Plist error: {0}")]
@@ -70,6 +93,23 @@ pub enum Error {
     SHA2(#[from] sha2::digest::InvalidLength),
 }
 
+impl Error {
+    /// Result code the developer services answered with, for any of the API error variants.
+    pub fn developer_api_code(&self) -> Option<i64> {
+        match self {
+            Self::DeveloperApi { result_code, .. }
+            | Self::DeviceLimit { result_code, .. }
+            | Self::AppIdLimit { result_code, .. }
+            | Self::MaximumCertificates { result_code, .. }
+            | Self::SessionExpired { result_code, .. }
+            | Self::TeamNotFound { result_code, .. }
+            | Self::AgreementNotAccepted { result_code, .. }
+            | Self::CapabilityNotAllowed { result_code, .. } => Some(*result_code),
+            _ => None,
+        }
+    }
+}
+
 pub fn client() -> Result<reqwest::Client, Error> {
     const APPLE_ROOT: &[u8] = include_bytes!("./apple_root.der");
     let client = reqwest::ClientBuilder::new()
@@ -1,4 +1,9 @@
-use std::{fs, path::PathBuf, vec};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    vec,
+};
 
 use apple_codesign::{
     SigningSettings,
@@ -18,6 +23,7 @@ use x509_certificate::{CapturedX509Certificate, X509Certificate};
 use crate::{
     Error,
     developer::{DeveloperSession, qh::certs::Cert},
+    secrets::{self, SecretStore},
 };
 
 pub(crate) const MACHINE_NAME: &str = "AltStore";
@@ -63,6 +69,7 @@ impl CertificateIdentity {
     ) -> Result<Self, Error> {
         let machine_name = machine_name.unwrap_or_else(|| MACHINE_NAME.to_string());
 
+        let secrets = secrets::default_store(&config_path);
         let key_path = Self::key_dir(config_path, &team_id)?.join("key.pem");
 
         let mut identity = Self {
@@ -82,7 +89,7 @@ impl CertificateIdentity {
         // Only the key will be written to disk, certificate can just be gotten via the request
         // request we've made, by trying to match our public key with the requests public key
         let key_pair: [Vec<u8>; 2] = if key_path.exists() {
-            let key_string = fs::read_to_string(&key_path)?;
+            let key_string = Self::read_key(&key_path, secrets.as_ref())?;
             let priv_key = RsaPrivateKey::from_pkcs8_pem(&key_string)?;
 
             if let Some(certificate) = identity
@@ -123,7 +130,7 @@ impl CertificateIdentity {
                 .unwrap();
                 let key_pem = priv_key.to_pkcs8_pem(Default::default())?.to_string();
 
-                fs::write(&key_path, &key_pem)?;
+                Self::write_key(&key_path, secrets.as_ref(), &key_pem)?;
                 identity.new = true;
                 [cert_pem.into_bytes(), key_pem.into_bytes()]
             }
@@ -147,7 +154,7 @@ impl CertificateIdentity {
             .unwrap();
             let key_pem = priv_key.to_pkcs8_pem(Default::default())?.to_string();
 
-            fs::write(&key_path, &key_pem)?;
+            Self::write_key(&key_path, secrets.as_ref(), &key_pem)?;
             identity.new = true;
             [cert_pem.into_bytes(), key_pem.into_bytes()]
         };
@@ -173,6 +180,32 @@ impl CertificateIdentity {
         Ok(dir)
     }
 
+    // Keys written before encryption at rest are sealed the first time they are read
+    fn read_key(path: &Path, secrets: &dyn SecretStore) -> Result<String, Error> {
+        let data = fs::read(path)?;
+        let key = secrets.key()?;
+
+        if !secrets::is_sealed(&data) {
+            Self::replace_key(path, &secrets::seal(&key, &data)?)?;
+        }
+
+        String::from_utf8(secrets::open(&key, &data)?).map_err(|_| Error::Parse)
+    }
+
+    fn write_key(path: &Path, secrets: &dyn SecretStore, key_pem: &str) -> Result<(), Error> {
+        Self::replace_key(path, &secrets::seal(&secrets.key()?, key_pem.as_bytes())?)
+    }
+
+    /// Writes a key file aside and renames it over `path`, a crash can't leave it truncated.
+    pub fn replace_key(path: &Path, data: &[u8]) -> Result<(), Error> {
+        let temp = path.with_extension(format!("pem.{}.tmp", std::process::id()));
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
     fn set_machine_id(&mut self, machine_id: String) {
         self.machine_id = Some(machine_id);
     }
@@ -320,9 +353,7 @@ impl CertificateIdentity {
             {
                 Ok(id) => break id,
                 Err(e) => {
-                    // 7460 is for too many certificates (I think)
-                    if matches!(&e, Error::DeveloperApi { result_code, .. } if *result_code == 7460)
-                    {
+                    if matches!(&e, Error::MaximumCertificates { .. }) {
                         if !warned_about_reset {
                             if let Some(callback) = on_certificate_reset.as_deref_mut() {
                                 if !callback() {
@@ -1,16 +1,20 @@
 use std::fs;
+use std::io::{self, Read};
 use std::path::Path;
 
-use apple_codesign::{MachFile, MachOBinary, UniversalBinaryBuilder};
+use apple_codesign::{Blob, CodeDirectoryBlob, MachFile, MachOBinary, UniversalBinaryBuilder};
 use goblin::mach::{
     MachO as GoblinMachO,
-    cputype::CPU_TYPE_ARM64,
+    cputype::{CPU_SUBTYPE_MASK, CPU_TYPE_ARM64, get_arch_name_from_types},
     load_command::{
         CommandVariant, LC_LAZY_LOAD_DYLIB, LC_LOAD_DYLIB, LC_LOAD_UPWARD_DYLIB,
-        LC_LOAD_WEAK_DYLIB, LC_REEXPORT_DYLIB,
+        LC_LOAD_WEAK_DYLIB, LC_REEXPORT_DYLIB, LC_RPATH, PLATFORM_DRIVERKIT, PLATFORM_IOS,
+        PLATFORM_IOSSIMULATOR, PLATFORM_MACCATALYST, PLATFORM_MACOS, PLATFORM_TVOS,
+        PLATFORM_VISIONOS, PLATFORM_WATCHOS,
     },
 };
 use plist::{Dictionary, Value};
+use serde::Serialize;
 
 use crate::Error;
 
@@ -20,6 +24,7 @@ pub struct MachO {
     macho_file: MachFile<'static>,
     path: std::path::PathBuf,
     entitlements: Option<Dictionary>,
+    slice: Option<usize>,
 }
 
 impl MachO {
@@ -34,9 +39,45 @@ impl MachO {
             macho_file,
             path: path.as_ref().to_path_buf(),
             entitlements,
+            slice: None,
         })
     }
 
+    /// Restricts edits to a single slice, selected by index or architecture name (e.g. `arm64`).
+    pub fn select_slice(&mut self, selector: &str) -> Result<(), Error> {
+        let index = match selector.parse::<usize>() {
+            Ok(index) if index < self.macho_file.iter_macho().count() => index,
+            Ok(_) => return Err(Error::MachOSliceNotFound(selector.to_string())),
+            Err(_) => self
+                .macho_file
+                .iter_macho()
+                .position(|macho| macho.architecture() == selector)
+                .ok_or_else(|| Error::MachOSliceNotFound(selector.to_string()))?,
+        };
+
+        self.slice = Some(index);
+        Ok(())
+    }
+
+    /// Index of the slice from `select_slice`, if any.
+    pub fn selected_slice(&self) -> Option<usize> {
+        self.slice
+    }
+
+    /// The selected slice, or the first one when none was selected.
+    pub fn slice(&self) -> Result<&MachOBinary<'static>, Error> {
+        Ok(self.macho_file.nth_macho(self.slice.unwrap_or(0))?)
+    }
+
+    fn selected_machos_mut(&mut self) -> impl Iterator<Item = &mut MachOBinary<'static>> {
+        let slice = self.slice;
+        self.macho_file
+            .iter_macho_mut()
+            .enumerate()
+            .filter(move |(index, _)| slice.is_none_or(|s| s == *index))
+            .map(|(_, macho)| macho)
+    }
+
     pub fn macho_file(&self) -> &MachFile<'_> {
         &self.macho_file
     }
@@ -74,8 +115,7 @@ impl MachO {
     }
 
     pub fn add_dylib(&mut self, path: &str) -> Result<(), Error> {
-        let machos = self.macho_file.iter_macho_mut();
-        for macho in machos {
+        for macho in self.selected_machos_mut() {
             macho.add_dylib_load_path(path)?;
         }
         self.write_changes()?;
@@ -83,8 +123,7 @@ impl MachO {
     }
 
     pub fn replace_dylib(&mut self, old_path: &str, new_path: &str) -> Result<(), Error> {
-        let machos = self.macho_file.iter_macho_mut();
-        for macho in machos {
+        for macho in self.selected_machos_mut() {
             macho.replace_dylib_load_path(old_path, new_path)?;
         }
         self.write_changes()?;
@@ -92,17 +131,75 @@ impl MachO {
     }
 
     pub fn remove_dylib(&mut self, path: &str) -> Result<(), Error> {
-        let machos = self.macho_file.iter_macho_mut();
-        for macho in machos {
+        for macho in self.selected_machos_mut() {
             macho.remove_dylib_load_path(path)?;
         }
         self.write_changes()?;
         Ok(())
     }
 
+    /// Returns true if any slice still has a non-zero `cryptid`, i.e. is FairPlay encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        has_encrypted_slice(&self.macho_file)
+    }
+
+    /// Same as `is_encrypted` without loading the binary, only the fat header and the
+    /// header and load commands of each slice are read.
+    pub fn is_file_encrypted<P: AsRef<Path>>(path: P) -> Result<bool, Error> {
+        let mut file = io::BufReader::new(fs::File::open(path)?);
+        scan_encryption(&mut file, |file, len| {
+            file.seek_relative(len as i64)?;
+            Ok(())
+        })
+    }
+
+    /// Like `is_file_encrypted`, for a reader that can only move forward, e.g. a
+    /// compressed zip entry. Slices before the last one are read through.
+    pub fn is_reader_encrypted<R: Read>(mut reader: R) -> Result<bool, Error> {
+        scan_encryption(&mut reader, |reader, len| {
+            io::copy(&mut reader.take(len), &mut io::sink())?;
+            Ok(())
+        })
+    }
+
+    /// Reports every architecture slice in the file.
+    pub fn inspect(&self) -> Result<Vec<MachOSliceInfo>, Error> {
+        self.macho_file
+            .iter_macho()
+            .enumerate()
+            .map(|(index, macho)| {
+                Ok(MachOSliceInfo {
+                    index,
+                    arch: macho.architecture(),
+                    build_version: macho.build_version(),
+                    rpaths: macho.rpaths(),
+                    dylibs: macho.dylib_load_paths()?,
+                    encryption: macho.encryption_info(),
+                    code_signature: macho.code_signature_info()?,
+                    entitlements: macho.embedded_entitlements()?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn add_rpath(&mut self, path: &str) -> Result<(), Error> {
+        for macho in self.selected_machos_mut() {
+            macho.add_rpath(path)?;
+        }
+        self.write_changes()?;
+        Ok(())
+    }
+
+    pub fn remove_rpath(&mut self, path: &str) -> Result<(), Error> {
+        for macho in self.selected_machos_mut() {
+            macho.remove_rpath(path)?;
+        }
+        self.write_changes()?;
+        Ok(())
+    }
+
     pub fn replace_sdk_version(&mut self, new_version: &str) -> Result<(), Error> {
-        let machos = self.macho_file.iter_macho_mut();
-        for macho in machos {
+        for macho in self.selected_machos_mut() {
             macho.replace_sdk_version(new_version)?;
         }
         self.write_changes()?;
@@ -110,14 +207,58 @@ impl MachO {
     }
 }
 
+/// A single architecture slice, as reported by `MachO::inspect`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MachOSliceInfo {
+    pub index: usize,
+    pub arch: String,
+    pub build_version: Option<BuildVersion>,
+    pub rpaths: Vec<String>,
+    pub dylibs: Vec<String>,
+    pub encryption: Option<EncryptionInfo>,
+    pub code_signature: Option<CodeSignatureInfo>,
+    pub entitlements: Option<Dictionary>,
+}
+
+/// Target platform and versions from `LC_BUILD_VERSION` or `LC_VERSION_MIN_*`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildVersion {
+    pub platform: String,
+    pub min_os: String,
+    pub sdk: String,
+}
+
+/// `LC_ENCRYPTION_INFO(_64)`, a non-zero `cryptid` means the slice is still FairPlay encrypted.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EncryptionInfo {
+    pub cryptid: u32,
+    pub cryptoff: u32,
+    pub cryptsize: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeSignatureInfo {
+    pub identifier: String,
+    pub team_id: Option<String>,
+    /// Hex encoded, the primary code directory first.
+    pub cdhashes: Vec<String>,
+}
+
 #[allow(dead_code)]
 pub trait MachOExt {
     fn embedded_entitlements(&self) -> Result<Option<Dictionary>, Error>;
     fn dylib_load_paths(&self) -> Result<Vec<String>, Error>;
+    fn architecture(&self) -> String;
+    fn build_version(&self) -> Option<BuildVersion>;
+    fn rpaths(&self) -> Vec<String>;
+    fn encryption_info(&self) -> Option<EncryptionInfo>;
+    fn code_signature_info(&self) -> Result<Option<CodeSignatureInfo>, Error>;
     fn add_dylib_load_path(&mut self, path: &str) -> Result<(), Error>;
     fn remove_dylib_load_path(&mut self, path: &str) -> Result<(), Error>;
     fn replace_dylib_load_path(&mut self, old_path: &str, new_path: &str) -> Result<(), Error>;
     fn replace_sdk_version(&mut self, new_version: &str) -> Result<(), Error>;
+    fn add_rpath(&mut self, path: &str) -> Result<(), Error>;
+    fn remove_rpath(&mut self, path: &str) -> Result<(), Error>;
 }
 
 // theres multiple binaries in MachFile, being Vec<MachOBinary>
@@ -163,6 +304,88 @@ impl<'a> MachOExt for MachOBinary<'a> {
         Ok(paths)
     }
 
+    fn architecture(&self) -> String {
+        let header = &self.macho.header;
+        get_arch_name_from_types(header.cputype, header.cpusubtype & !CPU_SUBTYPE_MASK)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:#x}/{:#x}", header.cputype, header.cpusubtype))
+    }
+
+    fn build_version(&self) -> Option<BuildVersion> {
+        let version_min = |platform: &str, version: u32, sdk: u32| BuildVersion {
+            platform: platform.to_string(),
+            min_os: format_version(version),
+            sdk: format_version(sdk),
+        };
+
+        self.macho
+            .load_commands
+            .iter()
+            .find_map(|load_cmd| match &load_cmd.command {
+                CommandVariant::BuildVersion(cmd) => Some(BuildVersion {
+                    platform: platform_name(cmd.platform),
+                    min_os: format_version(cmd.minos),
+                    sdk: format_version(cmd.sdk),
+                }),
+                CommandVariant::VersionMinIphoneos(cmd) => {
+                    Some(version_min("ios", cmd.version, cmd.sdk))
+                }
+                CommandVariant::VersionMinMacosx(cmd) => {
+                    Some(version_min("macos", cmd.version, cmd.sdk))
+                }
+                CommandVariant::VersionMinTvos(cmd) => {
+                    Some(version_min("tvos", cmd.version, cmd.sdk))
+                }
+                CommandVariant::VersionMinWatchos(cmd) => {
+                    Some(version_min("watchos", cmd.version, cmd.sdk))
+                }
+                _ => None,
+            })
+    }
+
+    fn rpaths(&self) -> Vec<String> {
+        self.macho.rpaths.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn encryption_info(&self) -> Option<EncryptionInfo> {
+        self.macho
+            .load_commands
+            .iter()
+            .find_map(|load_cmd| match &load_cmd.command {
+                CommandVariant::EncryptionInfo64(cmd) => Some(EncryptionInfo {
+                    cryptid: cmd.cryptid,
+                    cryptoff: cmd.cryptoff,
+                    cryptsize: cmd.cryptsize,
+                }),
+                CommandVariant::EncryptionInfo32(cmd) => Some(EncryptionInfo {
+                    cryptid: cmd.cryptid,
+                    cryptoff: cmd.cryptoff,
+                    cryptsize: cmd.cryptsize,
+                }),
+                _ => None,
+            })
+    }
+
+    fn code_signature_info(&self) -> Result<Option<CodeSignatureInfo>, Error> {
+        let Some(embedded_sig) = self.code_signature()? else {
+            return Ok(None);
+        };
+        let Some(code_directory) = embedded_sig.code_directory()? else {
+            return Ok(None);
+        };
+
+        let mut cdhashes = vec![cdhash(&code_directory)?];
+        for (_, alternate) in embedded_sig.alternate_code_directories()? {
+            cdhashes.push(cdhash(&alternate)?);
+        }
+
+        Ok(Some(CodeSignatureInfo {
+            identifier: code_directory.ident.to_string(),
+            team_id: code_directory.team_name.as_ref().map(|t| t.to_string()),
+            cdhashes,
+        }))
+    }
+
     // these require rewriting the Mach-O
     fn add_dylib_load_path(&mut self, path: &str) -> Result<(), Error> {
         let macho = &self.macho;
@@ -323,21 +546,11 @@ impl<'a> MachOExt for MachOBinary<'a> {
             return Ok(());
         }
 
-        let current_sizeofcmds = read_u32_le(&self.data, 20);
-        let current_ncmds = read_u32_le(&self.data, 16);
-        let mut total_removed_size = 0;
-        for (arch_offset, cmd_offset, cmdsize) in &replacements {
-            let absolute_cmd_offset = arch_offset + cmd_offset - total_removed_size;
-            // Shift subsequent data to overwrite the removed command
-            data.copy_within(absolute_cmd_offset + cmdsize.., absolute_cmd_offset);
-            total_removed_size += cmdsize;
-        }
-        // Update header fields
-        let new_sizeofcmds = current_sizeofcmds - total_removed_size as u32;
-        let new_ncmds = current_ncmds - replacements.len() as u32;
-        data[20..24].copy_from_slice(&new_sizeofcmds.to_le_bytes());
-        data[16..20].copy_from_slice(&new_ncmds.to_le_bytes());
-        data.truncate(20 + new_sizeofcmds as usize);
+        let commands = replacements
+            .iter()
+            .map(|(arch_offset, cmd_offset, cmdsize)| (arch_offset + cmd_offset, *cmdsize))
+            .collect::<Vec<_>>();
+        remove_load_commands(&mut data, macho.is_64, &commands);
 
         self.data = Box::leak(data.into_boxed_slice());
 
@@ -468,6 +681,279 @@ impl<'a> MachOExt for MachOBinary<'a> {
 
         Ok(())
     }
+
+    fn add_rpath(&mut self, path: &str) -> Result<(), Error> {
+        if self.macho.rpaths.contains(&path) {
+            log::warn!("Rpath already exists in binary: {}", path);
+            return Ok(());
+        }
+
+        // rpath_command is cmd, cmdsize and the path offset, followed by the path (8-byte aligned)
+        let padding = (8 - ((12 + path.len() + 1) % 8)) % 8;
+        let rpath_command_size = 12 + path.len() + 1 + padding;
+
+        let mut new_command = Vec::with_capacity(rpath_command_size);
+        new_command.extend_from_slice(&LC_RPATH.to_le_bytes()); // cmd
+        new_command.extend_from_slice(&(rpath_command_size as u32).to_le_bytes()); // cmdsize
+        new_command.extend_from_slice(&12u32.to_le_bytes()); // path.offset
+        new_command.extend_from_slice(path.as_bytes());
+        new_command.resize(rpath_command_size, 0); // null terminator and padding
+
+        let mut data = self.data.to_vec();
+        append_load_command(&mut data, &self.macho, &new_command)?;
+
+        self.data = Box::leak(data.into_boxed_slice());
+
+        Ok(())
+    }
+
+    fn remove_rpath(&mut self, path: &str) -> Result<(), Error> {
+        let commands = self
+            .macho
+            .load_commands
+            .iter()
+            .filter_map(|load_cmd| match &load_cmd.command {
+                CommandVariant::Rpath(rpath) => {
+                    extract_dylib_path(self.data, load_cmd.offset, rpath.path)
+                        .filter(|name| name == path)
+                        .map(|_| (load_cmd.offset, rpath.cmdsize as usize))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if commands.is_empty() {
+            log::warn!("No matching rpath load commands found for path: {}", path);
+            return Ok(());
+        }
+
+        let mut data = self.data.to_vec();
+        remove_load_commands(&mut data, self.macho.is_64, &commands);
+
+        self.data = Box::leak(data.into_boxed_slice());
+
+        Ok(())
+    }
+}
+
+// Writes a load command into the free space between the load commands and the first segment.
+fn append_load_command(data: &mut [u8], macho: &GoblinMachO, command: &[u8]) -> Result<(), Error> {
+    let read_u32_le = |data: &[u8], offset: usize| -> u32 {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+
+    let header_size = if macho.is_64 { 32 } else { 28 };
+    let current_sizeofcmds = read_u32_le(data, 20);
+    let current_ncmds = read_u32_le(data, 16);
+
+    let min_fileoff = macho
+        .load_commands
+        .iter()
+        .filter_map(|load_cmd| match &load_cmd.command {
+            CommandVariant::Segment64(seg) if seg.filesize > 0 && seg.fileoff > 0 => {
+                Some(seg.fileoff as usize)
+            }
+            CommandVariant::Segment32(seg) if seg.filesize > 0 && seg.fileoff > 0 => {
+                Some(seg.fileoff as usize)
+            }
+            _ => None,
+        })
+        .min()
+        .unwrap_or(data.len());
+
+    let insert_offset = header_size + current_sizeofcmds as usize;
+    if insert_offset + command.len() > min_fileoff {
+        return Err(Error::Parse);
+    }
+
+    data[insert_offset..insert_offset + command.len()].copy_from_slice(command);
+
+    let new_sizeofcmds = current_sizeofcmds + command.len() as u32;
+    let new_ncmds = current_ncmds + 1;
+    data[20..24].copy_from_slice(&new_sizeofcmds.to_le_bytes());
+    data[16..20].copy_from_slice(&new_ncmds.to_le_bytes());
+
+    Ok(())
+}
+
+fn has_encrypted_slice(macho_file: &MachFile<'_>) -> bool {
+    macho_file.iter_macho().any(|macho| {
+        macho
+            .encryption_info()
+            .is_some_and(|info| info.cryptid != 0)
+    })
+}
+
+// Walks the slices in file order and checks the `cryptid` of their encryption load
+// commands, `skip` moves the reader forward by the given number of bytes.
+fn scan_encryption<R: Read>(
+    reader: &mut R,
+    mut skip: impl FnMut(&mut R, u64) -> io::Result<()>,
+) -> Result<bool, Error> {
+    const FAT_MAGIC: u32 = 0xcafe_babe;
+    const FAT_MAGIC_64: u32 = 0xcafe_babf;
+    const MH_MAGIC: u32 = 0xfeed_face;
+    const MH_MAGIC_64: u32 = 0xfeed_facf;
+    const LC_ENCRYPTION_INFO: u32 = 0x21;
+    const LC_ENCRYPTION_INFO_64: u32 = 0x2c;
+    // Far more than any real binary has, guards the allocations against garbage
+    const MAX_FAT_ARCHS: usize = 64;
+    const MAX_SIZEOFCMDS: u32 = 16 << 20;
+
+    let read = |reader: &mut R, len: usize| -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf)?;
+        Ok(buf)
+    };
+    let be = |buf: &[u8], at: usize| {
+        u32::from_be_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+    };
+    let le = |buf: &[u8], at: usize| {
+        u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+    };
+
+    let magic = read(reader, 4)?;
+    let mut position = 4;
+    let mut thin_magic = None;
+    let mut offsets = Vec::new();
+
+    match be(&magic, 0) {
+        fat @ (FAT_MAGIC | FAT_MAGIC_64) => {
+            let count = be(&read(reader, 4)?, 0) as usize;
+            if count > MAX_FAT_ARCHS {
+                return Err(Error::Parse);
+            }
+
+            let arch_size = if fat == FAT_MAGIC_64 { 32 } else { 20 };
+            let archs = read(reader, count * arch_size)?;
+            position += 4 + archs.len() as u64;
+            for arch in archs.chunks(arch_size) {
+                offsets.push(if fat == FAT_MAGIC_64 {
+                    (be(arch, 8) as u64) << 32 | be(arch, 12) as u64
+                } else {
+                    be(arch, 8) as u64
+                });
+            }
+            offsets.sort_unstable();
+        }
+        _ => {
+            thin_magic = Some(magic);
+            offsets.push(0);
+        }
+    }
+
+    for offset in offsets {
+        let mut header = match thin_magic.take() {
+            Some(magic) => magic,
+            None => {
+                skip(reader, offset.checked_sub(position).ok_or(Error::Parse)?)?;
+                position = offset + 4;
+                read(reader, 4)?
+            }
+        };
+
+        let is_64 = match le(&header, 0) {
+            MH_MAGIC => false,
+            MH_MAGIC_64 => true,
+            _ => return Err(Error::Parse),
+        };
+        header.extend(read(reader, if is_64 { 28 } else { 24 })?);
+
+        let sizeofcmds = le(&header, 20);
+        if sizeofcmds > MAX_SIZEOFCMDS {
+            return Err(Error::Parse);
+        }
+        let commands = read(reader, sizeofcmds as usize)?;
+        position += (header.len() - 4 + commands.len()) as u64;
+
+        let mut at = 0;
+        while at + 8 <= commands.len() {
+            let cmd = le(&commands, at);
+            let cmdsize = le(&commands, at + 4) as usize;
+            // cryptid follows cmd, cmdsize, cryptoff and cryptsize
+            if (cmd == LC_ENCRYPTION_INFO || cmd == LC_ENCRYPTION_INFO_64)
+                && at + 20 <= commands.len()
+                && le(&commands, at + 16) != 0
+            {
+                return Ok(true);
+            }
+            if cmdsize < 8 {
+                break;
+            }
+            at += cmdsize;
+        }
+    }
+
+    Ok(false)
+}
+
+// Removes load commands given as (offset, cmdsize), shifting the remaining commands down.
+// The freed space at the end of the load commands is zeroed so segment offsets stay valid.
+fn remove_load_commands(data: &mut [u8], is_64: bool, commands: &[(usize, usize)]) {
+    let read_u32_le = |data: &[u8], offset: usize| -> u32 {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+
+    let header_size = if is_64 { 32 } else { 28 };
+    let current_sizeofcmds = read_u32_le(data, 20) as usize;
+    let current_ncmds = read_u32_le(data, 16);
+    let mut load_commands_end = header_size + current_sizeofcmds;
+
+    let mut commands = commands.to_vec();
+    commands.sort_by(|a, b| b.0.cmp(&a.0));
+
+    for (cmd_offset, cmdsize) in &commands {
+        data.copy_within(cmd_offset + cmdsize..load_commands_end, *cmd_offset);
+        load_commands_end -= cmdsize;
+        data[load_commands_end..load_commands_end + cmdsize].fill(0);
+    }
+
+    let removed_size: usize = commands.iter().map(|(_, cmdsize)| cmdsize).sum();
+    let new_sizeofcmds = (current_sizeofcmds - removed_size) as u32;
+    let new_ncmds = current_ncmds - commands.len() as u32;
+    data[20..24].copy_from_slice(&new_sizeofcmds.to_le_bytes());
+    data[16..20].copy_from_slice(&new_ncmds.to_le_bytes());
+}
+
+// The CDHash is the code directory digest truncated to 20 bytes.
+fn cdhash(code_directory: &CodeDirectoryBlob<'_>) -> Result<String, Error> {
+    let digest = code_directory.digest_with(code_directory.digest_type)?;
+    Ok(hex::encode(&digest[..digest.len().min(20)]))
+}
+
+// X.Y.Z is packed as xxxx.yy.zz: 16 bits for X, a byte each for Y and Z
+fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        version >> 16,
+        (version >> 8) & 0xff,
+        version & 0xff
+    )
+}
+
+fn platform_name(platform: u32) -> String {
+    match platform {
+        PLATFORM_MACOS => "macos",
+        PLATFORM_IOS => "ios",
+        PLATFORM_TVOS => "tvos",
+        PLATFORM_WATCHOS => "watchos",
+        PLATFORM_MACCATALYST => "maccatalyst",
+        PLATFORM_IOSSIMULATOR => "iossimulator",
+        PLATFORM_DRIVERKIT => "driverkit",
+        PLATFORM_VISIONOS => "visionos",
+        _ => return format!("unknown ({})", platform),
+    }
+    .to_string()
 }
 
 fn extract_dylib_path(
@@ -4,10 +4,11 @@ mod certificate;
 #[cfg(feature = "tweaks")]
 mod macho;
 mod provision;
+pub mod secrets;
 
 pub use certificate::CertificateIdentity;
 #[cfg(feature = "tweaks")]
-pub use macho::{MachO, MachOExt};
+pub use macho::{BuildVersion, CodeSignatureInfo, EncryptionInfo, MachO, MachOExt, MachOSliceInfo};
 pub use provision::MobileProvision;
 
 pub const TEAM_ID_REGEX: &str = r"^[A-Z0-9]{10}\.";
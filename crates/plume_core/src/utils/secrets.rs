@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use aes_gcm::{AeadInOut, Aes256Gcm, KeyInit, Nonce};
+use sha2::Sha256;
+
+use crate::Error;
+
+// Anything that does not start with this is read as plaintext, which is how
+// files written before encryption keep loading until they are saved again.
+const SEALED_MAGIC: &[u8] = b"PLUMESEC1";
+// Passphrase sealed data carries its salt, so it opens on any machine
+const PASSPHRASE_MAGIC: &[u8] = b"PLUMEPASS1";
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// Environment variable that switches the default store to a passphrase.
+pub const PASSPHRASE_ENV: &str = "PLUME_PASSPHRASE";
+
+#[derive(Clone)]
+pub struct SecretKey([u8; 32]);
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+/// Source of the key that seals credentials written to disk.
+pub trait SecretStore: fmt::Debug + Send + Sync {
+    fn key(&self) -> Result<SecretKey, Error>;
+}
+
+/// Keeps a random key in a file only the current user can read.
+#[derive(Debug)]
+pub struct FileSecretStore {
+    path: PathBuf,
+}
+
+impl FileSecretStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl SecretStore for FileSecretStore {
+    fn key(&self) -> Result<SecretKey, Error> {
+        let bytes = read_or_create(&self.path, || rand::random::<[u8; 32]>().to_vec())?;
+        let key = bytes
+            .try_into()
+            .map_err(|_| Error::SecretStore(format!("{} is not a key", self.path.display())))?;
+        Ok(SecretKey(key))
+    }
+}
+
+/// Derives the key from a passphrase, only a random salt is written to disk.
+pub struct PassphraseSecretStore {
+    passphrase: String,
+    salt_path: PathBuf,
+    key: OnceLock<SecretKey>,
+}
+
+impl PassphraseSecretStore {
+    pub fn new(passphrase: String, salt_path: PathBuf) -> Self {
+        Self {
+            passphrase,
+            salt_path,
+            key: OnceLock::new(),
+        }
+    }
+}
+
+impl fmt::Debug for PassphraseSecretStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PassphraseSecretStore")
+            .field("salt_path", &self.salt_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecretStore for PassphraseSecretStore {
+    fn key(&self) -> Result<SecretKey, Error> {
+        if let Some(key) = self.key.get() {
+            return Ok(key.clone());
+        }
+
+        let salt = read_or_create(&self.salt_path, || {
+            rand::random::<[u8; SALT_LEN]>().to_vec()
+        })?;
+        let key = derive_key(&self.passphrase, &salt)?;
+
+        Ok(self.key.get_or_init(|| key).clone())
+    }
+}
+
+/// Store used for files in `dir`, a passphrase from [`PASSPHRASE_ENV`] wins over the key file.
+///
+/// Stores are kept for the life of the process, so a passphrase is only derived once per
+/// directory and [`PASSPHRASE_ENV`] is read the first time `dir` is asked for.
+pub fn default_store(dir: &Path) -> Arc<dyn SecretStore> {
+    static STORES: OnceLock<Mutex<HashMap<PathBuf, Arc<dyn SecretStore>>>> = OnceLock::new();
+
+    let mut stores = STORES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    stores
+        .entry(dir.to_path_buf())
+        .or_insert_with(|| match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) if !passphrase.is_empty() => Arc::new(PassphraseSecretStore::new(
+                passphrase,
+                dir.join("secrets.salt"),
+            )),
+            _ => Arc::new(FileSecretStore::new(dir.join("secrets.key"))),
+        })
+        .clone()
+}
+
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(SEALED_MAGIC)
+}
+
+/// Encrypts `plaintext` with AES-256-GCM, the output carries its own nonce.
+pub fn seal(key: &SecretKey, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let cipher = Aes256Gcm::new(&key.0.into());
+    let nonce_bytes = rand::random::<[u8; NONCE_LEN]>();
+    let nonce = Nonce::try_from(&nonce_bytes[..])?;
+
+    let mut buf = plaintext.to_vec();
+    cipher.encrypt_in_place(&nonce, SEALED_MAGIC, &mut buf)?;
+
+    let mut sealed = Vec::with_capacity(SEALED_MAGIC.len() + NONCE_LEN + buf.len());
+    sealed.extend_from_slice(SEALED_MAGIC);
+    sealed.extend_from_slice(&nonce_bytes);
+    sealed.extend_from_slice(&buf);
+    Ok(sealed)
+}
+
+/// Decrypts data from [`seal`], plaintext is passed through unchanged.
+pub fn open(key: &SecretKey, data: &[u8]) -> Result<Vec<u8>, Error> {
+    if !is_sealed(data) {
+        return Ok(data.to_vec());
+    }
+
+    let data = &data[SEALED_MAGIC.len()..];
+    if data.len() < NONCE_LEN {
+        return Err(Error::SecretStore("Sealed data is truncated".into()));
+    }
+
+    let cipher = Aes256Gcm::new(&key.0.into());
+    let nonce = Nonce::try_from(&data[..NONCE_LEN])?;
+
+    let mut buf = data[NONCE_LEN..].to_vec();
+    cipher.decrypt_in_place(&nonce, SEALED_MAGIC, &mut buf)?;
+    Ok(buf)
+}
+
+/// PBKDF2-SHA256 over `passphrase`, slow on purpose.
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<SecretKey, Error> {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<hmac::Hmac<Sha256>>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key)?;
+    Ok(SecretKey(key))
+}
+
+/// Like [`seal`], with a key derived from `passphrase` and a fresh salt stored in the output.
+pub fn seal_with_passphrase(passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let salt = rand::random::<[u8; SALT_LEN]>();
+    let sealed = seal(&derive_key(passphrase, &salt)?, plaintext)?;
+
+    let mut out = Vec::with_capacity(PASSPHRASE_MAGIC.len() + SALT_LEN + sealed.len());
+    out.extend_from_slice(PASSPHRASE_MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// Decrypts data from [`seal_with_passphrase`], a wrong passphrase fails authentication.
+pub fn open_with_passphrase(passphrase: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let data = data
+        .strip_prefix(PASSPHRASE_MAGIC)
+        .ok_or_else(|| Error::SecretStore("Data is not sealed with a passphrase".into()))?;
+    if data.len() < SALT_LEN || !is_sealed(&data[SALT_LEN..]) {
+        return Err(Error::SecretStore("Sealed data is truncated".into()));
+    }
+
+    let (salt, sealed) = data.split_at(SALT_LEN);
+    open(&derive_key(passphrase, salt)?, sealed)
+}
+
+/// Reads a secret file, or creates it with `init` readable by the current user only.
+fn read_or_create(path: &Path, init: impl FnOnce() -> Vec<u8>) -> Result<Vec<u8>, Error> {
+    match fs::read(path) {
+        Ok(bytes) if !bytes.is_empty() => return Ok(bytes),
+        // Left behind by a crash between creating the file and writing the key
+        Ok(_) => fs::remove_file(path).or_else(|e| match e.kind() {
+            ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        })?,
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{:016x}.tmp", rand::random::<u64>()));
+    let temp = PathBuf::from(temp);
+
+    let bytes = init();
+    let linked = write_synced(&temp, &bytes).and_then(|()| fs::hard_link(&temp, path));
+    fs::remove_file(&temp).ok();
+
+    // The key only appears once it is on disk, and linking never replaces an existing
+    // file, so two processes starting at once agree on one key
+    match linked {
+        Ok(()) => Ok(bytes),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(fs::read(path)?),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
@@ -1,6 +1,10 @@
 mod gsa_account;
+mod quota;
 mod refresh;
+mod schema;
 mod store;
 pub use gsa_account::{GsaAccount, account_from_session};
+pub use quota::{AppIdQuota, CreatedAppId, FREE_APP_ID_LIMIT, FREE_APP_ID_WINDOW_DAYS};
 pub use refresh::{RefreshApp, RefreshDevice};
+pub use schema::SCHEMA_VERSION;
 pub use store::AccountStore;
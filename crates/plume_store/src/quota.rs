@@ -0,0 +1,41 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+// free teams get 10 new App IDs per rolling 7 days, deleting one does not give the slot back
+pub const FREE_APP_ID_LIMIT: usize = 10;
+pub const FREE_APP_ID_WINDOW_DAYS: i64 = 7;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreatedAppId {
+    pub identifier: String,
+    pub created: DateTime<Utc>,
+}
+
+impl CreatedAppId {
+    pub fn frees_at(&self) -> DateTime<Utc> {
+        self.created + Duration::days(FREE_APP_ID_WINDOW_DAYS)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppIdQuota {
+    pub used: usize,
+    pub limit: usize,
+    pub next_slot: Option<DateTime<Utc>>, // when the oldest tracked creation stops counting
+}
+
+impl AppIdQuota {
+    pub fn new(created: &[CreatedAppId], now: DateTime<Utc>) -> Self {
+        let active = created.iter().filter(|app_id| app_id.frees_at() > now);
+
+        Self {
+            used: active.clone().count(),
+            limit: FREE_APP_ID_LIMIT,
+            next_slot: active.map(CreatedAppId::frees_at).min(),
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.limit.saturating_sub(self.used)
+    }
+}
@@ -0,0 +1,103 @@
+use serde_json::{Map, Value};
+
+use plume_core::Error;
+
+/// Version written to `accounts.json`, bump it together with a new step in `MIGRATIONS`.
+pub const SCHEMA_VERSION: u64 = 1;
+
+// MIGRATIONS[n] upgrades a version n store to n + 1
+const MIGRATIONS: [fn(&mut Map<String, Value>); SCHEMA_VERSION as usize] = [v0_to_v1];
+
+/// Upgrades a parsed store in place and returns the version it was written with.
+pub(crate) fn migrate(value: &mut Value) -> Result<u64, Error> {
+    let store = value.as_object_mut().ok_or(Error::Parse)?;
+    let version = store.get("version").and_then(Value::as_u64).unwrap_or(0);
+
+    if version > SCHEMA_VERSION {
+        return Err(Error::Store(format!(
+            "written by a newer version (schema {version}, this build reads up to {SCHEMA_VERSION})"
+        )));
+    }
+
+    for step in &MIGRATIONS[version as usize..] {
+        step(store);
+    }
+    store.insert("version".to_string(), SCHEMA_VERSION.into());
+
+    Ok(version)
+}
+
+// Unversioned stores leaned on serde defaults for everything added after the first release
+fn v0_to_v1(store: &mut Map<String, Value>) {
+    store.entry("selected_account").or_insert(Value::Null);
+    store.entry("locale").or_insert(Value::Null);
+    for key in ["accounts", "refreshes", "app_ids"] {
+        store
+            .entry(key)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+
+    if let Some(Value::Object(accounts)) = store.get_mut("accounts") {
+        for account in accounts.values_mut().filter_map(Value::as_object_mut) {
+            account
+                .entry("team_id")
+                .or_insert_with(|| Value::String(String::new()));
+        }
+    }
+
+    if let Some(Value::Object(refreshes)) = store.get_mut("refreshes") {
+        let apps = refreshes
+            .values_mut()
+            .filter_map(|device| device.get_mut("apps"))
+            .filter_map(Value::as_array_mut)
+            .flatten()
+            .filter_map(Value::as_object_mut);
+        for app in apps {
+            app.entry("name").or_insert(Value::Null);
+            app.entry("bundle_id").or_insert(Value::Null);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn migrates_unversioned_store() {
+        let mut store = json!({
+            "accounts": { "a@example.com": { "email": "a@example.com" } },
+            "refreshes": { "udid": { "apps": [{ "path": "/app" }] } },
+        });
+
+        assert_eq!(migrate(&mut store).unwrap(), 0);
+        assert_eq!(store["version"], SCHEMA_VERSION);
+        assert_eq!(store["selected_account"], Value::Null);
+        assert_eq!(store["locale"], Value::Null);
+        assert_eq!(store["app_ids"], json!({}));
+        assert_eq!(store["accounts"]["a@example.com"]["team_id"], "");
+        assert_eq!(store["refreshes"]["udid"]["apps"][0]["name"], Value::Null);
+        assert_eq!(
+            store["refreshes"]["udid"]["apps"][0]["bundle_id"],
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn keeps_values_of_current_store() {
+        let mut store = json!({ "version": SCHEMA_VERSION, "locale": "de" });
+
+        assert_eq!(migrate(&mut store).unwrap(), SCHEMA_VERSION);
+        assert_eq!(store["locale"], "de");
+    }
+
+    #[test]
+    fn refuses_newer_store() {
+        let mut store = json!({ "version": SCHEMA_VERSION + 1 });
+
+        assert!(matches!(migrate(&mut store), Err(Error::Store(_))));
+        assert_eq!(store["version"], SCHEMA_VERSION + 1);
+    }
+}
@@ -1,11 +1,17 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
 
 use plume_core::Error;
+use plume_core::secrets::{self, SecretStore};
 
-use crate::{GsaAccount, RefreshDevice};
+use crate::schema::{self, SCHEMA_VERSION};
+use crate::{AppIdQuota, CreatedAppId, GsaAccount, RefreshDevice};
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct AccountStore {
@@ -15,21 +21,19 @@ pub struct AccountStore {
     refreshes: HashMap<String, RefreshDevice>, // UDID -> RefreshDevice (apps?)
     #[serde(default)]
     locale: Option<String>, // None = system locale
+    #[serde(default)]
+    app_ids: HashMap<String, Vec<CreatedAppId>>, // Team ID -> App IDs created by us
     #[serde(skip)]
     path: Option<PathBuf>,
+    #[serde(skip)]
+    secrets: Option<Arc<dyn SecretStore>>, // seals the file, tokens are as good as a password
 }
 
 impl AccountStore {
     pub async fn load(path: &Option<PathBuf>) -> Result<Self, Error> {
         if let Some(path) = path {
-            let mut settings = if !path.exists() {
-                Self::default()
-            } else {
-                let contents = tokio::fs::read_to_string(path).await?;
-                serde_json::from_str(&contents)?
-            };
-            settings.path = Some(path.clone());
-            Ok(settings)
+            let _lock = lock(path).await?;
+            Self::read(path).await
         } else {
             Ok(Self::default())
         }
@@ -37,41 +41,215 @@ impl AccountStore {
 
     pub fn load_sync(path: &Option<PathBuf>) -> Result<Self, Error> {
         if let Some(path) = path {
-            let mut settings = if !path.exists() {
-                Self::default()
-            } else {
-                let contents = std::fs::read_to_string(path)?;
-                serde_json::from_str(&contents)?
-            };
-            settings.path = Some(path.clone());
-            Ok(settings)
+            let _lock = lock_sync(path)?;
+            Self::read_sync(path)
         } else {
             Ok(Self::default())
         }
     }
 
-    pub async fn save(&self) -> Result<(), Error> {
+    /// Applies `f` to the store currently on disk and saves it, all under the
+    /// store lock, so changes made meanwhile by other processes are kept.
+    /// Returns the updated store.
+    pub async fn update(
+        path: &Path,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self, Error> {
+        let _lock = lock(path).await?;
+        let mut store = Self::read(path).await?;
+        f(&mut store)?;
+        store.save().await?;
+        Ok(store)
+    }
+
+    pub fn update_sync(
+        path: &Path,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<Self, Error> {
+        let _lock = lock_sync(path)?;
+        let mut store = Self::read_sync(path)?;
+        f(&mut store)?;
+        store.save_sync()?;
+        Ok(store)
+    }
+
+    // Callers hold the store lock
+    async fn read(path: &Path) -> Result<Self, Error> {
+        let secrets = Self::secret_store(path);
+        let (mut settings, outdated) = if !path.exists() {
+            (Self::default(), false)
+        } else {
+            let contents = tokio::fs::read(path).await?;
+            match Self::decode(&contents, secrets.as_ref()) {
+                Ok((settings, version)) => {
+                    if version < SCHEMA_VERSION {
+                        let backup = seal_backup(&contents, Some(secrets.as_ref()))?;
+                        tokio::fs::write(sibling(path, &format!(".v{version}.bak")), backup)
+                            .await?;
+                    }
+                    let outdated = version < SCHEMA_VERSION || !secrets::is_sealed(&contents);
+                    (settings, outdated)
+                }
+                Err(e) => {
+                    let backup = tokio::fs::read(sibling(path, ".bak")).await.ok();
+                    let settings = Self::recover(backup, secrets.as_ref(), e)?;
+                    tokio::fs::rename(path, sibling(path, ".corrupt")).await?;
+                    (settings, true)
+                }
+            }
+        };
+        settings.path = Some(path.to_path_buf());
+        settings.secrets = Some(secrets);
+        if outdated {
+            settings.save().await?;
+        }
+        Ok(settings)
+    }
+
+    fn read_sync(path: &Path) -> Result<Self, Error> {
+        let secrets = Self::secret_store(path);
+        let (mut settings, outdated) = if !path.exists() {
+            (Self::default(), false)
+        } else {
+            let contents = std::fs::read(path)?;
+            match Self::decode(&contents, secrets.as_ref()) {
+                Ok((settings, version)) => {
+                    if version < SCHEMA_VERSION {
+                        let backup = seal_backup(&contents, Some(secrets.as_ref()))?;
+                        std::fs::write(sibling(path, &format!(".v{version}.bak")), backup)?;
+                    }
+                    let outdated = version < SCHEMA_VERSION || !secrets::is_sealed(&contents);
+                    (settings, outdated)
+                }
+                Err(e) => {
+                    let backup = std::fs::read(sibling(path, ".bak")).ok();
+                    let settings = Self::recover(backup, secrets.as_ref(), e)?;
+                    std::fs::rename(path, sibling(path, ".corrupt"))?;
+                    (settings, true)
+                }
+            }
+        };
+        settings.path = Some(path.to_path_buf());
+        settings.secrets = Some(secrets);
+        if outdated {
+            settings.save_sync()?;
+        }
+        Ok(settings)
+    }
+
+    // Saves write a temporary file and rename it over the store, so a crash
+    // never leaves a half written file. The previous store is kept as `.bak`.
+    async fn save(&self) -> Result<(), Error> {
         if let Some(path) = &self.path {
             if let Some(parent) = path.parent() {
                 tokio::fs::create_dir_all(parent).await?;
             }
 
-            tokio::fs::write(path, serde_json::to_string_pretty(self)?).await?;
+            let temp = sibling(path, &format!(".{}.tmp", std::process::id()));
+            let mut file = tokio::fs::File::create(&temp).await?;
+            file.write_all(&self.encode()?).await?;
+            file.sync_all().await?;
+            drop(file);
+
+            if path.exists() {
+                let previous = tokio::fs::read(path).await?;
+                let backup = seal_backup(&previous, self.secrets.as_deref())?;
+                tokio::fs::write(sibling(path, ".bak"), backup).await?;
+            }
+            tokio::fs::rename(&temp, path).await?;
         }
         Ok(())
     }
 
-    pub fn save_sync(&self) -> Result<(), Error> {
+    fn save_sync(&self) -> Result<(), Error> {
         if let Some(path) = &self.path {
             if let Some(parent) = path.parent() {
                 std::fs::create_dir_all(parent)?;
             }
 
-            std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+            let temp = sibling(path, &format!(".{}.tmp", std::process::id()));
+            let mut file = std::fs::File::create(&temp)?;
+            file.write_all(&self.encode()?)?;
+            file.sync_all()?;
+            drop(file);
+
+            if path.exists() {
+                let previous = std::fs::read(path)?;
+                let backup = seal_backup(&previous, self.secrets.as_deref())?;
+                std::fs::write(sibling(path, ".bak"), backup)?;
+            }
+            std::fs::rename(&temp, path)?;
         }
         Ok(())
     }
 
+    // The key lives next to the store, or comes from `PLUME_PASSPHRASE`
+    fn secret_store(path: &Path) -> Arc<dyn SecretStore> {
+        secrets::default_store(path.parent().unwrap_or(Path::new(".")))
+    }
+
+    // Returns the store and the schema version it was written with. Plaintext
+    // stores from before encryption still decode, `load` seals them right away.
+    fn decode(contents: &[u8], secrets: &dyn SecretStore) -> Result<(Self, u64), Error> {
+        let contents = if secrets::is_sealed(contents) {
+            secrets::open(&secrets.key()?, contents)?
+        } else {
+            contents.to_vec()
+        };
+        Self::decode_json(&contents)
+    }
+
+    fn decode_json(contents: &[u8]) -> Result<(Self, u64), Error> {
+        let mut value: serde_json::Value = serde_json::from_slice(contents)?;
+        let version = schema::migrate(&mut value)?;
+        Ok((serde_json::from_value(value)?, version))
+    }
+
+    // Falls back to the copy from the previous save when the store does not parse.
+    // A store from a newer build is left alone, there is nothing to recover, and
+    // so is a plaintext backup, which only builds before sealed backups wrote.
+    fn recover(
+        backup: Option<Vec<u8>>,
+        secrets: &dyn SecretStore,
+        err: Error,
+    ) -> Result<Self, Error> {
+        if matches!(err, Error::Store(_)) {
+            return Err(err);
+        }
+
+        let backup = backup.filter(|backup| secrets::is_sealed(backup));
+        match backup.map(|backup| Self::decode(&backup, secrets)) {
+            Some(Ok((store, _))) => Ok(store),
+            _ => Err(err),
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, Error> {
+        let contents = self.encode_json()?;
+        match &self.secrets {
+            Some(secrets) => secrets::seal(&secrets.key()?, &contents),
+            None => Ok(contents),
+        }
+    }
+
+    fn encode_json(&self) -> Result<Vec<u8>, Error> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(store) = value.as_object_mut() {
+            store.insert("version".to_string(), SCHEMA_VERSION.into());
+        }
+        Ok(serde_json::to_vec_pretty(&value)?)
+    }
+
+    /// Unsealed JSON of the store, for profiles that move to another machine.
+    pub fn to_portable(&self) -> Result<Vec<u8>, Error> {
+        self.encode_json()
+    }
+
+    /// Reads JSON from [`Self::to_portable`], migrated like a store on disk but not tied to a file.
+    pub fn from_portable(contents: &[u8]) -> Result<Self, Error> {
+        Ok(Self::decode_json(contents)?.0)
+    }
+
     pub fn accounts(&self) -> &HashMap<String, GsaAccount> {
         &self.accounts
     }
@@ -84,49 +262,25 @@ impl AccountStore {
         self.accounts.get(email)
     }
 
-    pub async fn accounts_add(&mut self, account: GsaAccount) -> Result<(), Error> {
-        let email = account.email().clone();
-        self.accounts.insert(email.clone(), account);
-        self.selected_account = Some(email);
-        self.save().await
-    }
+    // Setters only change the store in memory, callers persist them with `update`
 
-    pub fn accounts_add_sync(&mut self, account: GsaAccount) -> Result<(), Error> {
+    pub fn accounts_add(&mut self, account: GsaAccount) {
         let email = account.email().clone();
         self.accounts.insert(email.clone(), account);
         self.selected_account = Some(email);
-        self.save_sync()
-    }
-
-    pub async fn accounts_remove(&mut self, email: &str) -> Result<(), Error> {
-        self.accounts.remove(email);
-        if self.selected_account.as_ref() == Some(&email.to_string()) {
-            self.selected_account = None;
-        }
-        self.save().await
     }
 
-    pub fn accounts_remove_sync(&mut self, email: &str) -> Result<(), Error> {
+    pub fn accounts_remove(&mut self, email: &str) {
         self.accounts.remove(email);
         if self.selected_account.as_ref() == Some(&email.to_string()) {
             self.selected_account = None;
         }
-        self.save_sync()
     }
 
-    pub async fn account_select(&mut self, email: &str) -> Result<(), Error> {
+    pub fn account_select(&mut self, email: &str) -> Result<(), Error> {
         if self.accounts.contains_key(email) {
             self.selected_account = Some(email.to_string());
-            self.save().await
-        } else {
-            Err(Error::Parse) // we need better errors
-        }
-    }
-
-    pub fn account_select_sync(&mut self, email: &str) -> Result<(), Error> {
-        if self.accounts.contains_key(email) {
-            self.selected_account = Some(email.to_string());
-            self.save_sync()
+            Ok(())
         } else {
             Err(Error::Parse) // we need better errors
         }
@@ -144,48 +298,14 @@ impl AccountStore {
         self.locale.as_deref()
     }
 
-    pub fn set_locale_sync(&mut self, locale: Option<String>) -> Result<(), Error> {
+    pub fn set_locale(&mut self, locale: Option<String>) {
         self.locale = locale;
-        self.save_sync()
-    }
-
-    pub async fn accounts_add_from_session(
-        &mut self,
-        email: String,
-        account: plume_core::auth::Account,
-    ) -> Result<(), Error> {
-        let first_name = account.get_name().0;
-        let s = plume_core::developer::DeveloperSession::using_account(account).await?;
-        let teams_response = s.qh_list_teams().await?;
-        let adsid = s.adsid().clone();
-        let xcode_gs_token = s.xcode_gs_token().clone();
-
-        let team_id = if teams_response.teams.is_empty() {
-            "".to_string()
-        } else {
-            teams_response.teams[0].team_id.clone()
-        };
-
-        let account = GsaAccount::new(email, first_name, adsid, xcode_gs_token, team_id);
-
-        self.accounts_add(account).await?;
-
-        Ok(())
     }
 
-    pub async fn update_account_team(&mut self, email: &str, team_id: String) -> Result<(), Error> {
+    pub fn update_account_team(&mut self, email: &str, team_id: String) -> Result<(), Error> {
         if let Some(account) = self.accounts.get_mut(email) {
             account.set_team_id(team_id);
-            self.save().await
-        } else {
-            Err(Error::Parse)
-        }
-    }
-
-    pub fn update_account_team_sync(&mut self, email: &str, team_id: String) -> Result<(), Error> {
-        if let Some(account) = self.accounts.get_mut(email) {
-            account.set_team_id(team_id);
-            self.save_sync()
+            Ok(())
         } else {
             Err(Error::Parse)
         }
@@ -199,29 +319,231 @@ impl AccountStore {
         self.refreshes.get(udid)
     }
 
-    pub async fn add_or_update_refresh_device(
-        &mut self,
-        device: RefreshDevice,
-    ) -> Result<(), Error> {
+    pub fn add_or_update_refresh_device(&mut self, device: RefreshDevice) {
         self.refreshes.insert(device.udid.clone(), device);
-        self.save().await
     }
 
-    pub fn add_or_update_refresh_device_sync(
-        &mut self,
-        device: RefreshDevice,
-    ) -> Result<(), Error> {
-        self.refreshes.insert(device.udid.clone(), device);
-        self.save_sync()
+    pub fn remove_refresh_device(&mut self, udid: &str) {
+        self.refreshes.remove(udid);
     }
 
-    pub async fn remove_refresh_device(&mut self, udid: &str) -> Result<(), Error> {
-        self.refreshes.remove(udid);
-        self.save().await
+    pub fn app_id_quota(&self, team_id: &str) -> AppIdQuota {
+        let created = self
+            .app_ids
+            .get(team_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        AppIdQuota::new(created, Utc::now())
     }
 
-    pub fn remove_refresh_device_sync(&mut self, udid: &str) -> Result<(), Error> {
-        self.refreshes.remove(udid);
-        self.save_sync()
+    pub fn record_app_ids(&mut self, team_id: &str, identifiers: &[String]) {
+        if identifiers.is_empty() {
+            return;
+        }
+
+        let now = Utc::now();
+        let created = self.app_ids.entry(team_id.to_string()).or_default();
+        created.retain(|app_id| app_id.frees_at() > now);
+        created.extend(identifiers.iter().map(|identifier| CreatedAppId {
+            identifier: identifier.clone(),
+            created: now,
+        }));
+    }
+
+    /// Merges a store from another machine, accounts by email and devices by UDID.
+    /// Entries from `other` win, apps on a known device replace the ones with the
+    /// same path or bundle ID and the rest of the device's apps are kept.
+    pub fn merge(&mut self, other: AccountStore) {
+        self.accounts.extend(other.accounts);
+        if self.selected_account.is_none() {
+            self.selected_account = other.selected_account;
+        }
+
+        for (udid, device) in other.refreshes {
+            let Some(existing) = self.refreshes.get_mut(&udid) else {
+                self.refreshes.insert(udid, device);
+                continue;
+            };
+
+            for app in device.apps {
+                existing.apps.retain(|a| {
+                    a.path != app.path && (app.bundle_id.is_none() || a.bundle_id != app.bundle_id)
+                });
+                existing.apps.push(app);
+            }
+            existing.name = device.name;
+            existing.account = device.account;
+        }
+
+        for (team_id, app_ids) in other.app_ids {
+            let created = self.app_ids.entry(team_id).or_default();
+            for app_id in app_ids {
+                if !created.iter().any(|c| c.identifier == app_id.identifier) {
+                    created.push(app_id);
+                }
+            }
+        }
+    }
+
+    /// Whether an app saved for refreshing still needs the App ID, extensions included.
+    pub fn is_app_id_in_use(&self, identifier: &str) -> bool {
+        self.refreshes
+            .values()
+            .flat_map(|device| &device.apps)
+            .filter_map(|app| app.bundle_id.as_deref())
+            .any(|bundle_id| {
+                identifier == bundle_id
+                    || identifier
+                        .strip_prefix(bundle_id)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+    }
+}
+
+// The lock sits on `accounts.json.lock` because saves replace the store file
+// itself. Other processes wait until the returned file is dropped.
+fn lock_sync(path: &Path) -> Result<std::fs::File, Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(sibling(path, ".lock"))?;
+    file.lock()?;
+    Ok(file)
+}
+
+async fn lock(path: &Path) -> Result<std::fs::File, Error> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || lock_sync(&path))
+        .await
+        .map_err(|e| Error::Store(e.to_string()))?
+}
+
+// Backups hold the same tokens as the store, so they are sealed like it
+fn seal_backup(contents: &[u8], store: Option<&dyn SecretStore>) -> Result<Vec<u8>, Error> {
+    match store {
+        Some(store) if !secrets::is_sealed(contents) => secrets::seal(&store.key()?, contents),
+        _ => Ok(contents.to_vec()),
+    }
+}
+
+// `accounts.json` + `.bak` -> `accounts.json.bak`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every test gets its own directory, with its own key
+    fn store_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("plume_store_{}_{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("accounts.json")
+    }
+
+    fn open(path: &Path) -> Vec<u8> {
+        let key = AccountStore::secret_store(path).key().unwrap();
+        secrets::open(&key, &std::fs::read(path).unwrap()).unwrap()
+    }
+
+    fn set_locale(path: &Path, locale: &str) {
+        AccountStore::update_sync(path, |store| {
+            store.set_locale(Some(locale.to_string()));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sealed_round_trip() {
+        let path = store_path("round_trip");
+        set_locale(&path, "de");
+
+        assert!(secrets::is_sealed(&std::fs::read(&path).unwrap()));
+        let store = AccountStore::load_sync(&Some(path)).unwrap();
+        assert_eq!(store.locale(), Some("de"));
+    }
+
+    #[test]
+    fn migration_backs_up_sealed() {
+        let path = store_path("migration");
+        let original = br#"{"locale":"fr","accounts":{}}"#;
+        std::fs::write(&path, original).unwrap();
+
+        let store = AccountStore::load_sync(&Some(path.clone())).unwrap();
+        assert_eq!(store.locale(), Some("fr"));
+
+        for file in [
+            path.clone(),
+            sibling(&path, ".v0.bak"),
+            sibling(&path, ".bak"),
+        ] {
+            assert!(secrets::is_sealed(&std::fs::read(&file).unwrap()));
+        }
+        assert_eq!(open(&sibling(&path, ".v0.bak")), original);
+    }
+
+    #[test]
+    fn recovers_from_backup() {
+        let path = store_path("recover");
+        set_locale(&path, "de");
+        set_locale(&path, "it");
+        std::fs::write(&path, b"not a store").unwrap();
+
+        let store = AccountStore::load_sync(&Some(path.clone())).unwrap();
+        assert_eq!(store.locale(), Some("de"));
+        assert_eq!(
+            std::fs::read(sibling(&path, ".corrupt")).unwrap(),
+            b"not a store"
+        );
+        assert!(secrets::is_sealed(&std::fs::read(&path).unwrap()));
+    }
+
+    #[test]
+    fn ignores_plaintext_backup() {
+        let path = store_path("plaintext_backup");
+        std::fs::write(&path, b"not a store").unwrap();
+        std::fs::write(sibling(&path, ".bak"), br#"{"locale":"de"}"#).unwrap();
+
+        assert!(AccountStore::load_sync(&Some(path.clone())).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a store");
+    }
+
+    #[test]
+    fn refuses_newer_store() {
+        let path = store_path("newer");
+        let newer = format!(r#"{{"version":{}}}"#, SCHEMA_VERSION + 1);
+        std::fs::write(&path, &newer).unwrap();
+
+        assert!(matches!(
+            AccountStore::load_sync(&Some(path.clone())),
+            Err(Error::Store(_))
+        ));
+        assert_eq!(std::fs::read(&path).unwrap(), newer.as_bytes());
+    }
+
+    #[test]
+    fn async_save_round_trip() {
+        let path = store_path("async");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let store = runtime.block_on(async {
+            AccountStore::update(&path, |store| {
+                store.set_locale(Some("ja".to_string()));
+                Ok(())
+            })
+            .await?;
+            AccountStore::load(&Some(path.clone())).await
+        });
+        assert_eq!(store.unwrap().locale(), Some("ja"));
     }
 }
@@ -0,0 +1,123 @@
+//! Times the package pipeline against the previous copy, extract and recompress one.
+//!
+//! Both pipelines unpack the whole app, signing needs every resource on disk to hash it.
+//! What differs is the copy of the source package and the recompression of unchanged
+//! entries, so large apps still need their unpacked size free in the temp directory.
+//!
+//! Signing needs an account, so both paths stand in for it by touching what the signer
+//! rewrites: the executable and code resources of every bundle.
+//!
+//! ```sh
+//! cargo run --release -p plume_utils --example package_bench -- App.ipa
+//! ```
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use std::{env, fs, io};
+
+use plume_utils::{Bundle, Package, PlistInfoTrait, SignerCompression};
+use zip::ZipArchive;
+use zip::write::SimpleFileOptions;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let ipa = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .ok_or("usage: package_bench <package.ipa>")?;
+
+    let stage = env::temp_dir().join("plume_package_bench");
+    fs::remove_dir_all(&stage).ok();
+
+    let start = Instant::now();
+    let legacy_out = legacy(&ipa, &stage)?;
+    report("legacy", start, &legacy_out)?;
+
+    let start = Instant::now();
+    let pkg = Package::new(ipa)?;
+    let bundle = pkg.get_package_bundle()?;
+    pretend_sign(&bundle)?;
+    let out = pkg.get_archive_based_on_path(bundle.bundle_dir(), SignerCompression::default())?;
+    report("package", start, &out)?;
+
+    pkg.remove_package_stage();
+    fs::remove_dir_all(&stage).ok();
+
+    Ok(())
+}
+
+fn report(label: &str, start: Instant, out: &Path) -> io::Result<()> {
+    println!(
+        "{label:>8}: {:>8.2?}  {} bytes",
+        start.elapsed(),
+        fs::metadata(out)?.len()
+    );
+    Ok(())
+}
+
+fn pretend_sign(bundle: &Bundle) -> Result<(), Box<dyn std::error::Error>> {
+    for bundle in bundle.collect_bundles_sorted()? {
+        if let Some(executable) = bundle.get_executable() {
+            let path = bundle.bundle_dir().join(executable);
+            if path.is_file() {
+                fs::OpenOptions::new()
+                    .append(true)
+                    .open(path)?
+                    .write_all(&[0; 16])?;
+            }
+        }
+
+        let signature = bundle.bundle_dir().join("_CodeSignature");
+        fs::create_dir_all(&signature)?;
+        fs::write(signature.join("CodeResources"), b"resources")?;
+    }
+    Ok(())
+}
+
+/// The pipeline before in-place packages: copy, extract, sign, recompress on one thread.
+fn legacy(ipa: &Path, stage: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(stage)?;
+    let copy = stage.join("stage.ipa");
+    fs::copy(ipa, &copy)?;
+    ZipArchive::new(fs::File::open(&copy)?)?.extract(stage)?;
+
+    let payload = stage.join("Payload");
+    let app = fs::read_dir(&payload)?
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .find(|p| p.extension().is_some_and(|e| e == "app"))
+        .ok_or("no app in Payload")?;
+    pretend_sign(&Bundle::new(app)?)?;
+
+    fn add_dir(
+        zip: &mut zip::ZipWriter<fs::File>,
+        path: &Path,
+        prefix: &Path,
+        options: SimpleFileOptions,
+    ) -> zip::result::ZipResult<()> {
+        for entry in fs::read_dir(path)? {
+            let entry_path = entry?.path();
+            let name = entry_path
+                .strip_prefix(prefix)
+                .expect("entry inside the stage")
+                .to_string_lossy()
+                .to_string();
+            if entry_path.is_file() {
+                zip.start_file(name, options)?;
+                io::copy(&mut fs::File::open(&entry_path)?, zip)?;
+            } else if entry_path.is_dir() {
+                zip.add_directory(name, options)?;
+                add_dir(zip, &entry_path, prefix, options)?;
+            }
+        }
+        Ok(())
+    }
+
+    let out = stage.join("resigned.ipa");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&out)?);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    add_dir(&mut zip, &payload, stage, options)?;
+    zip.finish()?;
+
+    Ok(out)
+}
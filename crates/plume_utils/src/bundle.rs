@@ -5,7 +5,11 @@ use goblin::mach::{
     header::{MH_MAGIC, MH_MAGIC_64},
 };
 use plist::Value;
-use std::{fs, path::PathBuf};
+use plume_core::MachO;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone)]
 pub struct Bundle {
@@ -56,8 +60,61 @@ impl Bundle {
 
         Ok(bundles)
     }
+
+    /// Executables in this bundle tree that are still FairPlay encrypted.
+    pub fn encrypted_executables(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut encrypted = Vec::new();
+
+        for bundle in self.collect_bundles_sorted()? {
+            let executable = match bundle.bundle_type() {
+                BundleType::Dylib => bundle.bundle_dir().clone(),
+                _ => match bundle.get_executable() {
+                    Some(name) => bundle.bundle_dir().join(name),
+                    None => continue,
+                },
+            };
+
+            if !executable.is_file() {
+                continue;
+            }
+
+            if MachO::is_file_encrypted(&executable).unwrap_or(false) {
+                encrypted.push(executable);
+            }
+        }
+
+        Ok(encrypted)
+    }
+
+    /// Extensions, Watch apps and the Watch placeholder, which the app runs without.
+    pub fn removable_bundles(&self) -> Result<Vec<Bundle>, Error> {
+        Ok(self
+            .collect_nested_bundles()?
+            .into_iter()
+            .filter(|b| {
+                self.relative_path(b).is_some_and(|path| {
+                    let mut components = path.components();
+                    let dir = components.next().and_then(|c| c.as_os_str().to_str());
+                    dir.is_some_and(|dir| REMOVABLE_DIRS.contains(&dir)) && components.count() == 1
+                })
+            })
+            .collect())
+    }
+
+    /// Path of a nested `bundle` relative to this one.
+    pub fn relative_path<'a>(&self, bundle: &'a Bundle) -> Option<&'a Path> {
+        bundle.bundle_dir.strip_prefix(&self.bundle_dir).ok()
+    }
 }
 
+/// Folders of an app holding bundles it can do without.
+const REMOVABLE_DIRS: &[&str] = &[
+    "PlugIns",
+    "Extensions",
+    "Watch",
+    "com.apple.WatchPlaceholder",
+];
+
 impl Bundle {
     pub fn set_info_plist_key<V: Into<Value>>(&self, key: &str, value: V) -> Result<(), Error> {
         let mut plist = Value::from_file(&self.info_plist_path)?;
@@ -0,0 +1,315 @@
+use std::fmt;
+
+use plist::Value;
+use plume_core::{MachO, MachOExt};
+
+use crate::{Bundle, Error, PlistInfoTrait};
+
+/// What lockdown reports about a device, as far as installing an app goes.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceInfo {
+    pub product_version: String,
+    pub product_type: String,
+    pub cpu_architecture: String,
+    pub device_class: String,
+}
+
+impl DeviceInfo {
+    /// `UIDeviceFamily` value of the device, `None` when lockdown reported something unknown.
+    fn family(&self) -> Option<u64> {
+        match self.device_class.as_str() {
+            "iPhone" | "iPod" => Some(1),
+            "iPad" => Some(2),
+            "AppleTV" => Some(3),
+            "Watch" => Some(4),
+            "RealityDevice" => Some(7),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompatibilityIssue {
+    /// `MinimumOSVersion` is newer than the device.
+    MinimumOsVersion { required: String, installed: String },
+    /// `UIDeviceFamily` leaves out the device, unless it can run the app scaled.
+    DeviceFamily {
+        supported: Vec<u64>,
+        device_class: String,
+        compatibility_mode: bool,
+    },
+    /// `UISupportedDevices` lists models and this one is not among them.
+    UnsupportedDevice { product_type: String },
+    /// No slice of the main executable runs on the device CPU.
+    Architecture { slices: Vec<String>, device: String },
+    /// `UIRequiredDeviceCapabilities` asks for something the device lacks.
+    MissingCapability(String),
+    /// `UIRequiredDeviceCapabilities` excludes something the device has.
+    ForbiddenCapability(String),
+}
+
+impl CompatibilityIssue {
+    /// Blocking issues make installation_proxy refuse the app, the rest only degrade it.
+    pub fn is_blocking(&self) -> bool {
+        match self {
+            Self::DeviceFamily {
+                compatibility_mode, ..
+            } => !compatibility_mode,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for CompatibilityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MinimumOsVersion {
+                required,
+                installed,
+            } => write!(
+                f,
+                "Requires iOS {required} or later, the device runs {installed}"
+            ),
+            Self::DeviceFamily {
+                supported,
+                device_class,
+                compatibility_mode,
+            } => {
+                let supported = supported
+                    .iter()
+                    .map(|family| family_name(*family))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if *compatibility_mode {
+                    write!(
+                        f,
+                        "Built for {supported}, it runs in compatibility mode on {device_class}"
+                    )
+                } else {
+                    write!(f, "Built for {supported}, not {device_class}")
+                }
+            }
+            Self::UnsupportedDevice { product_type } => {
+                write!(f, "{product_type} is not in the app's supported devices")
+            }
+            Self::Architecture { slices, device } => write!(
+                f,
+                "Built for {}, none of which runs on {device}",
+                slices.join(", ")
+            ),
+            Self::MissingCapability(capability) => {
+                write!(
+                    f,
+                    "Requires the {capability} capability, the device lacks it"
+                )
+            }
+            Self::ForbiddenCapability(capability) => {
+                write!(f, "Excludes devices with the {capability} capability")
+            }
+        }
+    }
+}
+
+/// Result of checking a bundle against a device before installing it.
+#[derive(Debug, Clone, Default)]
+pub struct CompatibilityReport {
+    pub issues: Vec<CompatibilityIssue>,
+}
+
+impl CompatibilityReport {
+    /// Checks the Info.plist and main executable of `bundle` against `device`.
+    ///
+    /// Values the device did not report are skipped rather than guessed.
+    pub fn new(bundle: &Bundle, device: &DeviceInfo) -> Result<Self, Error> {
+        let info = Value::from_file(bundle.bundle_dir().join("Info.plist"))?;
+        let info = info.as_dictionary().ok_or(Error::BundleInfoPlistMissing)?;
+        let mut issues = Vec::new();
+
+        let minimum_os = info.get("MinimumOSVersion").and_then(Value::as_string);
+        if let Some(required) = minimum_os.filter(|v| is_older(&device.product_version, v)) {
+            issues.push(CompatibilityIssue::MinimumOsVersion {
+                required: required.to_string(),
+                installed: device.product_version.clone(),
+            });
+        }
+
+        if let Some(family) = device.family() {
+            // Apps without UIDeviceFamily are iPhone apps
+            let supported = match info.get("UIDeviceFamily") {
+                Some(Value::Array(values)) => values.iter().filter_map(family_value).collect(),
+                Some(value) => family_value(value).into_iter().collect(),
+                None => vec![1],
+            };
+
+            if !supported.is_empty() && !supported.contains(&family) {
+                // iPads scale iPhone apps, Vision runs both
+                let compatibility_mode = match family {
+                    2 => supported.contains(&1),
+                    7 => supported.contains(&1) || supported.contains(&2),
+                    _ => false,
+                };
+                issues.push(CompatibilityIssue::DeviceFamily {
+                    supported,
+                    device_class: device.device_class.clone(),
+                    compatibility_mode,
+                });
+            }
+        }
+
+        if let Some(Value::Array(models)) = info.get("UISupportedDevices") {
+            let listed = models
+                .iter()
+                .filter_map(Value::as_string)
+                .any(|model| model == device.product_type);
+            if !models.is_empty() && !device.product_type.is_empty() && !listed {
+                issues.push(CompatibilityIssue::UnsupportedDevice {
+                    product_type: device.product_type.clone(),
+                });
+            }
+        }
+
+        if let Some(executable) = bundle.get_executable() {
+            let path = bundle.bundle_dir().join(executable);
+            if path.is_file() && is_known_arch(&device.cpu_architecture) {
+                let macho = MachO::new(&path)?;
+                let slices = macho
+                    .macho_file()
+                    .iter_macho()
+                    .map(|slice| slice.architecture())
+                    .collect::<Vec<_>>();
+
+                if !slices.iter().any(|slice| runs_slice(device, slice)) {
+                    issues.push(CompatibilityIssue::Architecture {
+                        slices,
+                        device: device.cpu_architecture.clone(),
+                    });
+                }
+            }
+        }
+
+        // A dictionary maps each capability to whether it must be present or absent
+        let capabilities = match info.get("UIRequiredDeviceCapabilities") {
+            Some(Value::Array(values)) => values
+                .iter()
+                .filter_map(Value::as_string)
+                .map(|capability| (capability, true))
+                .collect(),
+            Some(Value::Dictionary(values)) => values
+                .iter()
+                .filter_map(|(capability, value)| Some((capability.as_str(), value.as_boolean()?)))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        for (capability, required) in capabilities {
+            match (has_capability(device, capability), required) {
+                (Some(false), true) => issues.push(CompatibilityIssue::MissingCapability(
+                    capability.to_string(),
+                )),
+                (Some(true), false) => issues.push(CompatibilityIssue::ForbiddenCapability(
+                    capability.to_string(),
+                )),
+                _ => {}
+            }
+        }
+
+        Ok(Self { issues })
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        self.issues.iter().any(CompatibilityIssue::is_blocking)
+    }
+
+    pub fn blocking(&self) -> impl Iterator<Item = &CompatibilityIssue> {
+        self.issues.iter().filter(|issue| issue.is_blocking())
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &CompatibilityIssue> {
+        self.issues.iter().filter(|issue| !issue.is_blocking())
+    }
+}
+
+impl fmt::Display for CompatibilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues = self
+            .issues
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{}", issues.join("\n"))
+    }
+}
+
+fn family_value(value: &Value) -> Option<u64> {
+    value
+        .as_unsigned_integer()
+        .or_else(|| value.as_string()?.parse().ok())
+}
+
+fn family_name(family: u64) -> String {
+    match family {
+        1 => "iPhone".to_string(),
+        2 => "iPad".to_string(),
+        3 => "Apple TV".to_string(),
+        4 => "Apple Watch".to_string(),
+        7 => "Apple Vision".to_string(),
+        family => format!("device family {family}"),
+    }
+}
+
+/// Whether `installed` is an older version than `required`, `1.2` being equal to `1.2.0`.
+fn is_older(installed: &str, required: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .split('.')
+            .map(|part| part.trim().parse::<u32>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+
+    let (Some(installed), Some(required)) = (parse(installed), parse(required)) else {
+        return false;
+    };
+
+    for index in 0..installed.len().max(required.len()) {
+        let installed = installed.get(index).copied().unwrap_or(0);
+        let required = required.get(index).copied().unwrap_or(0);
+        if installed != required {
+            return installed < required;
+        }
+    }
+    false
+}
+
+fn is_known_arch(arch: &str) -> bool {
+    matches!(arch, "arm64e" | "arm64" | "armv7s" | "armv7" | "armv6")
+}
+
+fn runs_slice(device: &DeviceInfo, slice: &str) -> bool {
+    match device.cpu_architecture.as_str() {
+        "arm64e" => matches!(slice, "arm64e" | "arm64"),
+        // 32-bit apps stopped launching with iOS 11
+        "arm64" => {
+            slice == "arm64"
+                || (slice.starts_with("armv7") && is_older(&device.product_version, "11.0"))
+        }
+        "armv7s" => matches!(slice, "armv7s" | "armv7" | "armv6"),
+        "armv7" => matches!(slice, "armv7" | "armv6"),
+        "armv6" => slice == "armv6",
+        _ => true,
+    }
+}
+
+/// Capabilities that can be told from lockdown values, `None` for the rest.
+fn has_capability(device: &DeviceInfo, capability: &str) -> Option<bool> {
+    let arch = device.cpu_architecture.as_str();
+    let known_arch = is_known_arch(arch);
+
+    match capability {
+        "arm64" if known_arch => Some(arch.starts_with("arm64")),
+        "armv7" if known_arch => Some(arch != "armv6"),
+        "armv6" if known_arch => Some(!arch.starts_with("arm64")),
+        "iphone-ipad-minimum-performance-a12" if known_arch => Some(arch == "arm64e"),
+        "telephony" | "sms" if device.family().is_some() => Some(device.device_class == "iPhone"),
+        _ => None,
+    }
+}
@@ -0,0 +1,474 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    env,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::Error;
+
+/// Packages a jailbreak provides itself, there is nothing to inject for them.
+const PROVIDED_PACKAGES: &[&str] = &[
+    "firmware",
+    "mobilesubstrate",
+    "com.ex.substitute",
+    "org.coolstar.libhooker",
+    "com.saurik.substrate.safemode",
+    "ellekit",
+    "preferenceloader",
+];
+
+/// The fields of a .deb `control` file that matter when injecting it.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DebControl {
+    pub package: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// `Depends` entries, each a list of alternatives without version constraints.
+    pub depends: Vec<Vec<String>>,
+    pub provides: Vec<String>,
+}
+
+impl DebControl {
+    pub fn parse(contents: &str) -> Self {
+        let mut fields: Vec<(String, String)> = Vec::new();
+
+        for line in contents.lines() {
+            if line.starts_with([' ', '\t']) {
+                // Continuation of the previous field.
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some((key, value)) = line.split_once(':') {
+                fields.push((key.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        };
+
+        let relations = |key: &str| -> Vec<Vec<String>> {
+            field(key)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|clause| {
+                            clause
+                                .split('|')
+                                .map(package_name)
+                                .filter(|name| !name.is_empty())
+                                .collect::<Vec<_>>()
+                        })
+                        .filter(|clause| !clause.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Self {
+            package: field("package").unwrap_or_default(),
+            name: field("name"),
+            version: field("version"),
+            depends: relations("depends"),
+            provides: relations("provides").into_iter().flatten().collect(),
+        }
+    }
+
+    /// Dependencies that something has to inject, i.e. not provided by the jailbreak.
+    pub fn injectable_depends(&self) -> Vec<Vec<String>> {
+        self.depends
+            .iter()
+            .filter(|clause| !clause.iter().any(|name| is_provided(name)))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Strips version constraints and architecture qualifiers, e.g. `libfoo:iphoneos-arm (>= 1.0)`.
+fn package_name(relation: &str) -> String {
+    let name = relation.split('(').next().unwrap_or_default().trim();
+    name.split(':')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+fn is_provided(name: &str) -> bool {
+    PROVIDED_PACKAGES.contains(&name) || name.starts_with("cy+")
+}
+
+/// Dependencies resolved for a set of tweaks.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TweakDependencies {
+    /// Packages from the repository to install before the tweaks, deepest dependencies first.
+    pub resolved: Vec<PathBuf>,
+    /// Dependencies that neither the tweaks nor the repository provide.
+    pub unresolved: Vec<String>,
+}
+
+/// A local directory of .deb files used to resolve tweak dependencies.
+#[derive(Debug, Clone, Default)]
+pub struct TweakRepository {
+    packages: Vec<(PathBuf, DebControl)>,
+}
+
+impl TweakRepository {
+    pub async fn open<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let mut entries = tokio::fs::read_dir(dir.as_ref()).await?;
+        let mut packages = Vec::new();
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("deb") {
+                continue;
+            }
+
+            match read_control(&path).await {
+                Ok(Some(control)) if !control.package.is_empty() => packages.push((path, control)),
+                Ok(_) => log::warn!("No control file in {}", path.display()),
+                Err(e) => log::warn!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+
+        packages.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(Self { packages })
+    }
+
+    fn find(&self, name: &str) -> Option<&(PathBuf, DebControl)> {
+        self.packages
+            .iter()
+            .find(|(_, c)| c.package == name)
+            .or_else(|| {
+                self.packages
+                    .iter()
+                    .find(|(_, c)| c.provides.iter().any(|p| p == name))
+            })
+    }
+
+    /// Resolves the `Depends` of the given tweaks, dependencies of dependencies included.
+    pub async fn resolve(&self, tweak_files: &[PathBuf]) -> Result<TweakDependencies, Error> {
+        let mut tweaks = Vec::new();
+
+        for tweak_file in tweak_files {
+            if tweak_file.extension().and_then(|e| e.to_str()) != Some("deb") {
+                continue;
+            }
+
+            if let Some(control) = read_control(tweak_file).await? {
+                tweaks.push(control);
+            }
+        }
+
+        Ok(self.resolve_controls(&tweaks))
+    }
+
+    fn resolve_controls(&self, tweaks: &[DebControl]) -> TweakDependencies {
+        let mut installed = HashSet::new();
+
+        for control in tweaks {
+            installed.insert(control.package.clone());
+            installed.extend(control.provides.iter().cloned());
+        }
+
+        let mut dependencies = TweakDependencies::default();
+        let mut unresolved = BTreeSet::new();
+
+        for clause in tweaks.iter().flat_map(DebControl::injectable_depends) {
+            self.visit(
+                &clause,
+                &mut installed,
+                &mut dependencies.resolved,
+                &mut unresolved,
+            );
+        }
+
+        dependencies.unresolved = unresolved.into_iter().collect();
+
+        dependencies
+    }
+
+    // Post-order, so a package lands after everything it depends on. It counts as
+    // installed before its own dependencies are visited, which ends cycles there.
+    fn visit(
+        &self,
+        clause: &[String],
+        installed: &mut HashSet<String>,
+        resolved: &mut Vec<PathBuf>,
+        unresolved: &mut BTreeSet<String>,
+    ) {
+        if clause.iter().any(|name| installed.contains(name)) {
+            return;
+        }
+
+        match clause.iter().find_map(|name| self.find(name)) {
+            Some((path, control)) => {
+                installed.insert(control.package.clone());
+                installed.extend(control.provides.iter().cloned());
+                for dependency in control.injectable_depends() {
+                    self.visit(&dependency, installed, resolved, unresolved);
+                }
+                resolved.push(path.clone());
+            }
+            None => {
+                unresolved.insert(clause.join(" | "));
+            }
+        }
+    }
+}
+
+/// Reads the control file of a .deb without extracting its data.
+pub async fn read_control(deb_path: &Path) -> Result<Option<DebControl>, Error> {
+    let stage_dir = env::temp_dir().join(format!("plume_deb_{}", Uuid::new_v4()));
+    tokio::fs::create_dir_all(&stage_dir).await?;
+
+    let control = extract_control(deb_path, &stage_dir).await;
+
+    tokio::fs::remove_dir_all(&stage_dir).await.ok();
+
+    control
+}
+
+/// Extracts the first member of a .deb (an ar archive) whose name starts with `prefix`
+/// into `dest`, the codec is detected from its contents.
+///
+/// Returns false when the .deb has no such member.
+pub(crate) async fn extract_member(
+    deb_path: &Path,
+    prefix: &str,
+    stage_dir: &Path,
+    dest: &Path,
+) -> Result<bool, Error> {
+    let deb_path_sync = deb_path.to_path_buf();
+    let prefix_sync = prefix.to_string();
+    let ar_dir = stage_dir.join("ar_contents");
+    let dest_sync = dest.to_path_buf();
+
+    tokio::task::spawn_blocking(move || -> Result<bool, decompress::DecompressError> {
+        let member =
+            decompress::decompressors::ar::extract_member(&deb_path_sync, &prefix_sync, &ar_dir)?;
+        let Some(member) = member else {
+            return Ok(false);
+        };
+
+        std::fs::create_dir_all(&dest_sync)?;
+        decompress::decompress(&member, &dest_sync, &extract_opts())?;
+        Ok(true)
+    })
+    .await
+    .map_err(|e| Error::TweakExtractionFailed(format!("Failed to extract {}: {}", prefix, e)))?
+    .map_err(|e| match e {
+        e @ decompress::DecompressError::UnsafeEntry { .. } => Error::from_decompress(e),
+        e => Error::TweakExtractionFailed(format!("Failed to extract {}: {}", prefix, e)),
+    })
+}
+
+pub(crate) async fn extract_control(
+    deb_path: &Path,
+    stage_dir: &Path,
+) -> Result<Option<DebControl>, Error> {
+    let control_dir = stage_dir.join("control_contents");
+    if !extract_member(deb_path, "control.tar", stage_dir, &control_dir).await? {
+        return Ok(None);
+    }
+
+    match tokio::fs::read_to_string(control_dir.join("control")).await {
+        Ok(contents) => Ok(Some(DebControl::parse(&contents))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn extract_opts() -> decompress::ExtractOpts {
+    decompress::ExtractOpts {
+        strip: 0,
+        detect_content: true,
+        filter: Box::new(|_: &std::path::Path| true),
+        map: Box::new(|p| std::borrow::Cow::Borrowed(p)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(controls: &[&str]) -> TweakRepository {
+        TweakRepository {
+            packages: controls
+                .iter()
+                .map(|contents| {
+                    let control = DebControl::parse(contents);
+                    (PathBuf::from(format!("{}.deb", control.package)), control)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parse_joins_continuation_lines() {
+        let control = DebControl::parse(
+            "Package: com.example.tweak\nName: Tweak\nDepends: libfoo,\n  libbar,\n\tlibbaz\nVersion: 1.0\n",
+        );
+
+        assert_eq!(control.package, "com.example.tweak");
+        assert_eq!(control.name.as_deref(), Some("Tweak"));
+        assert_eq!(control.version.as_deref(), Some("1.0"));
+        assert_eq!(
+            control.depends,
+            vec![
+                vec!["libfoo".to_string()],
+                vec!["libbar".to_string()],
+                vec!["libbaz".to_string()]
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_keeps_alternatives_together() {
+        let control = DebControl::parse("Package: a\nDepends: libfoo | libbar, libbaz\n");
+
+        assert_eq!(
+            control.depends,
+            vec![
+                vec!["libfoo".to_string(), "libbar".to_string()],
+                vec!["libbaz".to_string()]
+            ]
+        );
+    }
+
+    #[test]
+    fn package_name_strips_architecture_and_version() {
+        assert_eq!(package_name("libfoo:iphoneos-arm (>= 1.0)"), "libfoo");
+        assert_eq!(package_name(" libbar (<< 2) "), "libbar");
+        assert_eq!(package_name("libbaz:any"), "libbaz");
+
+        let control = DebControl::parse(
+            "Package: a\nDepends: libfoo:iphoneos-arm64 (>= 1.2) | libbar (= 3)\nProvides: libqux (= 1)\n",
+        );
+        assert_eq!(
+            control.depends,
+            vec![vec!["libfoo".to_string(), "libbar".to_string()]]
+        );
+        assert_eq!(control.provides, vec!["libqux".to_string()]);
+    }
+
+    #[test]
+    fn injectable_depends_skips_jailbreak_packages() {
+        let control = DebControl::parse(
+            "Package: a\nDepends: mobilesubstrate, firmware (>= 14.0), cy+cpu.arm64, ellekit | libfoo, libbar\n",
+        );
+
+        assert_eq!(
+            control.injectable_depends(),
+            vec![vec!["libbar".to_string()]]
+        );
+    }
+
+    #[test]
+    fn resolve_uses_provides() {
+        let repository = repository(&["Package: com.example.hooks\nProvides: libhooks\n"]);
+        let tweak = DebControl::parse("Package: tweak\nDepends: libhooks\n");
+
+        let dependencies = repository.resolve_controls(&[tweak]);
+
+        assert_eq!(
+            dependencies.resolved,
+            vec![PathBuf::from("com.example.hooks.deb")]
+        );
+        assert!(dependencies.unresolved.is_empty());
+    }
+
+    #[test]
+    fn resolve_installs_deepest_dependencies_first() {
+        let repository = repository(&[
+            "Package: libbase\n",
+            "Package: libmiddle\nDepends: libbase\n",
+            "Package: libtop\nDepends: libmiddle, libbase\n",
+        ]);
+        let tweak = DebControl::parse("Package: tweak\nDepends: libtop\n");
+
+        let dependencies = repository.resolve_controls(&[tweak]);
+
+        assert_eq!(
+            dependencies.resolved,
+            vec![
+                PathBuf::from("libbase.deb"),
+                PathBuf::from("libmiddle.deb"),
+                PathBuf::from("libtop.deb"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_orders_shared_dependencies_first() {
+        // The tweak names libshared first, libuser depends on it too
+        let repository = repository(&[
+            "Package: libuser\nDepends: libshared\n",
+            "Package: libshared\n",
+        ]);
+        let tweak = DebControl::parse("Package: tweak\nDepends: libshared, libuser\n");
+
+        let dependencies = repository.resolve_controls(&[tweak]);
+
+        assert_eq!(
+            dependencies.resolved,
+            vec![PathBuf::from("libshared.deb"), PathBuf::from("libuser.deb")]
+        );
+    }
+
+    #[test]
+    fn resolve_tolerates_cycles() {
+        let repository = repository(&[
+            "Package: liba\nDepends: libb\n",
+            "Package: libb\nDepends: liba\n",
+        ]);
+        let tweak = DebControl::parse("Package: tweak\nDepends: liba\n");
+
+        let dependencies = repository.resolve_controls(&[tweak]);
+
+        assert_eq!(
+            dependencies.resolved,
+            vec![PathBuf::from("libb.deb"), PathBuf::from("liba.deb")]
+        );
+    }
+
+    #[test]
+    fn resolve_skips_what_the_tweaks_bring_themselves() {
+        let repository = repository(&["Package: libfoo\n"]);
+        let tweaks = [
+            DebControl::parse("Package: tweak\nDepends: libfoo, libshared\n"),
+            DebControl::parse("Package: other\nProvides: libshared\nDepends: tweak\n"),
+        ];
+
+        let dependencies = repository.resolve_controls(&tweaks);
+
+        assert_eq!(dependencies.resolved, vec![PathBuf::from("libfoo.deb")]);
+        assert!(dependencies.unresolved.is_empty());
+    }
+
+    #[test]
+    fn resolve_reports_unresolved_dependencies() {
+        let repository = repository(&["Package: libfoo\nDepends: libmissing\n"]);
+        let tweak =
+            DebControl::parse("Package: tweak\nDepends: libfoo, liba | libb, libfoo, libzzz\n");
+
+        let dependencies = repository.resolve_controls(&[tweak]);
+
+        assert_eq!(dependencies.resolved, vec![PathBuf::from("libfoo.deb")]);
+        assert_eq!(
+            dependencies.unresolved,
+            vec![
+                "liba | libb".to_string(),
+                "libmissing".to_string(),
+                "libzzz".to_string()
+            ]
+        );
+    }
+}
@@ -13,8 +13,9 @@ use idevice::utils::installation;
 use idevice::{IdeviceService, RemoteXpcClient};
 use plume_core::MobileProvision;
 
-use crate::Error;
+use crate::compatibility::{CompatibilityReport, DeviceInfo};
 use crate::options::SignerAppReal;
+use crate::{Bundle, Error};
 use idevice::afc::opcode::AfcFopenMode;
 use idevice::house_arrest::HouseArrestClient;
 use idevice::usbmuxd::UsbmuxdConnection;
@@ -69,6 +70,30 @@ impl Device {
         Ok(get_dict_string!(values, "DeviceName"))
     }
 
+    pub async fn info(&self) -> Result<DeviceInfo, Error> {
+        let device = match &self.usbmuxd_device {
+            Some(dev) => dev,
+            None => return Err(Error::Other("Device is not connected via USB".to_string())),
+        };
+
+        let mut lockdown =
+            LockdownClient::connect(&device.to_provider(UsbmuxdAddr::default(), CONNECTION_LABEL))
+                .await?;
+        let values = lockdown.get_value(None, None).await?;
+
+        Ok(DeviceInfo {
+            product_version: get_dict_string!(values, "ProductVersion"),
+            product_type: get_dict_string!(values, "ProductType"),
+            cpu_architecture: get_dict_string!(values, "CPUArchitecture"),
+            device_class: get_dict_string!(values, "DeviceClass"),
+        })
+    }
+
+    /// Checks `bundle` against this device before handing it to installation_proxy.
+    pub async fn check_compatibility(&self, bundle: &Bundle) -> Result<CompatibilityReport, Error> {
+        CompatibilityReport::new(bundle, &self.info().await?)
+    }
+
     pub async fn installed_apps(&self) -> Result<Vec<SignerAppReal>, Error> {
         let device = match &self.usbmuxd_device {
             Some(dev) => dev,
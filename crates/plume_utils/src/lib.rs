@@ -1,27 +1,42 @@
 mod bundle;
 mod cgbi;
+mod compatibility;
+mod deb;
 mod device;
+mod manifest;
 mod options;
 mod package;
+mod profile;
+mod refresh;
 mod signer;
 mod tweak;
 
 use std::path::Path;
 
 pub use bundle::{Bundle, BundleType}; // Bundle helper
+pub use compatibility::{CompatibilityIssue, CompatibilityReport, DeviceInfo}; // Pre-install checks
+pub use deb::{DebControl, TweakDependencies, TweakRepository}; // Debian package helper
 pub use device::{Device, get_device_for_id, install_app_mac}; // Device helper
+pub use manifest::SignerManifest; // Signing recipe
 pub use options::{
     SignerApp, // Supported app types
     SignerAppReal,
+    SignerCompression, // Output compression
     SignerEmbedding,   // Embedding options
     SignerFeatures,    // Feature support options
     SignerInstallMode, // Installation mode
     SignerMode,        // Signing mode
     SignerOptions,     // Main
+    SignerRemovals,    // Nested bundles to drop
 };
 pub use package::Package; // Package helper
+pub use profile::{ProfileSummary, export_profile, import_profile}; // Moving to another machine
+pub use refresh::{ConnectedDevices, RefreshDaemon}; // Background refreshing
 pub use signer::Signer; // Signer
-pub use tweak::Tweak; // Tweak helper
+pub use tweak::{
+    InjectedDylib, LoadPathRewrite, Tweak, TweakFilter, TweakItemKind, TweakPlan, TweakPlanItem,
+    TweakPlans,
+}; // Tweak helper
 
 use thiserror::Error as ThisError;
 #[derive(Debug, ThisError)]
@@ -38,6 +53,28 @@ pub enum Error {
     TweakExtractionFailed(String),
     #[error("Unsupported file type: {0}")]
     UnsupportedFileType(String),
+    #[error("Not injected into the app: {0}")]
+    TweakNotInjected(String),
+    // Archive
+    #[error("Refusing to unpack {entry}: {reason}")]
+    UnsafeArchiveEntry {
+        entry: String,
+        reason: decompress::UnsafeEntryReason,
+    },
+    // Manifest
+    #[error("Invalid signing manifest: {0}")]
+    Manifest(String),
+    // Signer
+    #[error(
+        "Encrypted binaries found, decrypt the app before signing: {}",
+        .paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    EncryptedBinary { paths: Vec<std::path::PathBuf> },
+    // Profile
+    #[error("Wrong password, or the profile is damaged")]
+    ProfilePassword,
+    #[error("Not a Plume profile: {0}")]
+    ProfileInvalid(String),
 
     #[error("Zip error: {0}")]
     Zip(#[from] zip::result::ZipError),
@@ -59,6 +96,18 @@ pub enum Error {
     Image(#[from] image::ImageError),
 }
 
+impl Error {
+    /// Keeps refused archive entries typed, anything else is reported as is.
+    pub(crate) fn from_decompress(e: decompress::DecompressError) -> Self {
+        match e {
+            decompress::DecompressError::UnsafeEntry { entry, reason } => {
+                Self::UnsafeArchiveEntry { entry, reason }
+            }
+            e => Self::Other(e.to_string()),
+        }
+    }
+}
+
 pub trait PlistInfoTrait {
     fn get_name(&self) -> Option<String>;
     fn get_executable(&self) -> Option<String>;
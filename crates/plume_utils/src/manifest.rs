@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, SignerOptions};
+
+/// A signing recipe that can be replayed with `plumesign sign --manifest`.
+///
+/// Stored as TOML, or as JSON when the file has a `.json` extension.
+/// Relative paths are resolved against the directory containing the manifest.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignerManifest {
+    /// Package to sign (.ipa, .tipa or .app).
+    pub package: Option<PathBuf>,
+    /// Output path for the signed package.
+    pub output: Option<PathBuf>,
+    /// Device UDID to register and install to.
+    pub udid: Option<String>,
+    /// Email of the saved account to sign with, defaults to the selected account.
+    pub account: Option<String>,
+    /// Team ID to sign with, prompts when the account has several teams.
+    pub team_id: Option<String>,
+    /// Signer settings, including tweaks.
+    pub options: SignerOptions,
+}
+
+impl SignerManifest {
+    pub fn new(options: SignerOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+
+        let mut manifest: Self = if is_json(path) {
+            serde_json::from_str(&contents).map_err(|e| Error::Manifest(e.to_string()))?
+        } else {
+            toml::from_str(&contents).map_err(|e| Error::Manifest(e.to_string()))?
+        };
+
+        if let Some(base) = path.parent() {
+            manifest.resolve_paths(base);
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        let contents = if is_json(path) {
+            serde_json::to_string_pretty(self).map_err(|e| Error::Manifest(e.to_string()))?
+        } else {
+            toml::to_string_pretty(self).map_err(|e| Error::Manifest(e.to_string()))?
+        };
+
+        std::fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |p: &mut PathBuf| {
+            if p.is_relative() {
+                *p = base.join(&*p);
+            }
+        };
+
+        let options = &mut self.options;
+        [
+            self.package.as_mut(),
+            self.output.as_mut(),
+            options.custom_icon.as_mut(),
+            options.custom_entitlements.as_mut(),
+            options.tweak_repository.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(options.tweaks.iter_mut().flatten())
+        .for_each(resolve);
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
@@ -1,7 +1,10 @@
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
 
 /// Settings for the signer process.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SignerOptions {
     /// Custom app name override.
     pub custom_name: Option<String>,
@@ -16,16 +19,22 @@ pub struct SignerOptions {
     pub features: SignerFeatures,
     /// Embedding options.
     pub embedding: SignerEmbedding,
+    /// Nested bundles to leave out of the signed app.
+    pub removals: SignerRemovals,
     /// Mode.
     pub mode: SignerMode,
     /// Installation mode.
     pub install_mode: SignerInstallMode,
     /// Tweaks to apply before signing.
     pub tweaks: Option<Vec<PathBuf>>,
+    /// Directory of .deb files used to resolve tweak dependencies.
+    pub tweak_repository: Option<PathBuf>,
     /// App type.
     pub app: SignerApp,
     /// Apply autorefresh
     pub refresh: bool,
+    /// Compression of the written package.
+    pub compression: SignerCompression,
 }
 
 impl Default for SignerOptions {
@@ -38,11 +47,14 @@ impl Default for SignerOptions {
             custom_entitlements: None,
             features: SignerFeatures::default(),
             embedding: SignerEmbedding::default(),
+            removals: SignerRemovals::default(),
             mode: SignerMode::default(),
             install_mode: SignerInstallMode::default(),
             tweaks: None,
+            tweak_repository: None,
             app: SignerApp::Default,
             refresh: false,
+            compression: SignerCompression::default(),
         }
     }
 }
@@ -65,7 +77,8 @@ impl SignerOptions {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SignerFeatures {
     pub support_minimum_os_version: bool,
     pub support_file_sharing: bool,
@@ -78,12 +91,46 @@ pub struct SignerFeatures {
 }
 
 /// Embedding options.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SignerEmbedding {
     pub single_profile: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Nested bundles dropped before signing, each extension otherwise costs an App ID.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignerRemovals {
+    /// Every app extension in `PlugIns` and `Extensions`.
+    pub extensions: bool,
+    /// The `Watch` app and `com.apple.WatchPlaceholder`.
+    pub watch_app: bool,
+    /// Single bundles, relative to the app (i.e. `PlugIns/Widgets.appex`).
+    pub bundles: Vec<PathBuf>,
+}
+
+impl SignerRemovals {
+    pub fn is_empty(&self) -> bool {
+        !self.extensions && !self.watch_app && self.bundles.is_empty()
+    }
+
+    /// Whether the removable bundle at `path`, relative to the app, gets dropped.
+    pub fn matches(&self, path: &Path) -> bool {
+        let group = match path.components().next() {
+            Some(Component::Normal(dir)) => match dir.to_str() {
+                Some("PlugIns" | "Extensions") => self.extensions,
+                Some("Watch" | "com.apple.WatchPlaceholder") => self.watch_app,
+                _ => false,
+            },
+            _ => false,
+        };
+
+        group || self.bundles.iter().any(|bundle| bundle == path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SignerInstallMode {
     Install,
     Export,
@@ -104,7 +151,43 @@ impl std::fmt::Display for SignerInstallMode {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Compression of written packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerCompression {
+    /// Entries are stored as is, the quickest for local installs.
+    Store,
+    /// Deflate at a level from 1 to 9.
+    Deflate(u8),
+}
+
+impl Default for SignerCompression {
+    fn default() -> Self {
+        SignerCompression::Deflate(6)
+    }
+}
+
+impl SignerCompression {
+    /// Maps a `zip`-style level, where 0 means store.
+    pub fn from_level(level: u8) -> Self {
+        match level {
+            0 => SignerCompression::Store,
+            level => SignerCompression::Deflate(level.min(9)),
+        }
+    }
+}
+
+impl std::fmt::Display for SignerCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerCompression::Store => write!(f, "Store"),
+            SignerCompression::Deflate(level) => write!(f, "Deflate ({})", level),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SignerMode {
     Pem,
     Adhoc,
@@ -152,7 +235,8 @@ impl SignerAppReal {
 }
 
 /// Supported app types.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SignerApp {
     Default,
     Antrag,
@@ -1,89 +1,230 @@
 use super::{Bundle, PlistInfoTrait};
-use crate::{Error, SignerApp, SignerOptions, cgbi};
+use crate::{Error, SignerApp, SignerCompression, SignerOptions, cgbi};
 use plist::Dictionary;
-use std::path::PathBuf;
-use std::{env, fs, io::Read};
+use plume_core::MachO;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{
+    env, fs,
+    io::{self, Read, Seek},
+};
 use uuid::Uuid;
-use zip::ZipArchive;
-use zip::write::FileOptions;
+use zip::result::{ZipError, ZipResult};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive};
+
+/// Extensions of what `Package::new` opens, besides any other zip with an app in it.
+const PACKAGE_EXTENSIONS: &[&str] = &["ipa", "tipa", "zip", "xcarchive"];
+
+/// Where the app of a package comes from.
+#[derive(Debug, Clone)]
+enum PackageSource {
+    /// A zip and the folder holding its `.app`: `Payload/` for an IPA, nothing for a
+    /// zipped `.app` and `Products/Applications/` for a zipped Xcode archive.
+    Archive { app_parent: String },
+    /// The app inside an Xcode archive folder.
+    Xcarchive { app: PathBuf },
+}
 
 #[derive(Debug, Clone)]
 pub struct Package {
     package_file: PathBuf,
+    source: PackageSource,
     stage_dir: PathBuf,
     stage_payload_dir: PathBuf,
     info_plist_dictionary: Dictionary,
     archive_entries: Vec<String>,
+    encrypted_executables: Vec<String>,
     pub app_icon_data: Option<Vec<u8>>,
 }
 
 impl Package {
+    /// Opens an `.ipa`, `.tipa`, a zipped `.app` or an `.xcarchive`, zipped or not.
     pub fn new(package_file: PathBuf) -> Result<Self, Error> {
         let stage_dir = env::temp_dir().join(format!(
             "plume_stage_{:08}",
             Uuid::new_v4().to_string().to_uppercase()
         ));
-        let out_package_file = stage_dir.join("stage.ipa");
 
         fs::create_dir_all(&stage_dir).ok();
-        fs::copy(&package_file, &out_package_file)?;
 
-        let file = fs::File::open(&out_package_file)?;
+        if package_file.is_dir() {
+            return Self::new_from_xcarchive(package_file, stage_dir);
+        }
+
+        // The source package is only ever read, so it is used in place
+        let file = fs::File::open(&package_file)?;
         let mut archive = ZipArchive::new(file)?;
         let archive_entries = (0..archive.len())
             .filter_map(|i| archive.by_index(i).ok().map(|f| f.name().to_string()))
             .collect::<Vec<_>>();
 
-        let info_plist_dictionary =
-            Self::get_info_plist_from_archive(&out_package_file, &archive_entries)?;
+        let info_plist_path =
+            Self::find_app_info_plist(&archive_entries).ok_or(Error::PackageInfoPlistMissing)?;
+        let app_root = info_plist_path.trim_end_matches("Info.plist");
+        let app_parent = app_root
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .map_or(String::new(), |(parent, _)| format!("{parent}/"));
+
+        let info_plist_dictionary: Dictionary =
+            plist::from_bytes(&read_entry(&mut archive, info_plist_path)?)?;
+
+        let app_icon_data = Self::find_icon(&info_plist_dictionary, |name| {
+            read_entry(&mut archive, &format!("{app_root}{name}")).ok()
+        });
 
-        let app_icon_data = Self::extract_icon_from_archive(
-            &out_package_file,
+        let encrypted_executables = Self::find_encrypted_executables(
+            &mut archive,
             &archive_entries,
+            &app_parent,
             &info_plist_dictionary,
         );
 
         Ok(Self {
-            package_file: out_package_file,
+            package_file,
+            source: PackageSource::Archive { app_parent },
             stage_dir: stage_dir.clone(),
             stage_payload_dir: stage_dir.join("Payload"),
             info_plist_dictionary,
             archive_entries,
+            encrypted_executables,
             app_icon_data,
         })
     }
 
+    fn new_from_xcarchive(package_file: PathBuf, stage_dir: PathBuf) -> Result<Self, Error> {
+        let app = fs::read_dir(package_file.join("Products").join("Applications"))
+            .map_err(|_| Error::PackageInfoPlistMissing)?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .find(|p| p.is_dir() && p.extension().and_then(|e| e.to_str()) == Some("app"))
+            .ok_or(Error::PackageInfoPlistMissing)?;
+
+        let info_plist_dictionary: Dictionary =
+            plist::from_file(app.join("Info.plist")).map_err(|_| Error::PackageInfoPlistMissing)?;
+
+        let app_icon_data =
+            Self::find_icon(&info_plist_dictionary, |name| fs::read(app.join(name)).ok());
+
+        // Named as they will be staged, `Payload/App.app/...`
+        let apps_dir = app.parent().unwrap_or(&app);
+        let encrypted_executables = Bundle::new(&app)?
+            .encrypted_executables()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|path| path.strip_prefix(apps_dir).ok())
+            .map(|path| format!("Payload/{}", archive_name(path)))
+            .collect();
+
+        let mut entries = Vec::new();
+        collect_staged(&app, apps_dir, &mut entries)?;
+        let archive_entries = entries
+            .iter()
+            .map(|entry| format!("Payload/{}", entry.name()))
+            .collect();
+
+        Ok(Self {
+            package_file,
+            source: PackageSource::Xcarchive { app },
+            stage_payload_dir: stage_dir.join("Payload"),
+            stage_dir,
+            info_plist_dictionary,
+            archive_entries,
+            encrypted_executables,
+            app_icon_data,
+        })
+    }
+
+    /// Whether `path` has the extension of a package rather than a bundle to sign in place.
+    pub fn is_supported_path(path: &Path) -> bool {
+        path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+            PACKAGE_EXTENSIONS
+                .iter()
+                .any(|ext| e.eq_ignore_ascii_case(ext))
+        })
+    }
+
     pub fn package_file(&self) -> &PathBuf {
         &self.package_file
     }
 
-    fn get_info_plist_from_archive(
-        archive_path: &PathBuf,
+    /// Staged paths (`Payload/...`) of executables that are still FairPlay encrypted.
+    pub fn encrypted_executables(&self) -> &[String] {
+        &self.encrypted_executables
+    }
+
+    /// The `Info.plist` of the app, in any of the layouts a zipped package can have.
+    fn find_app_info_plist(archive_entries: &[String]) -> Option<&String> {
+        let is_app_parent = |parent: &str| match parent.strip_suffix("Products/Applications") {
+            Some(xcarchive) => {
+                xcarchive.is_empty()
+                    || xcarchive
+                        .strip_suffix(".xcarchive/")
+                        .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+            }
+            None => parent.is_empty() || parent == "Payload",
+        };
+
+        archive_entries.iter().find(|entry| {
+            let Some(app_root) = entry.strip_suffix("/Info.plist") else {
+                return false;
+            };
+            let (parent, app) = app_root.rsplit_once('/').unwrap_or(("", app_root));
+            app.ends_with(".app") && is_app_parent(parent)
+        })
+    }
+
+    fn find_encrypted_executables<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
         archive_entries: &[String],
-    ) -> Result<Dictionary, Error> {
-        let file = fs::File::open(archive_path)?;
-        let mut archive = ZipArchive::new(file)?;
+        app_parent: &str,
+        plist: &Dictionary,
+    ) -> Vec<String> {
+        let main_executable = plist.get("CFBundleExecutable").and_then(|v| v.as_string());
+
+        // Bundle executables are named after their bundle, e.g. `App.app/PlugIns/Ext.appex/Ext`.
+        let is_executable = |entry: &str| {
+            let Some((parent, name)) = entry.rsplit_once('/') else {
+                return false;
+            };
+            if name.is_empty()
+                || !parent
+                    .split('/')
+                    .next()
+                    .is_some_and(|app| app.ends_with(".app"))
+            {
+                return false;
+            }
+            if name.ends_with(".dylib") {
+                return true;
+            }
 
-        let info_plist_path = archive_entries
-            .iter()
-            .find(|entry| {
-                entry.starts_with("Payload/")
-                    && entry.ends_with("/Info.plist")
-                    && entry.matches('/').count() == 2
-            })
-            .ok_or(Error::PackageInfoPlistMissing)?;
+            let parent_name = parent.rsplit('/').next().unwrap_or(parent);
 
-        let mut plist_file = archive.by_name(info_plist_path)?;
-        let mut plist_data = Vec::new();
-        plist_file.read_to_end(&mut plist_data)?;
+            (!parent.contains('/') && Some(name) == main_executable)
+                || [".app", ".appex", ".framework"]
+                    .iter()
+                    .any(|ext| parent_name.strip_suffix(ext) == Some(name))
+        };
 
-        Ok(plist::from_bytes(&plist_data)?)
+        archive_entries
+            .iter()
+            .filter_map(|entry| entry.strip_prefix(app_parent))
+            .filter(|entry| is_executable(entry))
+            .filter(|entry| {
+                archive
+                    .by_name(&format!("{app_parent}{entry}"))
+                    .is_ok_and(|file| MachO::is_reader_encrypted(file).unwrap_or(false))
+            })
+            .map(|entry| format!("Payload/{entry}"))
+            .collect()
     }
 
-    fn extract_icon_from_archive(
-        archive_path: &PathBuf,
-        archive_entries: &[String],
+    /// Reads the app icon through `read`, which takes a path inside the app.
+    fn find_icon(
         plist: &Dictionary,
+        mut read: impl FnMut(&str) -> Option<Vec<u8>>,
     ) -> Option<Vec<u8>> {
         // Collects all candidate icon base names from the plist, in order of preference.
         // CFBundleIcons (iPhone) takes priority, fall back to CFBundleIcons~ipad, then
@@ -133,28 +274,12 @@ impl Package {
             return None;
         }
 
-        let app_prefix = archive_entries
-            .iter()
-            .find(|e| {
-                e.starts_with("Payload/")
-                    && e.ends_with("/Info.plist")
-                    && e.matches('/').count() == 2
-            })?
-            .trim_end_matches("/Info.plist");
-
-        let file = fs::File::open(archive_path).ok()?;
-        let mut archive = ZipArchive::new(file).ok()?;
-
         let suffixes = ["@3x.png", "@2x.png", "@1x.png", ".png"];
 
         for name in &icon_names {
             for suffix in &suffixes {
-                let candidate = format!("{app_prefix}/{name}{suffix}");
-                if let Ok(mut entry) = archive.by_name(&candidate) {
-                    let mut data = Vec::new();
-                    if entry.read_to_end(&mut data).is_ok() && !data.is_empty() {
-                        return Some(cgbi::normalize(data));
-                    }
+                if let Some(data) = read(&format!("{name}{suffix}")).filter(|d| !d.is_empty()) {
+                    return Some(cgbi::normalize(data));
                 }
             }
         }
@@ -162,10 +287,29 @@ impl Package {
         None
     }
 
+    /// Unpacks the app into the stage.
+    ///
+    /// The whole app is unpacked, not only what the signer rewrites: code signing hashes
+    /// every resource into `_CodeSignature/CodeResources` and reads them from the bundle
+    /// directory. Unchanged entries are still copied back raw when archiving.
+    ///
+    /// Whatever an earlier call staged is removed first, so every call starts from the
+    /// package as it is, without tweaks or removals of a previous attempt.
     pub fn get_package_bundle(&self) -> Result<Bundle, Error> {
-        let file = fs::File::open(&self.package_file)?;
-        let mut archive = ZipArchive::new(file)?;
-        archive.extract(&self.stage_dir)?;
+        if self.stage_dir.exists() {
+            fs::remove_dir_all(&self.stage_dir)?;
+        }
+        fs::create_dir_all(&self.stage_dir)?;
+
+        match &self.source {
+            PackageSource::Xcarchive { app } => {
+                let staged_app = self
+                    .stage_payload_dir
+                    .join(app.file_name().unwrap_or_default());
+                stage_dir_tree(app, &staged_app, &self.stage_dir)?;
+            }
+            PackageSource::Archive { app_parent } => self.stage_archive(app_parent)?,
+        }
 
         let app_dir = fs::read_dir(&self.stage_payload_dir)?
             .filter_map(Result::ok)
@@ -176,51 +320,227 @@ impl Package {
         Ok(Bundle::new(app_dir)?)
     }
 
-    pub fn get_archive_based_on_path(&self, path: &PathBuf) -> Result<PathBuf, Error> {
+    fn stage_archive(&self, app_parent: &str) -> Result<(), Error> {
+        let file = fs::File::open(&self.package_file)?;
+        let mut archive = ZipArchive::new(file)?;
+        Self::check_archive_entries(&mut archive, app_parent)?;
+
+        if app_parent == "Payload/" {
+            archive.extract(&self.stage_dir)?;
+        } else {
+            // Other layouts are unpacked aside, then their app is moved into `Payload`
+            let unpacked = self.stage_dir.join("source");
+            archive.extract(&unpacked)?;
+            fs::create_dir_all(&self.stage_payload_dir)?;
+
+            let apps = fs::read_dir(unpacked.join(app_parent))?
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|p| p.is_dir() && p.extension().and_then(|e| e.to_str()) == Some("app"));
+            for app in apps {
+                let staged = self
+                    .stage_payload_dir
+                    .join(app.file_name().unwrap_or_default());
+                fs::rename(&app, staged)?;
+            }
+
+            fs::remove_dir_all(&unpacked).ok();
+        }
+
+        Ok(())
+    }
+
+    /// Refuses entries that would land outside the stage, `extract` would quietly skip or rewrite them.
+    /// Links of the app are checked again from where they end up in `Payload`.
+    fn check_archive_entries<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        app_parent: &str,
+    ) -> Result<(), Error> {
+        use decompress::sanitize::{check_entry_path, check_link_target};
+
+        let mut entries = Vec::with_capacity(archive.len());
+        let mut links = Vec::new();
+
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let entry = PathBuf::from(file.name());
+            check_entry_path(&entry).map_err(Error::from_decompress)?;
+
+            if file.is_symlink() {
+                let mut target = String::new();
+                file.read_to_string(&mut target)?;
+                check_link_target(&entry, Path::new(&target)).map_err(Error::from_decompress)?;
+                if let Some(rest) = file.name().strip_prefix(app_parent) {
+                    check_link_target(&Path::new("Payload").join(rest), Path::new(&target))
+                        .map_err(Error::from_decompress)?;
+                }
+                links.push(entry.clone());
+            }
+
+            entries.push(entry);
+        }
+
+        // Link targets are checked from their own path, so nothing may be unpacked through a link.
+        for entry in &entries {
+            if links
+                .iter()
+                .any(|link| entry != link && entry.starts_with(link))
+            {
+                return Err(Error::UnsafeArchiveEntry {
+                    entry: entry.display().to_string(),
+                    reason: decompress::UnsafeEntryReason::ResolvesOutside,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_archive_based_on_path(
+        &self,
+        path: &PathBuf,
+        compression: SignerCompression,
+    ) -> Result<PathBuf, Error> {
         if path.is_dir() {
-            self.clone().archive_package_bundle()
+            self.clone().archive_package_bundle(compression)
         } else {
             Ok(self.package_file.clone())
         }
     }
 
-    fn archive_package_bundle(self) -> Result<PathBuf, Error> {
+    /// Writes the staged bundle to a new package, byte for byte the same for the same bundle.
+    /// Files the signer left alone keep their compressed bytes from a source IPA when it used
+    /// the same method, everything else is compressed in parallel.
+    fn archive_package_bundle(self, compression: SignerCompression) -> Result<PathBuf, Error> {
         let zip_file_path = self.stage_dir.join("resigned.ipa");
-        let file = fs::File::create(&zip_file_path)?;
-        let mut zip = zip::ZipWriter::new(file);
-        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
-
-        let payload_dir = self.stage_payload_dir;
-
-        fn add_dir_to_zip(
-            zip: &mut zip::ZipWriter<fs::File>,
-            path: &PathBuf,
-            prefix: &PathBuf,
-            options: &FileOptions<'_, zip::write::ExtendedFileOptions>,
-        ) -> Result<(), Error> {
-            for entry in fs::read_dir(path)? {
-                let entry = entry?;
-                let entry_path = entry.path();
-                let name = entry_path
-                    .strip_prefix(prefix)
-                    .map_err(|_| Error::PackageInfoPlistMissing)?
-                    .to_string_lossy()
-                    .to_string();
-
-                if entry_path.is_file() {
-                    zip.start_file(&name, options.clone())?;
-                    let mut f = fs::File::open(&entry_path)?;
-                    std::io::copy(&mut f, zip)?;
-                } else if entry_path.is_dir() {
-                    zip.add_directory(&name, options.clone())?;
-                    add_dir_to_zip(zip, &entry_path, prefix, options)?;
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_file_path)?);
+
+        let (method, level) = match compression {
+            SignerCompression::Store => (CompressionMethod::Stored, None),
+            SignerCompression::Deflate(level) => {
+                (CompressionMethod::Deflated, Some(level.clamp(1, 9) as i64))
+            }
+        };
+        let plain = SimpleFileOptions::default().last_modified_time(DateTime::default());
+        let options = plain.compression_method(method).compression_level(level);
+
+        let mut entries = Vec::new();
+        collect_staged(&self.stage_payload_dir, &self.stage_dir, &mut entries)?;
+        let files = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                StagedEntry::File(file) => Some(file),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Compressed bytes are copied under their own name, so only IPAs can lend theirs
+        let mut source = match &self.source {
+            PackageSource::Archive { app_parent } if app_parent == "Payload/" => {
+                Some(ZipArchive::new(fs::File::open(&self.package_file)?)?)
+            }
+            _ => None,
+        };
+        let mut source_entries = HashMap::new();
+        if let Some(source) = &mut source {
+            for index in 0..source.len() {
+                let entry = source.by_index_raw(index)?;
+                if entry.is_file() {
+                    source_entries.insert(
+                        entry.name().to_string(),
+                        SourceEntry {
+                            index,
+                            size: entry.size(),
+                            crc32: entry.crc32(),
+                            method: entry.compression(),
+                            mode: entry.unix_mode(),
+                        },
+                    );
                 }
             }
+        }
+
+        let reused = in_parallel(&files, |file| {
+            let Some(entry) = source_entries.get(&file.name) else {
+                return Ok(None);
+            };
+            let unchanged = entry.method == method
+                && entry.size == file.len
+                && file_crc32(&file.path)? == entry.crc32;
+            Ok(unchanged.then_some(entry.index))
+        })?;
+        let reused = files
+            .iter()
+            .zip(reused)
+            .filter_map(|(file, index)| Some((file.name.as_str(), index?)))
+            .collect::<HashMap<_, _>>();
+
+        // The rest is compressed into one part per worker, then copied over in order
+        let changed = files
+            .iter()
+            .copied()
+            .filter(|file| !reused.contains_key(file.name.as_str()))
+            .collect::<Vec<_>>();
+        let parts = balance(&self.stage_dir, changed);
+        in_parallel(&parts, |(part, files)| {
+            let mut zip = zip::ZipWriter::new(fs::File::create(part)?);
+            for file in files {
+                let options = options.large_file(file.len > u32::MAX as u64);
+                zip.start_file(file.name.as_str(), options)?;
+                io::copy(&mut fs::File::open(&file.path)?, &mut zip)?;
+            }
+            zip.finish()?;
             Ok(())
+        })?;
+
+        let mut part_archives = parts
+            .iter()
+            .map(|(part, _)| ZipArchive::new(fs::File::open(part)?))
+            .collect::<ZipResult<Vec<_>>>()?;
+        let compressed = parts
+            .iter()
+            .enumerate()
+            .flat_map(|(part, (_, files))| files.iter().map(move |f| (f.name.as_str(), part)))
+            .collect::<HashMap<_, _>>();
+
+        for entry in &entries {
+            match entry {
+                StagedEntry::Directory(name) => {
+                    zip.add_directory(name.as_str(), plain.unix_permissions(0o755))?
+                }
+                StagedEntry::Symlink { name, target } => {
+                    zip.add_symlink(name.as_str(), target.as_str(), plain)?
+                }
+                StagedEntry::File(file) => {
+                    let name = file.name.as_str();
+                    let executable = file.executable
+                        || source_entries
+                            .get(name)
+                            .and_then(|entry| entry.mode)
+                            .is_some_and(|mode| mode & 0o111 != 0);
+
+                    let raw = match reused.get(name) {
+                        Some(&index) => source
+                            .as_mut()
+                            .ok_or(ZipError::FileNotFound)?
+                            .by_index_raw(index)?,
+                        None => {
+                            let part = &mut part_archives[compressed[name]];
+                            let index = part.index_for_name(name).ok_or(ZipError::FileNotFound)?;
+                            part.by_index_raw(index)?
+                        }
+                    };
+                    let mode = if executable { 0o755 } else { 0o644 };
+                    zip.raw_copy_file_touch(raw, DateTime::default(), Some(mode))?;
+                }
+            }
         }
 
-        add_dir_to_zip(&mut zip, &payload_dir, &self.stage_dir, &options)?;
         zip.finish()?;
+        drop(part_archives);
+        for (part, _) in &parts {
+            fs::remove_file(part).ok();
+        }
 
         Ok(zip_file_path)
     }
@@ -230,6 +550,213 @@ impl Package {
     }
 }
 
+/// A file of the staged bundle and its name in the archive.
+pub(crate) struct StagedFile {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    len: u64,
+    pub(crate) executable: bool,
+}
+
+pub(crate) enum StagedEntry {
+    Directory(String),
+    Symlink { name: String, target: String },
+    File(StagedFile),
+}
+
+impl StagedEntry {
+    fn name(&self) -> &str {
+        match self {
+            StagedEntry::Directory(name) | StagedEntry::Symlink { name, .. } => name,
+            StagedEntry::File(file) => &file.name,
+        }
+    }
+}
+
+/// What is needed of a source entry to reuse its compressed bytes.
+struct SourceEntry {
+    index: usize,
+    size: u64,
+    crc32: u32,
+    method: CompressionMethod,
+    mode: Option<u32>,
+}
+
+/// Collects the staged bundle in path order, each directory before its contents.
+pub(crate) fn collect_staged(
+    dir: &Path,
+    prefix: &Path,
+    entries: &mut Vec<StagedEntry>,
+) -> Result<(), Error> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    for entry_path in paths {
+        // Archive names always use `/`, whatever the platform
+        let name = archive_name(
+            entry_path
+                .strip_prefix(prefix)
+                .map_err(|_| Error::PackageInfoPlistMissing)?,
+        );
+
+        let metadata = fs::symlink_metadata(&entry_path)?;
+        if metadata.file_type().is_symlink() {
+            let target = archive_name(&fs::read_link(&entry_path)?);
+            entries.push(StagedEntry::Symlink { name, target });
+        } else if metadata.is_file() {
+            entries.push(StagedEntry::File(StagedFile {
+                name,
+                len: metadata.len(),
+                executable: is_executable(&metadata),
+                path: entry_path,
+            }));
+        } else if metadata.is_dir() {
+            entries.push(StagedEntry::Directory(name));
+            collect_staged(&entry_path, prefix, entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies an app into the stage, holding its links to the same rules as archive entries.
+fn stage_dir_tree(src: &Path, dst: &Path, stage_dir: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+
+        if file_type.is_symlink() {
+            let target = fs::read_link(&src_path)?;
+            let link = dst_path
+                .strip_prefix(stage_dir)
+                .map_err(|_| Error::PackageInfoPlistMissing)?;
+            decompress::sanitize::check_link_target(link, &target)
+                .map_err(Error::from_decompress)?;
+
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&target, &dst_path)?;
+        } else if file_type.is_dir() {
+            stage_dir_tree(&src_path, &dst_path, stage_dir)?;
+        } else if file_type.is_file() {
+            fs::copy(&src_path, &dst_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    archive.by_name(name)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+pub(crate) fn archive_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+// Modes are lost when unpacking elsewhere, the source entry's mode is used instead
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+fn worker_count() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Spreads files over the workers, largest first onto the least loaded one.
+fn balance<'a>(
+    stage_dir: &Path,
+    mut files: Vec<&'a StagedFile>,
+) -> Vec<(PathBuf, Vec<&'a StagedFile>)> {
+    files.sort_by_key(|file| std::cmp::Reverse(file.len));
+
+    let workers = worker_count().min(files.len());
+    let mut parts = (0..workers)
+        .map(|index| {
+            (
+                stage_dir.join(format!("part_{}.zip", index)),
+                Vec::new(),
+                0u64,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for file in files {
+        if let Some(part) = parts.iter_mut().min_by_key(|(_, _, len)| *len) {
+            part.2 += file.len;
+            part.1.push(file);
+        }
+    }
+
+    parts
+        .into_iter()
+        .map(|(path, files, _)| (path, files))
+        .collect()
+}
+
+/// Maps `items` on every core, keeping their order.
+fn in_parallel<T: Sync, R: Send>(
+    items: &[T],
+    f: impl Fn(&T) -> ZipResult<R> + Sync,
+) -> ZipResult<Vec<R>> {
+    let workers = worker_count().min(items.len()).max(1);
+
+    let results = std::thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|worker| {
+                let f = &f;
+                scope.spawn(move || {
+                    items
+                        .iter()
+                        .skip(worker)
+                        .step_by(workers)
+                        .map(f)
+                        .collect::<ZipResult<Vec<_>>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("archive worker panicked"))
+            .collect::<ZipResult<Vec<_>>>()
+    })?;
+
+    let mut results = results.into_iter().map(Vec::into_iter).collect::<Vec<_>>();
+    Ok((0..items.len())
+        .filter_map(|index| results[index % workers].next())
+        .collect())
+}
+
+fn file_crc32(path: &Path) -> io::Result<u32> {
+    let mut file = fs::File::open(path)?;
+    let mut crc = flate2::Crc::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok(crc.sum());
+        }
+        crc.update(&buf[..read]);
+    }
+}
+
 // TODO: make bundle and package share a common trait for plist info access
 macro_rules! get_plist_dict_value {
     ($self:ident, $key:expr) => {{
@@ -0,0 +1,292 @@
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use decompress::sanitize::{check_entry_path, check_link_target};
+use plume_core::CertificateIdentity;
+use plume_core::secrets::{self, SecretStore};
+use plume_store::AccountStore;
+use serde::Serialize;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::Error;
+use crate::package::{StagedEntry, archive_name, collect_staged};
+
+const ACCOUNTS_FILE: &str = "accounts.json";
+const KEYS_DIR: &str = "keys";
+const KEY_FILE: &str = "key.pem";
+const REFRESH_DIR: &str = "refresh_store";
+// Saved copies are unpacked here first, a failed import leaves the refresh store alone
+const REFRESH_STAGING_DIR: &str = "refresh_store.import";
+
+/// What went into, or came out of, a profile archive.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ProfileSummary {
+    pub accounts: usize,
+    pub keys: usize,
+    pub devices: usize,
+    pub apps: usize,
+}
+
+/// Writes accounts, signing keys, the refresh list and the saved app copies under
+/// `data_dir` to one archive sealed with `password`.
+///
+/// Keys are stored unsealed inside the archive, the machine secret does not travel
+/// with it, so the importing machine seals them again with its own.
+pub fn export_profile(
+    data_dir: &Path,
+    password: &str,
+    output: &Path,
+) -> Result<ProfileSummary, Error> {
+    let store = AccountStore::load_sync(&Some(data_dir.join(ACCOUNTS_FILE)))?;
+    let secrets = secrets::default_store(data_dir);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut summary = ProfileSummary {
+        accounts: store.accounts().len(),
+        devices: store.refreshes().len(),
+        ..Default::default()
+    };
+
+    zip.start_file(ACCOUNTS_FILE, options)?;
+    zip.write_all(&store.to_portable()?)?;
+
+    let keys_dir = data_dir.join(KEYS_DIR);
+    if keys_dir.is_dir() {
+        for entry in fs::read_dir(&keys_dir)? {
+            let entry = entry?;
+            let key_path = entry.path().join(KEY_FILE);
+            if !key_path.is_file() {
+                continue;
+            }
+
+            let key = secrets::open(&secrets.key()?, &fs::read(&key_path)?)?;
+            let team_id = entry.file_name();
+            zip.start_file(
+                format!("{KEYS_DIR}/{}/{KEY_FILE}", team_id.to_string_lossy()),
+                options,
+            )?;
+            zip.write_all(&key)?;
+            summary.keys += 1;
+        }
+    }
+
+    for app in store.refreshes().values().flat_map(|device| &device.apps) {
+        let (Some(parent), Some(name)) = (app.path.parent(), app.path.file_name()) else {
+            continue;
+        };
+        if !app.path.is_dir() {
+            log::warn!("Saved copy {:?} is missing, leaving it out", app.path);
+            continue;
+        }
+
+        // Copies are filed by name, the importing side moves them into its own refresh store
+        zip.add_directory(
+            format!("{REFRESH_DIR}/{}", archive_name(Path::new(name))),
+            options.unix_permissions(0o755),
+        )?;
+
+        let mut entries = Vec::new();
+        collect_staged(&app.path, parent, &mut entries)?;
+        for entry in entries {
+            match entry {
+                StagedEntry::Directory(entry_name) => zip.add_directory(
+                    format!("{REFRESH_DIR}/{entry_name}"),
+                    options.unix_permissions(0o755),
+                )?,
+                StagedEntry::Symlink {
+                    name: entry_name,
+                    target,
+                } => zip.add_symlink(format!("{REFRESH_DIR}/{entry_name}"), target, options)?,
+                StagedEntry::File(file) => {
+                    let mode = if file.executable { 0o755 } else { 0o644 };
+                    zip.start_file(
+                        format!("{REFRESH_DIR}/{}", file.name),
+                        options.unix_permissions(mode),
+                    )?;
+                    std::io::copy(&mut fs::File::open(&file.path)?, &mut zip)?;
+                }
+            }
+        }
+        summary.apps += 1;
+    }
+
+    let archive = zip.finish()?.into_inner();
+    fs::write(output, secrets::seal_with_passphrase(password, &archive)?)?;
+    Ok(summary)
+}
+
+/// Unpacks a profile from [`export_profile`] into `data_dir` and merges it with what is there.
+///
+/// Accounts are merged by email and devices by UDID, see [`AccountStore::merge`]. A key
+/// for a team that already has a different one replaces it, the old key is kept as
+/// `key.pem.bak`, so the certificate from the profile keeps working without a reset.
+pub fn import_profile(
+    data_dir: &Path,
+    password: &str,
+    input: &Path,
+) -> Result<ProfileSummary, Error> {
+    let archive =
+        secrets::open_with_passphrase(password, &fs::read(input)?).map_err(|e| match e {
+            plume_core::Error::AesGcm(_) => Error::ProfilePassword,
+            plume_core::Error::SecretStore(reason) => Error::ProfileInvalid(reason),
+            e => e.into(),
+        })?;
+    let mut zip = ZipArchive::new(Cursor::new(archive))?;
+
+    let secrets = secrets::default_store(data_dir);
+    let staging_dir = data_dir.join(REFRESH_STAGING_DIR);
+    remove_path(&staging_dir)?;
+
+    let mut imported = None;
+    let mut summary = ProfileSummary::default();
+    let mut links = Vec::new();
+
+    let mut link_names = Vec::new();
+    for index in 0..zip.len() {
+        let entry = zip.by_index(index)?;
+        if entry.is_symlink() {
+            link_names.push(PathBuf::from(entry.name()));
+        }
+    }
+
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        let name = PathBuf::from(entry.name());
+        check_entry_path(&name).map_err(Error::from_decompress)?;
+        if link_names
+            .iter()
+            .any(|link| &name != link && name.starts_with(link))
+        {
+            return Err(Error::UnsafeArchiveEntry {
+                entry: name.display().to_string(),
+                reason: decompress::UnsafeEntryReason::ResolvesOutside,
+            });
+        }
+
+        if name == Path::new(ACCOUNTS_FILE) {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            imported = Some(AccountStore::from_portable(&contents)?);
+        } else if name.starts_with(KEYS_DIR)
+            && name.ends_with(KEY_FILE)
+            && name.components().count() == 3
+        {
+            let mut key = Vec::new();
+            entry.read_to_end(&mut key)?;
+            import_key(&data_dir.join(&name), secrets.as_ref(), &key)?;
+            summary.keys += 1;
+        } else if let Ok(relative) = name.strip_prefix(REFRESH_DIR) {
+            let dest = staging_dir.join(relative);
+            if entry.is_symlink() {
+                let mut target = String::new();
+                entry.read_to_string(&mut target)?;
+                check_link_target(&name, Path::new(&target)).map_err(Error::from_decompress)?;
+                links.push((dest, target));
+            } else if entry.is_dir() {
+                fs::create_dir_all(&dest)?;
+            } else {
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                std::io::copy(&mut entry, &mut fs::File::create(&dest)?)?;
+                set_mode(&dest, entry.unix_mode())?;
+            }
+        }
+    }
+
+    // Links go last, nothing in the archive is unpacked through one
+    for (dest, target) in links {
+        create_link(&dest, &target)?;
+    }
+
+    // Each copy replaces the one with the same name, others on this machine stay
+    let refresh_dir = data_dir.join(REFRESH_DIR);
+    if staging_dir.is_dir() {
+        fs::create_dir_all(&refresh_dir)?;
+        for entry in fs::read_dir(&staging_dir)? {
+            let entry = entry?;
+            let dest = refresh_dir.join(entry.file_name());
+            remove_path(&dest)?;
+            fs::rename(entry.path(), &dest)?;
+        }
+        fs::remove_dir_all(&staging_dir)?;
+    }
+
+    let mut imported =
+        imported.ok_or_else(|| Error::ProfileInvalid(format!("{ACCOUNTS_FILE} is missing")))?;
+
+    // Saved copies now live in this machine's refresh store
+    for mut device in imported.refreshes().values().cloned().collect::<Vec<_>>() {
+        for app in &mut device.apps {
+            if let Some(name) = app.path.file_name() {
+                app.path = refresh_dir.join(name);
+            }
+        }
+        device.apps.retain(|app| app.path.is_dir());
+        summary.apps += device.apps.len();
+        imported.add_or_update_refresh_device(device);
+    }
+    summary.accounts = imported.accounts().len();
+    summary.devices = imported.refreshes().len();
+
+    AccountStore::update_sync(&data_dir.join(ACCOUNTS_FILE), |store| {
+        store.merge(imported);
+        Ok(())
+    })?;
+
+    Ok(summary)
+}
+
+fn import_key(path: &Path, secrets: &dyn SecretStore, key_pem: &[u8]) -> Result<(), Error> {
+    let key = secrets.key()?;
+    if path.exists() {
+        if secrets::open(&key, &fs::read(path)?).ok().as_deref() == Some(key_pem) {
+            return Ok(());
+        }
+        fs::copy(path, path.with_extension("pem.bak"))?;
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    CertificateIdentity::replace_key(path, &secrets::seal(&key, key_pem)?)?;
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<(), Error> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    if let Some(mode) = mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: Option<u32>) -> Result<(), Error> {
+    Ok(())
+}
+
+fn create_link(dest: &Path, target: &str) -> Result<(), Error> {
+    if fs::symlink_metadata(dest).is_ok() {
+        fs::remove_file(dest)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, dest)?;
+    #[cfg(not(unix))]
+    log::warn!("Skipping link {:?} -> {}", dest, target);
+    Ok(())
+}
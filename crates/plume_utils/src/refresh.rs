@@ -0,0 +1,431 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use plume_core::{
+    AnisetteConfiguration, CertificateIdentity, MobileProvision, developer::DeveloperSession,
+};
+use plume_store::{AccountStore, RefreshDevice};
+
+use crate::{Bundle, Device, Signer, SignerMode, SignerOptions};
+
+/// Devices the daemon may refresh on, keyed by UDID. Whoever owns the device
+/// listener keeps this up to date.
+pub type ConnectedDevices = Arc<Mutex<HashMap<String, Device>>>;
+
+struct RefreshGuard {
+    udid: String,
+    tasks: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            tasks.remove(&self.udid);
+            log::debug!("Released lock for device {}", self.udid);
+        }
+    }
+}
+
+/// Re-signs and reinstalls the apps in [`AccountStore::refreshes`] before their
+/// provisioning profiles expire, on whichever of their devices are connected.
+pub struct RefreshDaemon {
+    data_dir: PathBuf,
+    store_path: PathBuf,
+    connected_devices: ConnectedDevices,
+    active_tasks: Arc<Mutex<HashSet<String>>>,
+    check_interval: Duration,
+    notify: fn(&str),
+    describe: fn(&(dyn std::error::Error + 'static)) -> String,
+    on_certificate_reset: fn() -> bool,
+}
+
+impl RefreshDaemon {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            store_path: data_dir.join("accounts.json"),
+            data_dir,
+            connected_devices: Arc::new(Mutex::new(HashMap::new())),
+            active_tasks: Arc::new(Mutex::new(HashSet::new())),
+            check_interval: Duration::from_secs(60 * 3), // Check every 3 minutes
+            notify: |_| {},
+            describe: |e| e.to_string(),
+            on_certificate_reset: decline_certificate_reset,
+        }
+    }
+
+    /// Called with a short message when a refresh starts, finishes or fails.
+    pub fn with_notifier(mut self, notify: fn(&str)) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// Turns developer API errors into the text used in logs and notifications.
+    pub fn with_error_description(
+        mut self,
+        describe: fn(&(dyn std::error::Error + 'static)) -> String,
+    ) -> Self {
+        self.describe = describe;
+        self
+    }
+
+    /// Asked before revoking certificates to make room for a new one. Resets are
+    /// declined unless this is set, nobody is around to approve them otherwise.
+    pub fn with_certificate_reset(mut self, confirm: fn() -> bool) -> Self {
+        self.on_certificate_reset = confirm;
+        self
+    }
+
+    pub fn connected_devices(&self) -> ConnectedDevices {
+        self.connected_devices.clone()
+    }
+
+    /// Runs [`RefreshDaemon::run`] on its own thread and runtime.
+    pub fn spawn(self) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            rt.block_on(self.run())
+        })
+    }
+
+    /// Checks for due apps every few minutes, forever.
+    pub async fn run(&self) {
+        loop {
+            if let Err(e) = self.check_and_refresh().await {
+                log::error!("Refresh daemon error: {}", e);
+                (self.notify)(&format!("Failed to refresh: {}", e));
+            }
+
+            tokio::time::sleep(self.check_interval).await;
+        }
+    }
+
+    async fn check_and_refresh(&self) -> Result<(), String> {
+        let store = AccountStore::load(&Some(self.store_path.clone()))
+            .await
+            .map_err(|e| format!("Failed to load account store: {}", e))?;
+
+        let now = Utc::now();
+
+        for (udid, refresh_device) in store.refreshes() {
+            for app in &refresh_device.apps {
+                if app.scheduled_refresh <= now {
+                    // We check for active tasks here to prevent the background loop
+                    // from even starting a wait if a manual refresh is already running.
+                    if self.is_busy(udid) {
+                        log::info!(
+                            "Device {} is already being processed. Skipping this app for now.",
+                            udid
+                        );
+                        continue;
+                    }
+
+                    log::info!("App at {:?} needs refresh for device {}", app.path, udid);
+
+                    let device = self
+                        .connected_devices
+                        .lock()
+                        .ok()
+                        .and_then(|devices| devices.get(udid).cloned());
+
+                    let Some(device) = device else {
+                        log::debug!(
+                            "App at {:?} is due for refresh on {}, but no matching connected device was found. Retrying in {} seconds.",
+                            app.path,
+                            udid,
+                            self.check_interval.as_secs()
+                        );
+                        continue;
+                    };
+
+                    if let Err(e) = self.refresh_app(&store, refresh_device, app, &device).await {
+                        log::error!("Error refreshing app: {}", e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_busy(&self, udid: &str) -> bool {
+        self.active_tasks
+            .lock()
+            .map(|t| t.contains(udid))
+            .unwrap_or(false)
+    }
+
+    pub async fn refresh_app(
+        &self,
+        store: &AccountStore,
+        refresh_device: &RefreshDevice,
+        app: &plume_store::RefreshApp,
+        device: &Device,
+    ) -> Result<(), String> {
+        // Try to acquire the lock for this UDID.
+        {
+            let mut tasks = self
+                .active_tasks
+                .lock()
+                .map_err(|_| "Failed to lock task registry")?;
+            if tasks.contains(&device.udid) {
+                log::warn!(
+                    "Refresh already in progress for {}. Aborting duplicate.",
+                    device.udid
+                );
+                return Ok(());
+            }
+            tasks.insert(device.udid.clone());
+        }
+
+        // lock is released when this function returns
+        let _guard = RefreshGuard {
+            udid: device.udid.clone(),
+            tasks: self.active_tasks.clone(),
+        };
+
+        log::info!("Starting refresh for app at {:?}", app.path);
+
+        (self.notify)(&format!(
+            "Started refreshing {} for {}",
+            app.name.as_deref().unwrap_or("???"),
+            &refresh_device.name
+        ));
+
+        let account = store
+            .get_account(&refresh_device.account)
+            .ok_or_else(|| format!("Account {} not found", refresh_device.account))?;
+
+        let session = DeveloperSession::new(
+            account.adsid().clone(),
+            account.xcode_gs_token().clone(),
+            AnisetteConfiguration::default().set_configuration_path(self.data_dir.clone()),
+        )
+        .await
+        .map_err(|e| format!("Failed to create session: {}", (self.describe)(&e)))?;
+
+        let teams_response = session
+            .qh_list_teams()
+            .await
+            .map_err(|e| format!("Failed to list teams: {}", (self.describe)(&e)))?;
+
+        if teams_response.teams.is_empty() {
+            return Err("No teams available for this account".to_string());
+        }
+
+        let team_id = if account.team_id().is_empty() {
+            &teams_response.teams[0].team_id
+        } else {
+            account.team_id()
+        };
+
+        let identity_is_new = {
+            let mut on_certificate_reset = self.on_certificate_reset;
+            let identity = CertificateIdentity::new_with_session(
+                &session,
+                self.data_dir.clone(),
+                None,
+                team_id,
+                false,
+                Some(&mut on_certificate_reset),
+            )
+            .await
+            .map_err(|e| format!("Failed to create identity: {}", (self.describe)(&e)))?;
+            identity.new
+        };
+
+        let is_installed = if let Some(bundle_id) = app.bundle_id.as_deref() {
+            device
+                .is_app_installed(bundle_id)
+                .await
+                .map_err(|e| format!("Failed to check if app is installed: {}", e))?
+        } else {
+            false
+        };
+
+        // Determine if we need to reinstall:
+        // - Mac devices always need reinstalling
+        // - If the identity is new, we need to reinstall
+        // - If the app is not installed, we need to reinstall
+        // - If the app is installed and identity is not new, we can just update profiles
+        let needs_reinstall = device.is_mac || identity_is_new || !is_installed;
+
+        let created_app_ids = if needs_reinstall {
+            self.resign_and_reinstall(app, device, &session, team_id)
+                .await?
+        } else {
+            log::info!(
+                "Certificate exists and app is installed, updating provisioning profiles..."
+            );
+            self.update_provisioning_profiles(app, device, &session, team_id)
+                .await?
+        };
+
+        if let Err(e) = AccountStore::update(&self.store_path, |store| {
+            store.record_app_ids(team_id, &created_app_ids);
+            Ok(())
+        })
+        .await
+        {
+            log::error!("Failed to record created App IDs: {}", e);
+        }
+
+        self.update_refresh_schedule(refresh_device, app).await?;
+
+        log::info!("Successfully refreshed app at {:?}", app.path);
+
+        (self.notify)(&format!(
+            "Successfully refreshed {} for {}",
+            app.name.as_deref().unwrap_or("???"),
+            &refresh_device.name
+        ));
+
+        Ok(())
+    }
+
+    async fn resign_and_reinstall(
+        &self,
+        app: &plume_store::RefreshApp,
+        device: &Device,
+        session: &DeveloperSession,
+        team_id: &str,
+    ) -> Result<Vec<String>, String> {
+        let team_id_string = team_id.to_string();
+        session
+            .qh_ensure_device(&team_id_string, &device.name, &device.udid)
+            .await
+            .map_err(|e| format!("Failed to ensure device: {}", (self.describe)(&e)))?;
+
+        let bundle =
+            Bundle::new(app.path.clone()).map_err(|e| format!("Failed to create bundle: {}", e))?;
+
+        let options = SignerOptions {
+            mode: SignerMode::Pem,
+            ..Default::default()
+        };
+
+        let team_id_string = team_id.to_string();
+        let mut on_certificate_reset = self.on_certificate_reset;
+        let signing_identity = CertificateIdentity::new_with_session(
+            session,
+            self.data_dir.clone(),
+            None,
+            &team_id_string,
+            false,
+            Some(&mut on_certificate_reset),
+        )
+        .await
+        .map_err(|e| format!("Failed to create signing identity: {}", (self.describe)(&e)))?;
+
+        let mut signer = Signer::new(Some(signing_identity), options);
+
+        signer
+            .register_bundle(&bundle, session, &team_id.to_string(), true)
+            .await
+            .map_err(|e| format!("Failed to register bundle: {}", (self.describe)(&e)))?;
+
+        signer
+            .sign_bundle(&bundle)
+            .await
+            .map_err(|e| format!("Failed to sign bundle: {}", e))?;
+
+        if !device.is_mac {
+            device
+                .install_app(&app.path, |_| async {})
+                .await
+                .map_err(|e| format!("Failed to install app: {}", e))?;
+        } else {
+            crate::install_app_mac(&app.path)
+                .await
+                .map_err(|e| format!("Failed to install app on Mac: {}", e))?;
+        }
+
+        Ok(signer.created_app_ids)
+    }
+
+    async fn update_provisioning_profiles(
+        &self,
+        app: &plume_store::RefreshApp,
+        device: &Device,
+        session: &DeveloperSession,
+        team_id: &str,
+    ) -> Result<Vec<String>, String> {
+        let bundle =
+            Bundle::new(app.path.clone()).map_err(|e| format!("Failed to create bundle: {}", e))?;
+
+        let options = SignerOptions {
+            mode: SignerMode::Pem,
+            ..Default::default()
+        };
+
+        let mut signer = Signer::new(None, options);
+
+        signer
+            .register_bundle(&bundle, session, &team_id.to_string(), true)
+            .await
+            .map_err(|e| format!("Failed to register bundle: {}", (self.describe)(&e)))?;
+
+        for provision in &signer.provisioning_files {
+            device
+                .install_profile(provision)
+                .await
+                .map_err(|e| format!("Failed to install profile: {}", e))?;
+        }
+
+        Ok(signer.created_app_ids)
+    }
+
+    async fn update_refresh_schedule(
+        &self,
+        refresh_device: &RefreshDevice,
+        app: &plume_store::RefreshApp,
+    ) -> Result<(), String> {
+        let embedded_prov_path = app.path.join("embedded.mobileprovision");
+        if !embedded_prov_path.exists() {
+            return Err("embedded.mobileprovision not found".to_string());
+        }
+
+        let provision = MobileProvision::load_with_path(&embedded_prov_path)
+            .map_err(|e| format!("Failed to load mobile provision: {}", e))?;
+
+        let expiration_date = provision.expiration_date().clone();
+        let scheduled_refresh = expiration_date
+            .to_xml_format()
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap_or_else(|_| Utc::now() + chrono::Duration::days(4));
+        let scheduled_refresh = scheduled_refresh - chrono::Duration::days(3);
+
+        // The app may have been forgotten while it was refreshing, don't bring it back
+        AccountStore::update(&self.store_path, |store| {
+            if let Some(mut updated_device) =
+                store.get_refresh_device(&refresh_device.udid).cloned()
+            {
+                if let Some(existing_app) =
+                    updated_device.apps.iter_mut().find(|a| a.path == app.path)
+                {
+                    existing_app.scheduled_refresh = scheduled_refresh;
+                }
+                store.add_or_update_refresh_device(updated_device);
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| format!("Failed to update refresh schedule: {}", e))?;
+
+        log::info!("Next refresh scheduled for: {}", scheduled_refresh);
+
+        Ok(())
+    }
+}
+
+fn decline_certificate_reset() -> bool {
+    log::warn!("A certificate reset is needed to refresh, declining without confirmation");
+    false
+}
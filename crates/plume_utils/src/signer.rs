@@ -1,7 +1,10 @@
 // TODO: move to plist macro
 use futures::future::try_join_all;
 use plist::Value;
-use std::sync::Arc;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 use tokio::fs;
 
 use plume_core::{
@@ -9,21 +12,71 @@ use plume_core::{
     developer::DeveloperSession,
 };
 
-use crate::{Bundle, BundleType, Error, PlistInfoTrait, SignerApp, SignerMode, SignerOptions};
+use crate::{
+    Bundle, BundleType, Error, PlistInfoTrait, SignerApp, SignerMode, SignerOptions,
+    TweakDependencies, TweakPlans, TweakRepository,
+};
 
 pub struct Signer {
-    certificate: Option<CertificateIdentity>,
+    certificate: Option<Arc<CertificateIdentity>>,
     pub options: SignerOptions,
     pub provisioning_files: Vec<MobileProvision>,
+    /// App IDs `register_bundle` had to create, these count against the team's weekly limit.
+    pub created_app_ids: Vec<String>,
 }
 
 impl Signer {
     pub fn new(certificate: Option<CertificateIdentity>, options: SignerOptions) -> Self {
+        Self::new_shared(certificate.map(Arc::new), options)
+    }
+
+    /// Creates a signer that shares its identity with other signers, e.g. when batch signing.
+    pub fn new_shared(
+        certificate: Option<Arc<CertificateIdentity>>,
+        options: SignerOptions,
+    ) -> Self {
         Self {
             certificate,
             options,
             provisioning_files: Vec::new(),
+            created_app_ids: Vec::new(),
+        }
+    }
+
+    /// What `modify_bundle` would install from the configured tweaks, ElleKit included.
+    pub async fn plan_tweaks(&self) -> Result<TweakPlans, Error> {
+        if self.options.mode == SignerMode::None {
+            return Ok(TweakPlans::default());
+        }
+
+        let tweak_files = self.options.tweaks.as_deref().unwrap_or_default();
+        let dependencies = self.resolve_tweak_dependencies(tweak_files).await?;
+        let mut plans = Vec::new();
+
+        if self.options.features.support_ellekit || !tweak_files.is_empty() {
+            plans.push(crate::Tweak::plan_ellekit().await?);
         }
+
+        for tweak_file in dependencies.resolved.iter().chain(tweak_files) {
+            plans.push(crate::Tweak::plan(tweak_file).await?);
+        }
+
+        Ok(TweakPlans {
+            plans,
+            unresolved: dependencies.unresolved,
+        })
+    }
+
+    async fn resolve_tweak_dependencies(
+        &self,
+        tweak_files: &[PathBuf],
+    ) -> Result<TweakDependencies, Error> {
+        let repository = match &self.options.tweak_repository {
+            Some(dir) => TweakRepository::open(dir).await?,
+            None => TweakRepository::default(),
+        };
+
+        repository.resolve(tweak_files).await
     }
 
     pub async fn modify_bundle(
@@ -35,6 +88,15 @@ impl Signer {
             return Ok(());
         }
 
+        // Dropped first, an encrypted extension is no reason to refuse the app once it is gone.
+        self.remove_bundles(bundle).await?;
+
+        // Encrypted binaries sign fine but crash at launch, so refuse them up front.
+        let encrypted = bundle.encrypted_executables()?;
+        if !encrypted.is_empty() {
+            return Err(Error::EncryptedBinary { paths: encrypted });
+        }
+
         let bundles = bundle
             .collect_bundles_sorted()?
             .into_iter()
@@ -70,6 +132,25 @@ impl Signer {
             bundle.set_info_plist_key("CADisableMinimumFrameDurationOnPhone", true)?;
         }
 
+        // Tweak filters match the original identifiers, so inject before they get rewritten.
+        let has_tweaks = self.options.tweaks.as_ref().is_some_and(|t| !t.is_empty());
+
+        if self.options.features.support_ellekit || has_tweaks {
+            crate::Tweak::install_ellekit(&bundle).await?;
+        }
+
+        if let Some(tweak_files) = self.options.tweaks.as_ref() {
+            let dependencies = self.resolve_tweak_dependencies(tweak_files).await?;
+            for name in &dependencies.unresolved {
+                log::warn!("Unresolved tweak dependency: {}", name);
+            }
+
+            for tweak_file in dependencies.resolved.iter().chain(tweak_files) {
+                let tweak = crate::Tweak::new(tweak_file, bundle).await?;
+                tweak.apply().await?;
+            }
+        }
+
         let identifier = bundle.get_bundle_identifier();
 
         if self.options.mode != SignerMode::Adhoc && self.options.custom_identifier.is_none() {
@@ -190,19 +271,6 @@ impl Signer {
             bundle.set_info_plist_key("CFBundleIcons~ipad", cf_bundle_icons_ipad)?;
         }
 
-        let has_tweaks = self.options.tweaks.as_ref().is_some_and(|t| !t.is_empty());
-
-        if self.options.features.support_ellekit || has_tweaks {
-            crate::Tweak::install_ellekit(&bundle).await?;
-        }
-
-        if let Some(tweak_files) = self.options.tweaks.as_ref() {
-            for tweak_file in tweak_files {
-                let tweak = crate::Tweak::new(tweak_file, bundle).await?;
-                tweak.apply().await?;
-            }
-        }
-
         if self.options.features.support_liquid_glass {
             bundle.set_info_plist_key("UIDesignRequiresCompatibility", false)?;
 
@@ -222,6 +290,70 @@ impl Signer {
         Ok(())
     }
 
+    async fn remove_bundles(&self, bundle: &Bundle) -> Result<(), Error> {
+        let removals = &self.options.removals;
+        if removals.is_empty() {
+            return Ok(());
+        }
+
+        let removable = bundle.removable_bundles()?;
+        for path in &removals.bundles {
+            if !removable
+                .iter()
+                .any(|b| bundle.relative_path(b) == Some(path.as_path()))
+            {
+                log::warn!("Not a removable bundle: {}", path.display());
+            }
+        }
+
+        for removed in removable {
+            if bundle
+                .relative_path(&removed)
+                .is_some_and(|path| removals.matches(path))
+            {
+                log::info!("Removing {}", removed.bundle_dir().display());
+                fs::remove_dir_all(removed.bundle_dir()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bundles `register_bundle` creates an App ID for, without the ones `modify_bundle` removes.
+    pub fn app_id_bundles(&self, bundle: &Bundle) -> Result<Vec<Bundle>, Error> {
+        let removed = bundle
+            .removable_bundles()?
+            .into_iter()
+            .filter(|b| {
+                bundle
+                    .relative_path(b)
+                    .is_some_and(|path| self.options.removals.matches(path))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(bundle
+            .collect_bundles_sorted()?
+            .into_iter()
+            .filter(|b| {
+                matches!(b.bundle_type(), BundleType::App | BundleType::AppExtension)
+                    && (!self.options.embedding.single_profile
+                        || b.bundle_dir() == bundle.bundle_dir())
+                    && !removed
+                        .iter()
+                        .any(|r| b.bundle_dir().starts_with(r.bundle_dir()))
+            })
+            .collect())
+    }
+
+    /// How many App IDs signing `bundle` with an Apple ID uses up.
+    pub fn app_id_count(&self, bundle: &Bundle) -> Result<usize, Error> {
+        if self.options.mode != SignerMode::Pem {
+            return Ok(0);
+        }
+
+        Ok(self.app_id_bundles(bundle)?.len())
+    }
+
     pub async fn register_bundle(
         &mut self,
         bundle: &Bundle,
@@ -233,36 +365,24 @@ impl Signer {
             return Ok(());
         }
 
-        let bundles = bundle
-            .collect_bundles_sorted()?
-            .into_iter()
-            .filter(|b| b.bundle_type().should_have_entitlements())
-            .collect::<Vec<_>>();
+        let bundles = self.app_id_bundles(bundle)?;
         let signer_settings = &self.options;
 
         let bundle_arc = Arc::new(bundle.clone());
         let session_arc = Arc::new(session);
         let team_id_arc = Arc::new(team_id.clone());
+        // Collected outside the futures so creations are known even when a later one fails
+        let created_app_ids = Mutex::new(Vec::new());
+        let created_app_ids_ref = &created_app_ids;
 
-        let futures = bundles.iter().filter_map(|sub_bundle| {
+        let futures = bundles.iter().map(|sub_bundle| {
             let sub_bundle = sub_bundle.clone();
             let bundle = bundle_arc.clone();
             let session = session_arc.clone();
             let team_id = team_id_arc.clone();
             let signer_settings = signer_settings.clone();
 
-            if signer_settings.embedding.single_profile
-                && sub_bundle.bundle_dir() != bundle.bundle_dir()
-            {
-                return None;
-            }
-            if *sub_bundle.bundle_type() != BundleType::AppExtension
-                && *sub_bundle.bundle_type() != BundleType::App
-            {
-                return None;
-            }
-
-            Some(async move {
+            async move {
                 let bundle_executable_name = sub_bundle
                     .get_executable()
                     .ok_or_else(|| Error::Other("Failed to get bundle executable name.".into()))?;
@@ -276,12 +396,13 @@ impl Signer {
 
                 let name = sub_bundle.get_bundle_name().unwrap_or_else(|| id.clone());
 
-                session.qh_ensure_app_id(&team_id, &name, &id).await?;
-
-                let app_id_id = session
-                    .qh_get_app_id(&team_id, &id)
-                    .await?
-                    .ok_or_else(|| Error::Other("Failed to get ensured app ID.".into()))?;
+                let (app_id_id, created) = session.qh_ensure_app_id(&team_id, &name, &id).await?;
+                if created {
+                    let mut ids = created_app_ids_ref
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    ids.push(id.clone());
+                }
 
                 if let Some(e) = macho.entitlements().as_ref() {
                     session
@@ -349,11 +470,12 @@ impl Signer {
                 let mobile_provision =
                     MobileProvision::load_with_bytes(profile_data.as_ref().to_vec())?;
                 Ok::<_, Error>(mobile_provision)
-            })
+            }
         });
 
-        let provisionings: Vec<MobileProvision> = try_join_all(futures).await?;
-        self.provisioning_files = provisionings;
+        let provisionings = try_join_all(futures).await;
+        self.created_app_ids = created_app_ids.into_inner().unwrap_or_default();
+        self.provisioning_files = provisionings?;
 
         Ok(())
     }
@@ -365,7 +487,7 @@ impl Signer {
 
         let bundles = bundle.collect_bundles_sorted()?;
 
-        let settings = Self::build_base_settings(self.certificate.as_ref())?;
+        let settings = Self::build_base_settings(self.certificate.as_deref())?;
         let entitlements_xml = r#"<?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
 <plist version="1.0">
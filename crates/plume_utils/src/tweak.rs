@@ -3,12 +3,163 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use plume_core::MachO;
+use plume_core::{MachO, MachOExt};
+use serde::Serialize;
 use uuid::Uuid;
 
-use crate::{Bundle, Error, PlistInfoTrait, copy_dir_recursively};
+use crate::{Bundle, BundleType, DebControl, Error, PlistInfoTrait, copy_dir_recursively, deb};
 
 const ELLEKIT_BYTES: &[u8] = include_bytes!("./ellekit.deb");
+const SUBSTRATE_LOAD_PATH: &str = "@rpath/CydiaSubstrate.framework/CydiaSubstrate";
+/// Jailbreak prefix of rootless packages.
+const ROOTLESS_PREFIX: &str = "/var/jb";
+
+/// Everything a tweak installs into an app, worked out without modifying it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TweakPlan {
+    pub tweak: PathBuf,
+    /// Package metadata, for .deb tweaks.
+    pub control: Option<DebControl>,
+    pub items: Vec<TweakPlanItem>,
+}
+
+/// Plans for every tweak a signing run installs, dependencies first.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TweakPlans {
+    pub plans: Vec<TweakPlan>,
+    /// Dependencies that neither the tweaks nor the tweak repository provide.
+    pub unresolved: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TweakItemKind {
+    Dylib,
+    Framework,
+    Bundle,
+    Appex,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TweakPlanItem {
+    pub kind: TweakItemKind,
+    /// Path inside the tweak, relative to the package root for .deb files.
+    pub source: PathBuf,
+    /// Path relative to the app bundle.
+    pub destination: PathBuf,
+    /// Load command added to the main executable, if any.
+    pub load_command: Option<String>,
+    /// Whether a CydiaSubstrate reference gets rewritten to the bundled ElleKit.
+    pub rewrites_substrate: bool,
+    /// Jailbreak load paths of the copied binary that get rewritten to `@rpath`.
+    pub rewrites: Vec<LoadPathRewrite>,
+    /// MobileSubstrate filter shipped next to the dylib, if any.
+    pub filter: Option<TweakFilter>,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+/// A load path that only resolves on a jailbroken device and its `@rpath` equivalent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoadPathRewrite {
+    pub from: String,
+    pub to: String,
+}
+
+/// System frameworks every app loads, tweaks filtered on these are meant for all apps.
+const APP_FRAMEWORK_BUNDLES: &[&str] = &[
+    "com.apple.UIKit",
+    "com.apple.Foundation",
+    "com.apple.CoreFoundation",
+];
+
+/// The `Filter` dictionary of a MobileSubstrate dylib's sibling `.plist`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TweakFilter {
+    pub bundles: Vec<String>,
+    pub executables: Vec<String>,
+    pub classes: Vec<String>,
+    /// `Mode = Any`, a single matching category is enough instead of all of them.
+    pub any: bool,
+}
+
+impl TweakFilter {
+    fn load(plist_path: &Path) -> Option<Self> {
+        let value = plist::Value::from_file(plist_path).ok()?;
+        let filter = value.as_dictionary()?.get("Filter")?.as_dictionary()?;
+
+        let strings = |key: &str| -> Vec<String> {
+            filter
+                .get(key)
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_string().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Some(Self {
+            bundles: strings("Bundles"),
+            executables: strings("Executables"),
+            classes: strings("Classes"),
+            any: filter.get("Mode").and_then(|v| v.as_string()) == Some("Any"),
+        })
+    }
+
+    /// Whether the main app with this identifier and executable would load the tweak.
+    pub fn matches_app(&self, bundle_id: &str, executable: &str) -> bool {
+        self.evaluate(bundle_id, executable, true)
+    }
+
+    /// Whether an app extension would load the tweak, only when the filter names it.
+    pub fn matches_extension(&self, bundle_id: &str, executable: &str) -> bool {
+        self.evaluate(bundle_id, executable, false)
+    }
+
+    fn evaluate(&self, bundle_id: &str, executable: &str, is_app: bool) -> bool {
+        let mut results = Vec::new();
+
+        if !self.bundles.is_empty() {
+            results.push(self.bundles.iter().any(|b| {
+                b == bundle_id || (is_app && APP_FRAMEWORK_BUNDLES.contains(&b.as_str()))
+            }));
+        }
+
+        if !self.executables.is_empty() {
+            results.push(self.executables.iter().any(|e| e == executable));
+        }
+
+        // Classes are looked up at runtime, assume the app has them.
+        if is_app && !self.classes.is_empty() {
+            results.push(true);
+        }
+
+        if results.is_empty() {
+            return is_app;
+        }
+
+        if self.any {
+            results.into_iter().any(|m| m)
+        } else {
+            results.into_iter().all(|m| m)
+        }
+    }
+}
+
+/// A load command of the app or its extensions that resolves into the app's `Frameworks` directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct InjectedDylib {
+    /// Load command path, e.g. `@rpath/Tweak.dylib`.
+    pub load_path: String,
+    /// The dylib or `.framework` directory the load command points to.
+    pub path: PathBuf,
+    /// Executables with the load command, relative to the app bundle.
+    pub executables: Vec<PathBuf>,
+    /// Whether the binary links CydiaSubstrate, app frameworks never do so this marks tweaks.
+    pub uses_substrate: bool,
+}
 
 pub struct Tweak {
     path: PathBuf,
@@ -25,15 +176,184 @@ impl Tweak {
         tokio::fs::write(&deb_path, ELLEKIT_BYTES).await?;
 
         let tweak = Tweak::new(&deb_path, app_bundle).await?;
-        tweak.install_deb().await?;
+        tweak.apply().await?;
 
         tokio::fs::remove_dir_all(&stage_dir).await.ok();
 
         Ok(())
     }
 
+    /// Lists the `@rpath` load commands of the app and its extensions that point into
+    /// `Frameworks`, the executables [`Tweak::apply`] injects into.
+    pub fn list_injected(app_bundle: &Bundle) -> Result<Vec<InjectedDylib>, Error> {
+        let app_dir = app_bundle.bundle_dir();
+        let frameworks_dir = app_dir.join("Frameworks");
+
+        let mut injected: Vec<InjectedDylib> = Vec::new();
+        // The app first, then its extensions
+        for (_, executable_path) in Self::injectable_bundles(app_dir)?.into_iter().rev() {
+            let executable = executable_path
+                .strip_prefix(app_dir)
+                .unwrap_or(&executable_path)
+                .to_path_buf();
+            let macho = MachO::new(&executable_path)?;
+
+            for load_path in macho.slice()?.dylib_load_paths()? {
+                if let Some(item) = injected.iter_mut().find(|i| i.load_path == load_path) {
+                    item.executables.push(executable.clone());
+                    continue;
+                }
+
+                let Some(relative) = load_path.strip_prefix("@rpath/") else {
+                    continue;
+                };
+
+                let binary = frameworks_dir.join(relative);
+                if !binary.is_file() {
+                    continue;
+                }
+
+                let path = match relative.split_once('/') {
+                    Some((framework, _)) => frameworks_dir.join(framework),
+                    None => binary.clone(),
+                };
+
+                injected.push(InjectedDylib {
+                    load_path,
+                    path,
+                    executables: vec![executable.clone()],
+                    uses_substrate: Self::links_substrate(&binary),
+                });
+            }
+        }
+
+        Ok(injected)
+    }
+
+    /// Removes the given load commands from every executable that has them and deletes the
+    /// copied files once nothing loads them anymore.
+    ///
+    /// CydiaSubstrate (ElleKit) is removed along with the last tweak that links it, and kept
+    /// while any remaining tweak still needs it. Bundles and app extensions copied by a tweak
+    /// have no load command and are left in place. Returns what was removed, the bundle needs
+    /// to be signed again afterwards.
+    pub async fn uninstall(
+        app_bundle: &Bundle,
+        load_paths: &[String],
+    ) -> Result<Vec<InjectedDylib>, Error> {
+        let injected = Self::list_injected(app_bundle)?;
+
+        if let Some(missing) = load_paths
+            .iter()
+            .find(|p| !injected.iter().any(|i| &i.load_path == *p))
+        {
+            return Err(Error::TweakNotInjected(missing.clone()));
+        }
+
+        let (mut removed, remaining): (Vec<_>, Vec<_>) = injected
+            .into_iter()
+            .partition(|i| load_paths.contains(&i.load_path));
+
+        if remaining.iter().any(|i| i.uses_substrate) {
+            if let Some(index) = removed
+                .iter()
+                .position(|i| i.load_path == SUBSTRATE_LOAD_PATH)
+            {
+                log::warn!("Keeping CydiaSubstrate, remaining tweaks still link it");
+                removed.remove(index);
+            }
+        } else if removed.iter().any(|i| i.uses_substrate) {
+            removed.extend(
+                remaining
+                    .into_iter()
+                    .filter(|i| i.load_path == SUBSTRATE_LOAD_PATH),
+            );
+        }
+
+        for item in &removed {
+            for executable in &item.executables {
+                // Reparse every time, the load commands shift after each removal.
+                MachO::new(app_bundle.bundle_dir().join(executable))?
+                    .remove_dylib(&item.load_path)?;
+            }
+        }
+
+        // Another load path can still resolve to the same file
+        let still_loaded = Self::list_injected(app_bundle)?;
+        for item in &removed {
+            if still_loaded.iter().any(|i| i.path == item.path) {
+                continue;
+            }
+
+            if item.path.is_dir() {
+                tokio::fs::remove_dir_all(&item.path).await?;
+            } else if item.path.exists() {
+                tokio::fs::remove_file(&item.path).await?;
+            }
+        }
+
+        Ok(removed)
+    }
+
     pub async fn new<P: AsRef<Path>>(tweak_path: P, app_bundle: &Bundle) -> Result<Self, Error> {
         let path = tweak_path.as_ref();
+        Self::check_path(path)?;
+
+        let stage_dir = env::temp_dir().join(format!("plume_tweak_{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&stage_dir).await?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            app_bundle: app_bundle.bundle_dir().clone(),
+            stage_dir,
+        })
+    }
+
+    /// Works out what a tweak would install, without touching any app.
+    pub async fn plan<P: AsRef<Path>>(tweak_path: P) -> Result<TweakPlan, Error> {
+        let path = tweak_path.as_ref();
+        Self::check_path(path)?;
+
+        let stage_dir = env::temp_dir().join(format!("plume_tweak_{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&stage_dir).await?;
+
+        let plan = Self::prepare(path, &stage_dir).await;
+        tokio::fs::remove_dir_all(&stage_dir).await.ok();
+
+        plan
+    }
+
+    /// The plan for the bundled ElleKit, installed whenever tweaks are.
+    pub async fn plan_ellekit() -> Result<TweakPlan, Error> {
+        let stage_dir = env::temp_dir().join(format!("plume_ellekit_{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&stage_dir).await?;
+
+        let deb_path = stage_dir.join("ellekit.deb");
+        tokio::fs::write(&deb_path, ELLEKIT_BYTES).await?;
+
+        let plan = Self::plan(&deb_path).await;
+        tokio::fs::remove_dir_all(&stage_dir).await.ok();
+
+        // The staged path is gone by now, name the bundled package instead.
+        plan.map(|plan| TweakPlan {
+            tweak: PathBuf::from("ellekit.deb"),
+            ..plan
+        })
+    }
+
+    pub async fn apply(&self) -> Result<(), Error> {
+        let plan = Self::prepare(&self.path, &self.stage_dir).await?;
+
+        for item in &plan.items {
+            self.install_item(item).await?;
+        }
+
+        tokio::fs::remove_dir_all(&self.stage_dir).await.ok();
+
+        Ok(())
+    }
+
+    fn check_path(path: &Path) -> Result<(), Error> {
         if !path.exists() {
             return Err(Error::TweakInvalidPath);
         }
@@ -52,109 +372,53 @@ impl Tweak {
             return Err(Error::UnsupportedFileType(file_name.to_string()));
         }
 
-        let stage_dir = env::temp_dir().join(format!("plume_tweak_{}", Uuid::new_v4()));
-        tokio::fs::create_dir_all(&stage_dir).await?;
-
-        Ok(Self {
-            path: path.to_path_buf(),
-            app_bundle: app_bundle.bundle_dir().clone(),
-            stage_dir,
-        })
+        Ok(())
     }
 
-    pub async fn apply(&self) -> Result<(), Error> {
-        let file_name = self
-            .path
+    /// Extracts the tweak into `stage_dir` if needed and lists everything it installs.
+    async fn prepare(path: &Path, stage_dir: &Path) -> Result<TweakPlan, Error> {
+        let file_name = path
             .file_name()
             .and_then(|n| n.to_str())
             .ok_or(Error::TweakInvalidPath)?;
 
-        if file_name.ends_with(".deb") {
-            self.install_deb().await?;
+        let root = path.parent().unwrap_or(Path::new(""));
+        let mut control = None;
+        let items = if file_name.ends_with(".deb") {
+            let (extract_dir, deb_control) = Self::extract_deb(path, stage_dir).await?;
+            control = deb_control;
+            Self::scan_package(&extract_dir).await?
         } else if file_name.ends_with(".framework") {
-            self.install_framework(&self.path).await?;
+            vec![Self::plan_item(root, path, TweakItemKind::Framework)?]
         } else if file_name.ends_with(".bundle") {
-            self.install_bundle(&self.path).await?;
+            vec![Self::plan_item(root, path, TweakItemKind::Bundle)?]
         } else if file_name.ends_with(".appex") {
-            self.install_appex(&self.path).await?;
-        } else if file_name.ends_with(".dylib") {
-            self.install_dylib(&self.path).await?;
-        }
-
-        tokio::fs::remove_dir_all(&self.stage_dir).await.ok();
+            vec![Self::plan_item(root, path, TweakItemKind::Appex)?]
+        } else {
+            vec![Self::plan_item(root, path, TweakItemKind::Dylib)?]
+        };
 
-        Ok(())
+        Ok(TweakPlan {
+            tweak: path.to_path_buf(),
+            control,
+            items,
+        })
     }
 
-    async fn install_deb(&self) -> Result<(), Error> {
-        use decompress::ExtractOpts;
-
-        let extract_dir = self.stage_dir.join("deb_contents");
+    async fn extract_deb(
+        path: &Path,
+        stage_dir: &Path,
+    ) -> Result<(PathBuf, Option<DebControl>), Error> {
+        let extract_dir = stage_dir.join("deb_contents");
         tokio::fs::create_dir_all(&extract_dir).await?;
+        deb::extract_member(path, "data.tar", stage_dir, &extract_dir).await?;
 
-        let ar_extract_dir = self.stage_dir.join("ar_contents");
-        tokio::fs::create_dir_all(&ar_extract_dir).await?;
-
-        let ar_path_sync = self.path.clone();
-        let ar_extract_dir_sync = ar_extract_dir.clone();
-
-        tokio::task::spawn_blocking(move || {
-            decompress::decompress(
-                &ar_path_sync,
-                &ar_extract_dir_sync,
-                &ExtractOpts {
-                    strip: 0,
-                    detect_content: false,
-                    filter: Box::new(|_: &std::path::Path| true),
-                    map: Box::new(|p| std::borrow::Cow::Borrowed(p)),
-                },
-            )
-        })
-        .await
-        .ok()
-        .and_then(|r| r.ok())
-        .ok_or_else(|| Error::TweakExtractionFailed("Failed to extract .ar archive".to_string()))?;
-
-        for archive_name in [
-            "data.tar.lzma",
-            "data.tar.gz",
-            "data.tar.xz",
-            "data.tar.bz2",
-            "data.tar",
-        ] {
-            let data_path = ar_extract_dir.join(archive_name);
-            if data_path.exists() {
-                let extract_dir_sync = extract_dir.clone();
-                let data_path_sync = data_path.clone();
-
-                tokio::task::spawn_blocking(move || {
-                    decompress::decompress(
-                        &data_path_sync,
-                        &extract_dir_sync,
-                        &ExtractOpts {
-                            strip: 0,
-                            detect_content: false,
-                            filter: Box::new(|_: &std::path::Path| true),
-                            map: Box::new(|p| std::borrow::Cow::Borrowed(p)),
-                        },
-                    )
-                })
-                .await
-                .map_err(|e| {
-                    Error::TweakExtractionFailed(format!("Failed to extract data.tar: {}", e))
-                })?
-                .map_err(|e| {
-                    Error::TweakExtractionFailed(format!("Failed to extract data.tar: {}", e))
-                })?;
-
-                break;
-            }
-        }
+        let control = deb::extract_control(path, stage_dir).await?;
 
-        self.scan_and_install(&extract_dir).await
+        Ok((extract_dir, control))
     }
 
-    async fn scan_and_install(&self, root: &Path) -> Result<(), Error> {
+    async fn scan_package(root: &Path) -> Result<Vec<TweakPlanItem>, Error> {
         let search_paths = [
             "Library/MobileSubstrate/DynamicLibraries",
             "usr/lib",
@@ -166,20 +430,29 @@ impl Tweak {
             "var/jb/Library/Application Support",
         ];
 
+        let mut items = Vec::new();
         for search_path in search_paths {
             let dir = root.join(search_path);
             if dir.exists() {
-                self.scan_directory(&dir).await?;
+                Self::scan_directory(root, &dir, &mut items).await?;
             }
         }
 
-        Ok(())
+        Ok(items)
     }
 
-    async fn scan_directory(&self, dir: &Path) -> Result<(), Error> {
+    async fn scan_directory(
+        root: &Path,
+        dir: &Path,
+        items: &mut Vec<TweakPlanItem>,
+    ) -> Result<(), Error> {
         use futures::future::BoxFuture;
 
-        fn scan_recursive<'a>(tweak: &'a Tweak, dir: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        fn scan_recursive<'a>(
+            root: &'a Path,
+            dir: &'a Path,
+            items: &'a mut Vec<TweakPlanItem>,
+        ) -> BoxFuture<'a, Result<(), Error>> {
             Box::pin(async move {
                 let mut entries = tokio::fs::read_dir(dir).await?;
 
@@ -193,17 +466,21 @@ impl Tweak {
 
                     if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
                         if path.is_file() && name.ends_with(".dylib") {
-                            tweak.install_dylib(&path).await?;
+                            items.push(Tweak::plan_item(root, &path, TweakItemKind::Dylib)?);
                         } else if path.is_dir() {
                             if name.ends_with(".framework") {
-                                tweak.install_framework(&path).await?;
+                                items.push(Tweak::plan_item(
+                                    root,
+                                    &path,
+                                    TweakItemKind::Framework,
+                                )?);
                             } else if name.ends_with(".bundle") {
-                                tweak.install_bundle(&path).await?;
+                                items.push(Tweak::plan_item(root, &path, TweakItemKind::Bundle)?);
                             } else if name.ends_with(".appex") {
-                                tweak.install_appex(&path).await?;
+                                items.push(Tweak::plan_item(root, &path, TweakItemKind::Appex)?);
                             } else {
                                 // Recursively scan subdirectories
-                                scan_recursive(tweak, &path).await?;
+                                scan_recursive(root, &path, items).await?;
                             }
                         }
                     }
@@ -213,107 +490,333 @@ impl Tweak {
             })
         }
 
-        scan_recursive(self, dir).await
+        scan_recursive(root, dir, items).await
     }
 
-    async fn install_dylib(&self, dylib_path: &Path) -> Result<(), Error> {
-        let frameworks_dir = self.app_bundle.join("Frameworks");
-        tokio::fs::create_dir_all(&frameworks_dir).await?;
+    fn plan_item(root: &Path, path: &Path, kind: TweakItemKind) -> Result<TweakPlanItem, Error> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or(Error::TweakInvalidPath)?;
 
-        let dylib_name = dylib_path.file_name().ok_or(Error::TweakInvalidPath)?;
-        let dest = frameworks_dir.join(dylib_name);
+        let destination = match kind {
+            TweakItemKind::Dylib | TweakItemKind::Framework => Path::new("Frameworks").join(name),
+            TweakItemKind::Bundle => PathBuf::from(name),
+            TweakItemKind::Appex => Path::new("PlugIns").join(name),
+        };
 
-        tokio::fs::copy(dylib_path, &dest).await?;
+        // Only dylibs and framework executables get a load command, the main executable by default.
+        let binary = match kind {
+            TweakItemKind::Dylib => Some((path.to_path_buf(), format!("@rpath/{}", name))),
+            TweakItemKind::Framework => Bundle::new(path)
+                .ok()
+                .and_then(|bundle| bundle.get_executable())
+                .map(|exec| (path.join(&exec), format!("@rpath/{}/{}", name, exec)))
+                .filter(|(exec_path, _)| exec_path.exists()),
+            TweakItemKind::Bundle | TweakItemKind::Appex => None,
+        };
 
-        Self::patch_cydiasubstrate(&dest);
-        self.inject_dylib(&dest, false).await
+        let rewrites = binary
+            .as_ref()
+            .map(|(exec_path, _)| Self::rpath_rewrites(exec_path))
+            .unwrap_or_default();
+
+        Ok(TweakPlanItem {
+            kind,
+            source: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            destination,
+            rewrites_substrate: rewrites.iter().any(|r| r.to == SUBSTRATE_LOAD_PATH),
+            rewrites,
+            load_command: binary.map(|(_, load_command)| load_command),
+            filter: match kind {
+                TweakItemKind::Dylib => TweakFilter::load(&path.with_extension("plist")),
+                _ => None,
+            },
+            root: root.to_path_buf(),
+        })
     }
 
-    async fn install_framework(&self, framework_path: &Path) -> Result<(), Error> {
-        let frameworks_dir = self.app_bundle.join("Frameworks");
-        tokio::fs::create_dir_all(&frameworks_dir).await?;
+    async fn install_item(&self, item: &TweakPlanItem) -> Result<(), Error> {
+        let source = item.root.join(&item.source);
+        let dest = self.app_bundle.join(&item.destination);
 
-        let framework_name = framework_path.file_name().ok_or(Error::TweakInvalidPath)?;
-        let dest = frameworks_dir.join(framework_name);
+        let targets = match &item.filter {
+            Some(filter) => self.filter_targets(filter)?,
+            None => vec![Self::main_executable(&self.app_bundle)?],
+        };
+
+        if targets.is_empty() {
+            let filter = item.filter.as_ref().map(|f| {
+                let targets = f.bundles.iter().chain(&f.executables);
+                targets.cloned().collect::<Vec<_>>().join(", ")
+            });
+            log::warn!(
+                "Skipping {}, it is filtered for another app: {}",
+                item.source.display(),
+                filter.unwrap_or_default()
+            );
+            return Ok(());
+        }
 
-        copy_dir_recursively(&framework_path, &dest).await?;
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
 
-        if let Ok(bundle) = Bundle::new(&dest) {
-            if let Some(exec_name) = bundle.get_executable() {
-                let exec_path = dest.join(exec_name);
-                if exec_path.exists() {
-                    Self::patch_cydiasubstrate(&exec_path);
-                    self.inject_dylib(&exec_path, true).await?;
-                }
+        if item.kind == TweakItemKind::Dylib {
+            tokio::fs::copy(&source, &dest).await?;
+        } else {
+            copy_dir_recursively(&source, &dest).await?;
+        }
+
+        if let Some(load_command) = &item.load_command {
+            if !item.rewrites.is_empty() {
+                let binary = self
+                    .app_bundle
+                    .join("Frameworks")
+                    .join(load_command.trim_start_matches("@rpath/"));
+                Self::rewrite_load_paths(&binary, &item.rewrites);
+            }
+
+            for target in &targets {
+                Self::inject_load_command(target, load_command)?;
             }
         }
 
         Ok(())
     }
 
-    async fn install_bundle(&self, bundle_path: &Path) -> Result<(), Error> {
-        let bundle_name = bundle_path.file_name().ok_or(Error::TweakInvalidPath)?;
-        let dest = self.app_bundle.join(bundle_name);
+    /// Executables of the app and its extensions that a filtered dylib gets injected into.
+    fn filter_targets(&self, filter: &TweakFilter) -> Result<Vec<PathBuf>, Error> {
+        let mut targets = Vec::new();
+
+        for (bundle, executable_path) in Self::injectable_bundles(&self.app_bundle)? {
+            let is_app = bundle.bundle_dir() == &self.app_bundle;
+            let (Some(bundle_id), Some(executable)) =
+                (bundle.get_bundle_identifier(), bundle.get_executable())
+            else {
+                continue;
+            };
+
+            let matches = if is_app {
+                filter.matches_app(&bundle_id, &executable)
+            } else {
+                filter.matches_extension(&bundle_id, &executable)
+            };
+
+            if matches {
+                targets.push(executable_path);
+            }
+        }
 
-        copy_dir_recursively(bundle_path, &dest).await
+        Ok(targets)
     }
 
-    async fn install_appex(&self, appex_path: &Path) -> Result<(), Error> {
-        let plugins_dir = self.app_bundle.join("PlugIns");
-        tokio::fs::create_dir_all(&plugins_dir).await?;
+    /// The app and its extensions, with their executables, deepest bundles first.
+    fn injectable_bundles(app_bundle: &Path) -> Result<Vec<(Bundle, PathBuf)>, Error> {
+        let mut bundles = Vec::new();
+
+        for bundle in Bundle::new(app_bundle)?.collect_bundles_sorted()? {
+            let is_app = bundle.bundle_dir() == app_bundle;
+            if !is_app && *bundle.bundle_type() != BundleType::AppExtension {
+                continue;
+            }
+
+            let Some(executable) = bundle.get_executable() else {
+                continue;
+            };
 
-        let appex_name = appex_path.file_name().ok_or(Error::TweakInvalidPath)?;
-        let dest = plugins_dir.join(appex_name);
+            let executable_path = bundle.bundle_dir().join(executable);
+            if executable_path.exists() {
+                bundles.push((bundle, executable_path));
+            }
+        }
 
-        copy_dir_recursively(appex_path, &dest).await
+        Ok(bundles)
     }
 
-    async fn inject_dylib(&self, dylib_path: &Path, is_framework: bool) -> Result<(), Error> {
-        let bundle = Bundle::new(&self.app_bundle)?;
+    fn main_executable(app_bundle: &Path) -> Result<PathBuf, Error> {
+        let bundle = Bundle::new(app_bundle)?;
         let executable_name = bundle
             .get_executable()
             .ok_or(Error::BundleInfoPlistMissing)?;
 
-        let executable_path = self.app_bundle.join(&executable_name);
+        let executable_path = app_bundle.join(&executable_name);
         if !executable_path.exists() {
             return Err(Error::BundleInfoPlistMissing);
         }
 
-        let inject_path = if is_framework {
-            let components: Vec<_> = dylib_path.components().rev().take(2).collect();
-            format!(
-                "@rpath/{}/{}",
-                components[1]
-                    .as_os_str()
-                    .to_str()
-                    .ok_or(Error::TweakInvalidPath)?,
-                components[0]
-                    .as_os_str()
-                    .to_str()
-                    .ok_or(Error::TweakInvalidPath)?
-            )
-        } else {
-            let file_name = dylib_path
-                .file_name()
-                .and_then(|f| f.to_str())
-                .ok_or(Error::TweakInvalidPath)?;
-            format!("@rpath/{}", file_name)
+        Ok(executable_path)
+    }
+
+    fn links_substrate(binary_path: &Path) -> bool {
+        MachO::new(binary_path)
+            .and_then(|macho| macho.slice()?.dylib_load_paths())
+            .is_ok_and(|paths| {
+                paths.iter().any(|p| {
+                    p.ends_with("CydiaSubstrate.framework/CydiaSubstrate")
+                        || p.ends_with("libsubstrate.dylib")
+                })
+            })
+    }
+
+    /// Load paths of a tweak binary that point into the jailbreak, with their `@rpath` equivalents.
+    fn rpath_rewrites(binary_path: &Path) -> Vec<LoadPathRewrite> {
+        let paths = MachO::new(binary_path)
+            .and_then(|macho| macho.slice()?.dylib_load_paths())
+            .unwrap_or_default();
+
+        let mut rewrites: Vec<LoadPathRewrite> = Vec::new();
+        for from in paths {
+            if rewrites.iter().any(|r| r.from == from) {
+                continue;
+            }
+            if let Some(to) = Self::rpath_equivalent(&from) {
+                rewrites.push(LoadPathRewrite { from, to });
+            }
+        }
+
+        rewrites
+    }
+
+    /// Maps `/var/jb/...`, `@rpath/../var/jb/...` and `/Library/Frameworks/...` to where
+    /// the file ends up in the app, dylibs are copied flat into Frameworks.
+    fn rpath_equivalent(load_path: &str) -> Option<String> {
+        let rootless = load_path.strip_prefix("@rpath/..").unwrap_or(load_path);
+        let path = match rootless.strip_prefix(ROOTLESS_PREFIX) {
+            Some(path) if path.starts_with('/') => path,
+            _ if load_path.starts_with("/Library/Frameworks/") => load_path,
+            _ => return None,
+        };
+
+        // ElleKit only ships the framework, libsubstrate.dylib is a symlink to it.
+        if path.ends_with("CydiaSubstrate.framework/CydiaSubstrate")
+            || path.ends_with("libsubstrate.dylib")
+        {
+            return Some(SUBSTRATE_LOAD_PATH.to_string());
+        }
+
+        let name = match path.find(".framework/") {
+            Some(index) => &path[path[..index].rfind('/').map_or(0, |i| i + 1)..],
+            None => path.rsplit('/').next().filter(|name| !name.is_empty())?,
         };
 
-        let mut macho = MachO::new(&executable_path)?;
-        macho.add_dylib(&inject_path)?;
+        Some(format!("@rpath/{}", name))
+    }
+
+    fn inject_load_command(executable_path: &Path, load_command: &str) -> Result<(), Error> {
+        let mut macho = MachO::new(executable_path)?;
+        macho.add_dylib(load_command)?;
         macho.write_changes()?;
 
         Ok(())
     }
 
-    fn patch_cydiasubstrate(binary_path: &Path) {
-        if let Ok(mut macho) = MachO::new(binary_path) {
-            let _ = macho.replace_dylib(
-                "/Library/Frameworks/CydiaSubstrate.framework/CydiaSubstrate",
-                "@rpath/CydiaSubstrate.framework/CydiaSubstrate",
-            );
-            let _ = macho.write_changes();
+    fn rewrite_load_paths(binary_path: &Path, rewrites: &[LoadPathRewrite]) {
+        let mut macho = match MachO::new(binary_path) {
+            Ok(macho) => macho,
+            Err(e) => {
+                log::warn!("Failed to open {}: {}", binary_path.display(), e);
+                return;
+            }
+        };
+
+        for rewrite in rewrites {
+            // The new path has to fit into the existing load command.
+            match macho.replace_dylib(&rewrite.from, &rewrite.to) {
+                Ok(()) => log::info!(
+                    "Rewrote {} -> {} in {}",
+                    rewrite.from,
+                    rewrite.to,
+                    binary_path.display()
+                ),
+                Err(e) => log::warn!(
+                    "Failed to rewrite {} in {}: {}",
+                    rewrite.from,
+                    binary_path.display(),
+                    e
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use super::*;
+
+    // A thin arm64 Mach-O without load commands, with room to add some
+    fn macho() -> Vec<u8> {
+        let mut data = Vec::new();
+        for field in [0xfeed_facf_u32, 0x0100_000c, 0, 2, 0, 0, 0, 0] {
+            data.extend(field.to_le_bytes());
         }
+        data.resize(4096, 0);
+        data
+    }
+
+    fn write_plist(path: &Path, entries: &[(&str, Value)]) {
+        let dict = entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<Dictionary>();
+        Value::Dictionary(dict).to_file_xml(path).unwrap();
+    }
+
+    fn write_bundle(dir: &Path, identifier: &str, executable: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        write_plist(
+            &dir.join("Info.plist"),
+            &[
+                ("CFBundleIdentifier", identifier.into()),
+                ("CFBundleExecutable", executable.into()),
+            ],
+        );
+        std::fs::write(dir.join(executable), macho()).unwrap();
+    }
+
+    #[test]
+    fn uninstalls_tweak_injected_into_extension() {
+        let root = env::temp_dir().join(format!("plume_tweak_test_{}", Uuid::new_v4()));
+        let app = root.join("App.app");
+        write_bundle(&app, "com.example.app", "App");
+        write_bundle(
+            &app.join("PlugIns/Widget.appex"),
+            "com.example.app.widget",
+            "Widget",
+        );
+
+        // Filtered on the extension alone, the main executable is left alone
+        let tweak = root.join("Tweak.dylib");
+        std::fs::write(&tweak, macho()).unwrap();
+        let bundles = Value::Array(vec!["com.example.app.widget".into()]);
+        let filter = [("Bundles".to_string(), bundles)].into_iter().collect();
+        write_plist(
+            &root.join("Tweak.plist"),
+            &[("Filter", Value::Dictionary(filter))],
+        );
+
+        let bundle = Bundle::new(&app).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let removed = runtime.block_on(async {
+            Tweak::new(&tweak, &bundle).await?.apply().await?;
+
+            let injected = Tweak::list_injected(&bundle)?;
+            assert_eq!(injected.len(), 1);
+            assert_eq!(injected[0].load_path, "@rpath/Tweak.dylib");
+            assert_eq!(
+                injected[0].executables,
+                vec![PathBuf::from("PlugIns/Widget.appex/Widget")]
+            );
+
+            Tweak::uninstall(&bundle, &["@rpath/Tweak.dylib".to_string()]).await
+        });
+
+        assert_eq!(removed.unwrap().len(), 1);
+        assert!(Tweak::list_injected(&bundle).unwrap().is_empty());
+        assert!(!app.join("Frameworks/Tweak.dylib").exists());
+
+        std::fs::remove_dir_all(&root).ok();
     }
 }